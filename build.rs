@@ -28,10 +28,7 @@ fn process_wl_schema() {
         .add_root_schema(serde_json::from_str(&schema).unwrap())
         .unwrap();
 
-    let contents = format!(
-        "{}",
-        prettyplease::unparse(&syn::parse2(type_space.to_stream()).unwrap())
-    );
+    let contents = prettyplease::unparse(&syn::parse2(type_space.to_stream()).unwrap());
 
     let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
     fs::write(out_dir.join(WL_JSON_FILE), contents).unwrap();
@@ -0,0 +1,35 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Parses an arbitrary OTP dump.  The input is read as little-endian raw rows
+//! from USB_BOOT_FLAGS onwards, so the boot flags and USB_WHITE_LABEL_ADDR
+//! are under the fuzzer's control, and the rest of OTP is blank.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pico_otp::ecc;
+use pico_otp::whitelabel::{OTP_ROW_USB_BOOT_FLAGS, TOTAL_OTP_ROWS};
+use pico_otp::{OtpData, WhiteLabelStruct};
+
+fuzz_target!(|data: &[u8]| {
+    let mut raw = [0u32; TOTAL_OTP_ROWS];
+    for (row, word) in raw[OTP_ROW_USB_BOOT_FLAGS as usize..]
+        .iter_mut()
+        .zip(data.chunks_exact(4))
+    {
+        *row = u32::from_le_bytes([word[0], word[1], word[2], word[3]]) & 0x00ff_ffff;
+    }
+    let ecc_data: [u16; TOTAL_OTP_ROWS] = core::array::from_fn(|row| ecc::decode(raw[row]).data());
+
+    for strict in [false, true] {
+        let Ok(otp_data) = OtpData::from_full_otp_data(&raw, &ecc_data, strict) else {
+            continue;
+        };
+        let _ = otp_data.to_json();
+        if let Ok(wls) = WhiteLabelStruct::try_from(&otp_data) {
+            let _ = wls.to_otp_data_loose();
+        }
+    }
+});
@@ -0,0 +1,38 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Parses arbitrary USB_BOOT_FLAGS and white label rows.  The first 4 bytes
+//! are the boot flags, and the rest the rows, little-endian.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pico_otp::heapless::{DECODE_BUFFER_LEN, WhiteLabelFields};
+use pico_otp::{OtpData, WhiteLabelStruct};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((flags, rows)) = data.split_first_chunk::<4>() else {
+        return;
+    };
+    let usb_boot_flags = u32::from_le_bytes(*flags);
+    let rows: Vec<u16> = rows
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+
+    let mut buf = [0; DECODE_BUFFER_LEN];
+    let _ = WhiteLabelFields::decode(usb_boot_flags, &rows, &mut buf);
+
+    for strict in [false, true] {
+        let Ok(otp_data) = OtpData::from_white_label_data(usb_boot_flags, &rows, strict) else {
+            continue;
+        };
+        let _ = otp_data.to_json();
+        if let Ok(wls) = WhiteLabelStruct::try_from(&otp_data) {
+            let _ = wls.to_json();
+            let _ = wls.to_otp_data_loose();
+            let _ = wls.to_otp_data_strict();
+        }
+    }
+});
@@ -0,0 +1,34 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Parses arbitrary white label JSON.  Anything accepted must generate OTP
+//! data, which must parse back to the same white label data.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pico_otp::{OtpData, WhiteLabelStruct};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(json) = core::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(wls) = WhiteLabelStruct::from_json(json) else {
+        return;
+    };
+
+    let otp_data = wls
+        .to_otp_data_strict()
+        .expect("JSON accepted but OTP data not generated");
+    let parsed = OtpData::from_white_label_data(otp_data.usb_boot_flags(), otp_data.rows(), true)
+        .and_then(|otp_data| WhiteLabelStruct::try_from(&otp_data))
+        .expect("generated OTP data does not parse");
+    assert_eq!(parsed, wls);
+    assert_eq!(
+        wls.to_json().expect("JSON accepted but not regenerated"),
+        parsed
+            .to_json()
+            .expect("parsed OTP data not converted to JSON")
+    );
+});
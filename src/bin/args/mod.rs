@@ -3,6 +3,7 @@
 // MIT License
 
 use clap::{CommandFactory, Parser};
+use pico_otp::whitelabel::LayoutPolicy;
 
 /// Arguments for the pico-otp command line tool
 #[derive(Parser, Debug, Default)]
@@ -18,6 +19,55 @@ pub struct Args {
     #[clap(short = 'o', alias = "output", requires = "json_file")]
     pub otp_output_file: Option<String>,
 
+    /// OTP row index at which the white label data is to be written, used to
+    /// generate the programming plan.  Defaults to 0x100.
+    #[clap(
+        short = 'a',
+        alias = "address",
+        requires = "json_file",
+        value_parser = Args::parse_hex_row,
+        value_name = "0xHEXVAL",
+    )]
+    pub white_label_addr: Option<u16>,
+
+    /// Path to a full raw (non-ECC) OTP dump of the target device, 4 little
+    /// endian bytes per row.  If given, the white label data is placed in the
+    /// lowest suitable free region of OTP, rather than at a fixed row.
+    #[clap(
+        short = 'r',
+        alias = "raw-dump",
+        requires = "json_file",
+        conflicts_with = "white_label_addr"
+    )]
+    pub raw_otp_dump_file: Option<String>,
+
+    /// Allow the white label data to start at an odd OTP row.  Not
+    /// recommended, due to errata RP2350-E17.
+    #[clap(long = "allow-odd-address", requires = "json_file")]
+    pub allow_odd_address: bool,
+
+    /// How to place the white label strings: "field-order", as picotool
+    /// does, "auto" to place the longest last if field order doesn't fit, or
+    /// "compact" to also share rows between identical strings.  Defaults to
+    /// auto.
+    #[clap(
+        short = 'l',
+        alias = "layout",
+        requires = "json_file",
+        value_parser = Args::parse_layout,
+    )]
+    pub layout_policy: Option<LayoutPolicy>,
+
+    /// Follow picotool's encoding rules, placing strings in field order and
+    /// converting the bcd device revision using single precision floating
+    /// point.  Not yet verified against rows written by picotool.
+    #[clap(
+        long = "picotool",
+        requires = "json_file",
+        conflicts_with = "layout_policy"
+    )]
+    pub picotool: bool,
+
     /// Path to the OTP dump binary file.  Should contain only the ECC OTP rows
     /// that include the whitelabel data (that pointed to by USB_WHITE_LABEL_ADDR,
     /// 0x05c).
@@ -54,4 +104,20 @@ impl Args {
             Err("Value must start with 0x or 0X".to_string())
         }
     }
+
+    fn parse_layout(s: &str) -> Result<LayoutPolicy, String> {
+        match s {
+            "field-order" => Ok(LayoutPolicy::FieldOrder),
+            "auto" => Ok(LayoutPolicy::Auto),
+            "compact" => Ok(LayoutPolicy::Compact),
+            _ => Err(format!(
+                "Invalid layout: {s} - must be field-order, auto or compact"
+            )),
+        }
+    }
+
+    fn parse_hex_row(s: &str) -> Result<u16, String> {
+        let value = Self::parse_hex(s)?;
+        u16::try_from(value).map_err(|_| format!("Invalid OTP row: {s}"))
+    }
 }
@@ -3,11 +3,15 @@
 // MIT License
 
 use clap::Parser;
-use pico_otp::OtpData;
+use pico_otp::whitelabel::TOTAL_OTP_ROWS;
+use pico_otp::{OtpData, OtpImage, PageLocks, RegionStrategy, WhiteLabelStruct, region};
 
 mod args;
 use args::Args;
 
+// Default OTP row index for the white label data, if none is specified.
+const DEFAULT_WHITE_LABEL_ADDR: u16 = 0x100;
+
 fn main() {
     let args = Args::parse();
     std::process::exit(run(&args));
@@ -19,12 +23,12 @@ fn usage() {
 
 fn run(args: &Args) -> i32 {
     if args.json_file.is_some() {
-        return process_json(&args);
+        process_json(args)
     } else if args.otp_dump_file.is_some() {
-        return process_otp_dump(&args);
+        process_otp_dump(args)
     } else {
         usage();
-        return 1;
+        1
     }
 }
 
@@ -121,8 +125,15 @@ fn process_json(args: &Args) -> i32 {
     // Read the JSON file
     let json_content = std::fs::read_to_string(json_file).expect("Failed to read JSON file");
 
-    // Single step to turn it into OtpData
-    let otp_data = match OtpData::from_json(&json_content) {
+    // Turn it into OtpData, placing the strings as requested
+    let otp_data = if args.picotool {
+        OtpData::from_json_picotool(&json_content)
+    } else {
+        let layout_policy = args.layout_policy.unwrap_or_default();
+        WhiteLabelStruct::from_json_with_layout(&json_content, layout_policy)
+            .and_then(|wls| wls.to_otp_data_strict())
+    };
+    let otp_data = match otp_data {
         Ok(od) => {
             if output_file.is_none() {
                 println!("Parsed white label data from {json_file} successfully");
@@ -135,8 +146,27 @@ fn process_json(args: &Args) -> i32 {
         }
     };
 
-    // Get the OTP rows and boot flags
+    // Get the boot flags and programming plan
     let boot_flags = otp_data.usb_boot_flags();
+    let (white_label_addr, locks) = if let Some(raw_dump_file) = &args.raw_otp_dump_file {
+        match find_white_label_addr(raw_dump_file, otp_data.rows().len()) {
+            Some((addr, locks)) => (addr, locks),
+            None => return 1,
+        }
+    } else {
+        (
+            args.white_label_addr.unwrap_or(DEFAULT_WHITE_LABEL_ADDR),
+            None,
+        )
+    };
+    let plan =
+        match otp_data.programming_plan(white_label_addr, args.allow_odd_address, locks.as_ref()) {
+            Ok(plan) => plan,
+            Err(e) => {
+                eprintln!("Failed to generate programming plan: {e}");
+                return 1;
+            }
+        };
 
     if let Some(output_path) = output_file {
         // Get the bytes as a flat array of u8s, 2 for each ECC row
@@ -155,34 +185,68 @@ fn process_json(args: &Args) -> i32 {
         println!("USB boot flags: {boot_flags:#010X}");
         println!("-----");
         println!("To use this output to white label your RP2350:");
-        println!("  - Write the contents of the output to file to OTP memory as ECC rows");
-        println!("    starting at a known free OTP region, typically 0x100");
         println!("  - Write the offset you selected to OTP row 0x05c (USB_WHITE_LABEL_ADDR)");
+        println!("  - Write the contents of the output to file to OTP memory as ECC rows");
+        println!("    starting at a known free OTP region, such as {white_label_addr:#05X}");
         println!(
             "  - Write the USB boot flags {boot_flags:#010X} to OTP rows 0x059, 0x5a and 0x5b"
         );
         println!("    (USB_BOOT_FLAGS, USB_BOOT_FLAGS_R1 and USB_BOOT_FLAGS_R2)");
-        println!("    as raw (not ECC) data");
+        println!("    as raw (not ECC) data, setting WHITE_LABEL_ADDR_VALID (bit 22) last");
         println!("-----");
         println!(
             "PROCEED WITH CAUTION - WRITING TO OTP IS PERMANENT AND MAY IRREPARABLY BRICK YOUR DEVICE"
         );
     } else {
-        let otp_rows = otp_data.rows();
         println!("USB Boot Flags: {boot_flags:#010X}");
-        println!("Total OTP row count: {}", otp_rows.len());
+        println!("Total OTP row count: {}", otp_data.rows().len());
 
-        // Print OTP rows in hex as u16s
-        print!("OTP Rows - write at some offset, such as 0x100:\n");
-        for (ii, row) in otp_rows.iter().enumerate() {
-            let row_index = ii + 0x100;
-            println!("  {row_index:#05X}: {row:#06X}");
+        // Print the programming plan, one write per line
+        println!("OTP writes, in order, for white label data at {white_label_addr:#05X}:");
+        for write in plan.iter() {
+            println!("  {write}");
         }
     }
 
     0
 }
 
+// Finds the lowest suitable free region for the white label data in a raw
+// OTP dump, reporting any rejected regions to stderr.  Also returns the
+// dump's page locks, if they can be decoded.
+fn find_white_label_addr(raw_dump_file: &str, num_rows: usize) -> Option<(u16, Option<PageLocks>)> {
+    let raw_dump = match std::fs::read(raw_dump_file) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Failed to read raw OTP dump file: {e}");
+            return None;
+        }
+    };
+    if raw_dump.len() != TOTAL_OTP_ROWS * 4 {
+        eprintln!(
+            "Raw OTP dump file has an invalid length (must be {} bytes)",
+            TOTAL_OTP_ROWS * 4
+        );
+        return None;
+    }
+
+    let mut raw_rows = [0u32; TOTAL_OTP_ROWS];
+    for (row, chunk) in raw_rows.iter_mut().zip(raw_dump.chunks(4)) {
+        *row = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    let image = OtpImage::from_raw_data(&raw_rows);
+
+    let search = region::find_white_label_region(&image, num_rows, RegionStrategy::Lowest);
+    for rejected in search.rejected() {
+        eprintln!("Rejected {rejected}");
+    }
+    if search.address().is_none() {
+        eprintln!("No suitable free OTP region found for {num_rows} rows of white label data");
+    }
+    let locks = PageLocks::from_image(&image, false).ok();
+    search.address().map(|addr| (addr, locks))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
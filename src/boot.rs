@@ -0,0 +1,573 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Bootrom configuration stored in OTP.
+//!
+//! Alongside USB white labelling, the RP2350 bootrom reads its boot
+//! configuration from OTP page 1:
+//! - BOOT_FLAGS0 and BOOT_FLAGS1, each stored as three raw copies which are
+//!   voted on bit by bit - see [`BootFlags0`] and [`BootFlags1`].
+//! - FLASH_DEVINFO, FLASH_PARTITION_SLOT_SIZE, BOOTSEL_LED_CFG,
+//!   BOOTSEL_PLL_CFG and BOOTSEL_XOSC_CFG, each a single ECC row, only used
+//!   when enabled by the corresponding BOOT_FLAGS0 flag.
+//!
+//! The RP2350 has no dedicated OTP rows for boot retry behaviour - the
+//! closest settings, DOUBLE_TAP and DOUBLE_TAP_DELAY (entering BOOTSEL mode
+//! when RUN is pulsed twice), are part of BOOT_FLAGS1.
+//!
+//! [`BootConfig`] models all of these, with JSON import/export, generation of
+//! the OTP writes to program them, and decoding from a full OTP dump.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::ecc;
+use crate::flags::{BootFlags0, BootFlags1};
+use crate::plan::OtpWrite;
+use crate::vote;
+use crate::whitelabel::{EccCheckError, TOTAL_OTP_ROWS, check_ecc_rows};
+
+/// OTP row index for BOOT_FLAGS0
+pub const OTP_ROW_BOOT_FLAGS0: u16 = 0x048;
+/// OTP row index for BOOT_FLAGS0_R1
+pub const OTP_ROW_BOOT_FLAGS0_R1: u16 = 0x049;
+/// OTP row index for BOOT_FLAGS0_R2
+pub const OTP_ROW_BOOT_FLAGS0_R2: u16 = 0x04a;
+/// OTP row index for BOOT_FLAGS1
+pub const OTP_ROW_BOOT_FLAGS1: u16 = 0x04b;
+/// OTP row index for BOOT_FLAGS1_R1
+pub const OTP_ROW_BOOT_FLAGS1_R1: u16 = 0x04c;
+/// OTP row index for BOOT_FLAGS1_R2
+pub const OTP_ROW_BOOT_FLAGS1_R2: u16 = 0x04d;
+/// OTP row index for FLASH_DEVINFO
+pub const OTP_ROW_FLASH_DEVINFO: u16 = 0x054;
+/// OTP row index for FLASH_PARTITION_SLOT_SIZE
+pub const OTP_ROW_FLASH_PARTITION_SLOT_SIZE: u16 = 0x055;
+/// OTP row index for BOOTSEL_LED_CFG
+pub const OTP_ROW_BOOTSEL_LED_CFG: u16 = 0x056;
+/// OTP row index for BOOTSEL_PLL_CFG
+pub const OTP_ROW_BOOTSEL_PLL_CFG: u16 = 0x057;
+/// OTP row index for BOOTSEL_XOSC_CFG
+pub const OTP_ROW_BOOTSEL_XOSC_CFG: u16 = 0x058;
+
+/// Errors that can occur while decoding or encoding a [`BootConfig`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The JSON does not follow the boot configuration schema.
+    #[cfg(feature = "serde_json")]
+    Json(serde_json::Error),
+
+    /// The three copies of the raw flags row starting at the given OTP row,
+    /// such as BOOT_FLAGS0, do not all match.
+    NonMatchingBootFlags(u16),
+
+    /// A boot configuration value does not fit in its OTP row.  The String
+    /// contains details.
+    InvalidBootConfig(String),
+
+    /// The raw (non-ECC) value of the given OTP row contains more bit errors
+    /// than ECC can correct.
+    UncorrectableEcc(u16),
+
+    /// The raw (non-ECC) value of the given OTP row, once ECC decoded, does
+    /// not match the ECC value provided for the same row.
+    EccMismatch(u16),
+}
+
+#[cfg(feature = "serde_json")]
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl From<EccCheckError> for Error {
+    fn from(err: EccCheckError) -> Self {
+        match err {
+            EccCheckError::Uncorrectable(row) => Error::UncorrectableEcc(row),
+            EccCheckError::Mismatch(row) => Error::EccMismatch(row),
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            #[cfg(feature = "serde_json")]
+            Error::Json(e) => write!(f, "JSON error: {e}"),
+            Error::NonMatchingBootFlags(row) => {
+                write!(
+                    f,
+                    "The copies of the flags at OTP row {row:#05x} do not match"
+                )
+            }
+            Error::InvalidBootConfig(s) => write!(f, "Invalid boot configuration: {s}"),
+            Error::UncorrectableEcc(row) => {
+                write!(f, "Uncorrectable ECC error in OTP row {row:#05x}")
+            }
+            Error::EccMismatch(row) => {
+                write!(f, "Raw and ECC values of OTP row {row:#05x} do not match")
+            }
+        }
+    }
+}
+
+/// FLASH_DEVINFO - describes the attached flash devices.  Used when
+/// FLASH_DEVINFO_ENABLE is set in BOOT_FLAGS0.
+///
+/// Device sizes are encoded as 0 for no device, otherwise the size is
+/// 4 KiB << the value - see [`FlashDevInfo::size_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlashDevInfo {
+    /// CS0_SIZE - the size of the flash device on chip select 0 (4 bits).
+    #[serde(default)]
+    pub cs0_size: u8,
+
+    /// CS1_SIZE - the size of the flash device on chip select 1 (4 bits).
+    #[serde(default)]
+    pub cs1_size: u8,
+
+    /// D8H_ERASE_SUPPORTED - the flash supports the 64 KiB block erase
+    /// command, 0xd8.
+    #[serde(default)]
+    pub d8h_erase_supported: bool,
+
+    /// CS1_GPIO - the GPIO used for chip select 1 (6 bits).
+    #[serde(default)]
+    pub cs1_gpio: u8,
+}
+
+impl FlashDevInfo {
+    /// Decodes the FLASH_DEVINFO row.
+    pub fn from_row(row: u16) -> Self {
+        Self {
+            cs0_size: ((row >> 8) & 0xf) as u8,
+            cs1_size: ((row >> 12) & 0xf) as u8,
+            d8h_erase_supported: row & (1 << 7) != 0,
+            cs1_gpio: (row & 0x3f) as u8,
+        }
+    }
+
+    /// Returns the FLASH_DEVINFO row value.
+    pub fn to_row(&self) -> Result<u16, Error> {
+        check_field("cs0_size", self.cs0_size as u16, 4)?;
+        check_field("cs1_size", self.cs1_size as u16, 4)?;
+        check_field("cs1_gpio", self.cs1_gpio as u16, 6)?;
+        Ok(((self.cs1_size as u16) << 12)
+            | ((self.cs0_size as u16) << 8)
+            | ((self.d8h_erase_supported as u16) << 7)
+            | self.cs1_gpio as u16)
+    }
+
+    /// Returns the size in bytes of a flash device from its CS0_SIZE or
+    /// CS1_SIZE value.
+    pub fn size_bytes(size: u8) -> usize {
+        match size {
+            0 => 0,
+            size => 4096 << (size & 0xf),
+        }
+    }
+}
+
+/// BOOTSEL_LED_CFG - the BOOTSEL activity LED.  Used when ENABLE_BOOTSEL_LED
+/// is set in BOOT_FLAGS0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BootselLedCfg {
+    /// PIN - the GPIO the LED is connected to (6 bits).
+    #[serde(default)]
+    pub pin: u8,
+
+    /// ACTIVELOW - the LED is lit when the GPIO is low.
+    #[serde(default)]
+    pub active_low: bool,
+}
+
+impl BootselLedCfg {
+    /// Decodes the BOOTSEL_LED_CFG row.
+    pub fn from_row(row: u16) -> Self {
+        Self {
+            pin: (row & 0x3f) as u8,
+            active_low: row & (1 << 8) != 0,
+        }
+    }
+
+    /// Returns the BOOTSEL_LED_CFG row value.
+    pub fn to_row(&self) -> Result<u16, Error> {
+        check_field("pin", self.pin as u16, 6)?;
+        Ok(((self.active_low as u16) << 8) | self.pin as u16)
+    }
+}
+
+/// BOOTSEL_PLL_CFG - the USB PLL configuration used in BOOTSEL mode, for
+/// non-default crystal frequencies.  Used when
+/// ENABLE_BOOTSEL_NON_DEFAULT_PLL_XOSC_CFG is set in BOOT_FLAGS0.
+///
+/// Each field holds the value written to the corresponding PLL register
+/// field, as described in the datasheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BootselPllCfg {
+    /// FBDIV - the PLL feedback divider (9 bits).
+    #[serde(default)]
+    pub fbdiv: u16,
+
+    /// POSTDIV1 - the first PLL post divider (3 bits).
+    #[serde(default)]
+    pub postdiv1: u8,
+
+    /// POSTDIV2 - the second PLL post divider (3 bits).
+    #[serde(default)]
+    pub postdiv2: u8,
+
+    /// REFDIV - the PLL reference divider, less one (1 bit).
+    #[serde(default)]
+    pub refdiv: u8,
+}
+
+impl BootselPllCfg {
+    /// Decodes the BOOTSEL_PLL_CFG row.
+    pub fn from_row(row: u16) -> Self {
+        Self {
+            fbdiv: row & 0x1ff,
+            postdiv1: ((row >> 9) & 0x7) as u8,
+            postdiv2: ((row >> 12) & 0x7) as u8,
+            refdiv: (row >> 15) as u8,
+        }
+    }
+
+    /// Returns the BOOTSEL_PLL_CFG row value.
+    pub fn to_row(&self) -> Result<u16, Error> {
+        check_field("fbdiv", self.fbdiv, 9)?;
+        check_field("postdiv1", self.postdiv1 as u16, 3)?;
+        check_field("postdiv2", self.postdiv2 as u16, 3)?;
+        check_field("refdiv", self.refdiv as u16, 1)?;
+        Ok(((self.refdiv as u16) << 15)
+            | ((self.postdiv2 as u16) << 12)
+            | ((self.postdiv1 as u16) << 9)
+            | self.fbdiv)
+    }
+}
+
+/// BOOTSEL_XOSC_CFG - the crystal oscillator configuration used in BOOTSEL
+/// mode.  Used when ENABLE_BOOTSEL_NON_DEFAULT_PLL_XOSC_CFG is set in
+/// BOOT_FLAGS0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BootselXoscCfg {
+    /// STARTUP - the XOSC startup delay (14 bits).
+    #[serde(default)]
+    pub startup: u16,
+
+    /// RANGE - the XOSC frequency range (2 bits).
+    #[serde(default)]
+    pub range: u8,
+}
+
+impl BootselXoscCfg {
+    /// Decodes the BOOTSEL_XOSC_CFG row.
+    pub fn from_row(row: u16) -> Self {
+        Self {
+            startup: row & 0x3fff,
+            range: (row >> 14) as u8,
+        }
+    }
+
+    /// Returns the BOOTSEL_XOSC_CFG row value.
+    pub fn to_row(&self) -> Result<u16, Error> {
+        check_field("startup", self.startup, 14)?;
+        check_field("range", self.range as u16, 2)?;
+        Ok(((self.range as u16) << 14) | self.startup)
+    }
+}
+
+/// The bootrom configuration stored in OTP, other than USB white labelling.
+///
+/// The ECC configuration rows are None if blank.  Note that the bootrom only
+/// uses each of them if the corresponding BOOT_FLAGS0 flag is set, which is
+/// not done automatically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BootConfig {
+    /// BOOT_FLAGS0.
+    #[serde(default)]
+    pub boot_flags0: BootFlags0,
+
+    /// BOOT_FLAGS1.
+    #[serde(default)]
+    pub boot_flags1: BootFlags1,
+
+    /// FLASH_DEVINFO.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flash_devinfo: Option<FlashDevInfo>,
+
+    /// FLASH_PARTITION_SLOT_SIZE - the size of each partition table slot,
+    /// in 4 KiB sectors, less one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flash_partition_slot_size: Option<u16>,
+
+    /// BOOTSEL_LED_CFG.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootsel_led_cfg: Option<BootselLedCfg>,
+
+    /// BOOTSEL_PLL_CFG.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootsel_pll_cfg: Option<BootselPllCfg>,
+
+    /// BOOTSEL_XOSC_CFG.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootsel_xosc_cfg: Option<BootselXoscCfg>,
+}
+
+impl BootConfig {
+    #[cfg(feature = "serde_json")]
+    /// Creates a BootConfig from its JSON representation, checking every
+    /// field fits in its OTP row.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let config: Self = serde_json::from_str(json)?;
+        config.ecc_rows()?;
+        Ok(config)
+    }
+
+    #[cfg(feature = "serde_json")]
+    /// Returns a JSON representation of this boot configuration.
+    pub fn to_json(&self) -> Result<serde_json::Value, Error> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    /// Creates a BootConfig from a complete OTP dump, consisting of both
+    /// ECC and non-ECC data.
+    ///
+    /// Args:
+    /// - `non_ecc_data`: A reference to an array of 4096 u32 words containing
+    ///   the non-ECC OTP data dump.
+    /// - `ecc_data`: A reference to an array of 4096 u16 words containing
+    ///   the ECC OTP data dump.
+    /// - `strict`: If true, all three copies of BOOT_FLAGS0 and BOOT_FLAGS1
+    ///   must match, and the ECC configuration rows must ECC decode from
+    ///   `non_ecc_data` without an uncorrectable error, to the same value as
+    ///   provided in `ecc_data`.  If false, the flags are voted on bit by
+    ///   bit, as the bootrom does, and the ECC rows are used as provided.
+    ///
+    /// Returns:
+    /// - `Ok(BootConfig)`: The extracted boot configuration.
+    /// - `Err(Error)`: An error occurred while parsing the OTP data.
+    pub fn from_full_otp_data(
+        non_ecc_data: &[u32; TOTAL_OTP_ROWS],
+        ecc_data: &[u16; TOTAL_OTP_ROWS],
+        strict: bool,
+    ) -> Result<Self, Error> {
+        let voted = |row: u16| {
+            let row = row as usize;
+            let vote = vote::vote_2_of_3([
+                non_ecc_data[row],
+                non_ecc_data[row + 1],
+                non_ecc_data[row + 2],
+            ]);
+            if strict && !vote.is_unanimous() {
+                return Err(Error::NonMatchingBootFlags(row as u16));
+            }
+            Ok(vote.value())
+        };
+        let boot_flags0 = BootFlags0::from_bits_retain(voted(OTP_ROW_BOOT_FLAGS0)?);
+        let boot_flags1 = BootFlags1::from_bits_retain(voted(OTP_ROW_BOOT_FLAGS1)?);
+
+        check_ecc_rows(
+            non_ecc_data,
+            ecc_data,
+            OTP_ROW_FLASH_DEVINFO as usize,
+            (OTP_ROW_BOOTSEL_XOSC_CFG - OTP_ROW_FLASH_DEVINFO + 1) as usize,
+            strict,
+            &mut Vec::new(),
+        )?;
+        let ecc_row = |row: u16| Some(ecc_data[row as usize]).filter(|value| *value != 0);
+
+        Ok(Self {
+            boot_flags0,
+            boot_flags1,
+            flash_devinfo: ecc_row(OTP_ROW_FLASH_DEVINFO).map(FlashDevInfo::from_row),
+            flash_partition_slot_size: ecc_row(OTP_ROW_FLASH_PARTITION_SLOT_SIZE),
+            bootsel_led_cfg: ecc_row(OTP_ROW_BOOTSEL_LED_CFG).map(BootselLedCfg::from_row),
+            bootsel_pll_cfg: ecc_row(OTP_ROW_BOOTSEL_PLL_CFG).map(BootselPllCfg::from_row),
+            bootsel_xosc_cfg: ecc_row(OTP_ROW_BOOTSEL_XOSC_CFG).map(BootselXoscCfg::from_row),
+        })
+    }
+
+    /// Creates a BootConfig from a complete raw (non-ECC) OTP dump only.
+    /// The ECC rows are derived by ECC decoding each raw row.  See
+    /// [`from_full_otp_data`](`Self::from_full_otp_data`).
+    pub fn from_raw_otp_data(
+        raw_data: &[u32; TOTAL_OTP_ROWS],
+        strict: bool,
+    ) -> Result<Self, Error> {
+        let ecc_data: [u16; TOTAL_OTP_ROWS] =
+            core::array::from_fn(|row| ecc::decode(raw_data[row]).data());
+        Self::from_full_otp_data(raw_data, &ecc_data, strict)
+    }
+
+    /// Returns the ordered list of OTP writes required to program this boot
+    /// configuration into a blank device.
+    ///
+    /// The ECC configuration rows are written first, followed by the three
+    /// copies of BOOT_FLAGS1 and then BOOT_FLAGS0, so nothing is enabled
+    /// before the configuration it uses has been written.  Blank rows are
+    /// not written.
+    pub fn to_otp_writes(&self) -> Result<Vec<OtpWrite>, Error> {
+        let mut writes: Vec<OtpWrite> = self
+            .ecc_rows()?
+            .iter()
+            .filter(|(_, value)| *value != 0)
+            .map(|(row, value)| OtpWrite::ecc(*row, *value))
+            .collect();
+        if self.boot_flags1.bits() != 0 {
+            writes.extend(self.boot_flags1.to_otp_writes());
+        }
+        if self.boot_flags0.bits() != 0 {
+            writes.extend(self.boot_flags0.to_otp_writes());
+        }
+        Ok(writes)
+    }
+
+    // Returns the ECC configuration rows, as (row, value), blank if None.
+    fn ecc_rows(&self) -> Result<[(u16, u16); 5], Error> {
+        Ok([
+            (
+                OTP_ROW_FLASH_DEVINFO,
+                self.flash_devinfo
+                    .map(|c| c.to_row())
+                    .transpose()?
+                    .unwrap_or(0),
+            ),
+            (
+                OTP_ROW_FLASH_PARTITION_SLOT_SIZE,
+                self.flash_partition_slot_size.unwrap_or(0),
+            ),
+            (
+                OTP_ROW_BOOTSEL_LED_CFG,
+                self.bootsel_led_cfg
+                    .map(|c| c.to_row())
+                    .transpose()?
+                    .unwrap_or(0),
+            ),
+            (
+                OTP_ROW_BOOTSEL_PLL_CFG,
+                self.bootsel_pll_cfg
+                    .map(|c| c.to_row())
+                    .transpose()?
+                    .unwrap_or(0),
+            ),
+            (
+                OTP_ROW_BOOTSEL_XOSC_CFG,
+                self.bootsel_xosc_cfg
+                    .map(|c| c.to_row())
+                    .transpose()?
+                    .unwrap_or(0),
+            ),
+        ])
+    }
+}
+
+// Checks `value` fits in a field `bits` wide.
+fn check_field(name: &str, value: u16, bits: u32) -> Result<(), Error> {
+    if value >> bits != 0 {
+        return Err(Error::InvalidBootConfig(format!(
+            "{name} value {value} does not fit in {bits} bits"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+    use crate::image::OtpImage;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_boot_config_json() {
+        let json = r#"{
+            "boot_flags0": {
+                "flags": ["enable_bootsel_led", "flash_devinfo_enable", "disable_bootsel_usb_msd_ifc"]
+            },
+            "boot_flags1": { "double_tap": true, "double_tap_delay": 2 },
+            "flash_devinfo": { "cs0_size": 12, "d8h_erase_supported": true },
+            "bootsel_led_cfg": { "pin": 25 }
+        }"#;
+        let config = BootConfig::from_json(json).unwrap();
+        assert!(
+            config
+                .boot_flags0
+                .contains(BootFlags0::DISABLE_BOOTSEL_USB_MSD_IFC)
+        );
+        assert_eq!(config.boot_flags0.bits(), 0x0002_0024);
+        assert_eq!(config.boot_flags1.bits(), 0x000a_0000);
+        assert_eq!(config.flash_devinfo.unwrap().to_row().unwrap(), 0x0c80);
+        assert_eq!(FlashDevInfo::size_bytes(12), 16 * 1024 * 1024);
+        assert_eq!(config.bootsel_pll_cfg, None);
+
+        let round_trip = BootConfig::from_json(&config.to_json().unwrap().to_string()).unwrap();
+        assert_eq!(round_trip, config);
+
+        // Out of range values
+        assert!(matches!(
+            BootConfig::from_json(r#"{"bootsel_led_cfg": {"pin": 64}}"#),
+            Err(Error::InvalidBootConfig(_))
+        ));
+        assert!(BootConfig::from_json(r#"{"boot_flags0": {"flags": ["x"]}}"#).is_err());
+        assert!(BootConfig::from_json(r#"{"boot_flags1": {"key_valid": 16}}"#).is_err());
+    }
+
+    #[test]
+    fn test_boot_config_otp() {
+        let mut config = BootConfig {
+            boot_flags0: BootFlags0::ENABLE_BOOTSEL_NON_DEFAULT_PLL_XOSC_CFG
+                | BootFlags0::DISABLE_BOOTSEL_USB_PICOBOOT_IFC,
+            bootsel_pll_cfg: Some(BootselPllCfg {
+                fbdiv: 100,
+                postdiv1: 5,
+                postdiv2: 5,
+                refdiv: 0,
+            }),
+            bootsel_xosc_cfg: Some(BootselXoscCfg {
+                startup: 0x2000,
+                range: 0,
+            }),
+            ..Default::default()
+        };
+        config.boot_flags1.set_key_valid(0b0001);
+
+        let writes = config.to_otp_writes().unwrap();
+        assert_eq!(
+            writes,
+            [
+                OtpWrite::ecc(OTP_ROW_BOOTSEL_PLL_CFG, 0x5a64),
+                OtpWrite::ecc(OTP_ROW_BOOTSEL_XOSC_CFG, 0x2000),
+                OtpWrite::raw(OTP_ROW_BOOT_FLAGS1, 0x0000_0001),
+                OtpWrite::raw(OTP_ROW_BOOT_FLAGS1_R1, 0x0000_0001),
+                OtpWrite::raw(OTP_ROW_BOOT_FLAGS1_R2, 0x0000_0001),
+                OtpWrite::raw(OTP_ROW_BOOT_FLAGS0, 0x0004_0002),
+                OtpWrite::raw(OTP_ROW_BOOT_FLAGS0_R1, 0x0004_0002),
+                OtpWrite::raw(OTP_ROW_BOOT_FLAGS0_R2, 0x0004_0002),
+            ]
+        );
+
+        let mut image = OtpImage::new();
+        image.apply_plan(&writes).unwrap();
+        assert_eq!(image.boot_config(true).unwrap(), config);
+
+        // A partially programmed copy is voted away, except in strict mode
+        image
+            .write_raw(OTP_ROW_BOOT_FLAGS0_R2, 0x0004_0003)
+            .unwrap();
+        assert_eq!(image.boot_config(false).unwrap(), config);
+        assert!(matches!(
+            image.boot_config(true),
+            Err(Error::NonMatchingBootFlags(OTP_ROW_BOOT_FLAGS0))
+        ));
+    }
+}
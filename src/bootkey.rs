@@ -0,0 +1,487 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Secure boot key fingerprints (BOOTKEY0-3).
+//!
+//! The RP2350 bootrom verifies signed binaries against up to four boot key
+//! fingerprints, each the SHA-256 hash of a secp256k1 public key, stored in
+//! 16 ECC rows starting at BOOTKEYn_0 (0x080, 0x090, 0x0a0 and 0x0b0).
+//!
+//! Each key slot is only used if its bit is set in BOOT_FLAGS1's KEY_VALID
+//! field, and a key is revoked by setting its bit in KEY_INVALID - see
+//! [`BootFlags1`].  Secure boot itself is enabled separately, in CRIT1.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use sha2::{Digest, Sha256};
+
+use crate::boot::{OTP_ROW_BOOT_FLAGS1, OTP_ROW_BOOT_FLAGS1_R1, OTP_ROW_BOOT_FLAGS1_R2};
+use crate::ecc;
+use crate::flags::BootFlags1;
+use crate::image::OtpImage;
+use crate::plan::OtpWrite;
+use crate::vote;
+use crate::whitelabel::{EccCheckError, TOTAL_OTP_ROWS, check_ecc_rows};
+
+/// OTP row index for BOOTKEY0_0.  Each boot key fingerprint follows the
+/// previous one.
+pub const OTP_ROW_BOOTKEY0_0: u16 = 0x080;
+
+/// Number of ECC rows used by each boot key fingerprint.
+pub const BOOTKEY_ROWS: usize = 16;
+
+/// Number of boot key slots.
+pub const NUM_BOOTKEYS: usize = 4;
+
+// DER encoding of the secp256k1 curve OID, 1.3.132.0.10.
+const SECP256K1_OID: [u8; 7] = [0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x0a];
+
+/// Errors that can occur while decoding or planning boot keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// A secure boot public key could not be used.  The String contains
+    /// details.
+    InvalidBootKey(String),
+
+    /// The requested boot key change cannot be made to OTP.  The String
+    /// contains details.
+    BootKeyNotPossible(String),
+
+    /// The three copies of BOOT_FLAGS1, starting at the given OTP row, do not
+    /// all match.
+    NonMatchingBootFlags(u16),
+
+    /// The raw (non-ECC) value of the given OTP row contains more bit errors
+    /// than ECC can correct.
+    UncorrectableEcc(u16),
+
+    /// The raw (non-ECC) value of the given OTP row, once ECC decoded, does
+    /// not match the ECC value provided for the same row.
+    EccMismatch(u16),
+}
+
+impl From<EccCheckError> for Error {
+    fn from(err: EccCheckError) -> Self {
+        match err {
+            EccCheckError::Uncorrectable(row) => Error::UncorrectableEcc(row),
+            EccCheckError::Mismatch(row) => Error::EccMismatch(row),
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidBootKey(s) => write!(f, "Invalid boot key: {s}"),
+            Error::BootKeyNotPossible(s) => write!(f, "Boot key change not possible: {s}"),
+            Error::NonMatchingBootFlags(row) => {
+                write!(
+                    f,
+                    "The copies of the flags at OTP row {row:#05x} do not match"
+                )
+            }
+            Error::UncorrectableEcc(row) => {
+                write!(f, "Uncorrectable ECC error in OTP row {row:#05x}")
+            }
+            Error::EccMismatch(row) => {
+                write!(f, "Raw and ECC values of OTP row {row:#05x} do not match")
+            }
+        }
+    }
+}
+
+/// The SHA-256 fingerprint of a secure boot public key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BootKeyHash([u8; 32]);
+
+impl BootKeyHash {
+    /// Creates a fingerprint from a precomputed SHA-256 hash.
+    pub fn from_hash(hash: [u8; 32]) -> Self {
+        Self(hash)
+    }
+
+    /// Creates a fingerprint by hashing a secp256k1 public key.
+    ///
+    /// `key` is either the 65 byte SEC1 uncompressed encoding (0x04, then
+    /// the X and Y coordinates) or just the 64 bytes of the X and Y
+    /// coordinates.  The fingerprint is the SHA-256 hash of the X and Y
+    /// coordinates, as held in the SEC1 encoding.  Compressed keys are not
+    /// supported.
+    pub fn from_public_key(key: &[u8]) -> Result<Self, Error> {
+        let coordinates = match key {
+            [0x04, coordinates @ ..] if coordinates.len() == 64 => coordinates,
+            coordinates if coordinates.len() == 64 => coordinates,
+            [0x02 | 0x03, ..] if key.len() == 33 => {
+                return Err(Error::InvalidBootKey(
+                    "Compressed public keys are not supported".into(),
+                ));
+            }
+            _ => {
+                return Err(Error::InvalidBootKey(format!(
+                    "Public key is {} bytes - expected a 65 byte SEC1 uncompressed key",
+                    key.len()
+                )));
+            }
+        };
+        Ok(Self(Sha256::digest(coordinates).into()))
+    }
+
+    /// Creates a fingerprint from a PEM encoded secp256k1 public key
+    /// ("BEGIN PUBLIC KEY"), as produced by `openssl ec -pubout`.
+    pub fn from_pem(pem: &str) -> Result<Self, Error> {
+        let body: String = pem
+            .lines()
+            .map(str::trim)
+            .skip_while(|line| *line != "-----BEGIN PUBLIC KEY-----")
+            .skip(1)
+            .take_while(|line| *line != "-----END PUBLIC KEY-----")
+            .collect();
+        if body.is_empty() {
+            return Err(Error::InvalidBootKey("No PEM public key found".into()));
+        }
+        let der = base64_decode(&body)
+            .ok_or_else(|| Error::InvalidBootKey("Invalid PEM base64 data".into()))?;
+
+        if !der
+            .windows(SECP256K1_OID.len())
+            .any(|window| window == SECP256K1_OID)
+        {
+            return Err(Error::InvalidBootKey(
+                "PEM public key is not a secp256k1 key".into(),
+            ));
+        }
+
+        // The key itself is the final BIT STRING, holding the SEC1 encoding
+        let key_start = der.len().saturating_sub(65);
+        match der.get(key_start.saturating_sub(3)..key_start) {
+            Some([0x03, 0x42, 0x00]) => Self::from_public_key(&der[key_start..]),
+            _ => Err(Error::InvalidBootKey(
+                "PEM public key is not an uncompressed key".into(),
+            )),
+        }
+    }
+
+    /// Returns the SHA-256 hash.
+    pub fn bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Returns the 16 ECC row values holding this fingerprint.
+    pub fn rows(&self) -> [u16; BOOTKEY_ROWS] {
+        core::array::from_fn(|ii| u16::from_le_bytes([self.0[2 * ii], self.0[2 * ii + 1]]))
+    }
+
+    /// Creates a fingerprint from its 16 ECC row values.
+    pub fn from_rows(rows: &[u16; BOOTKEY_ROWS]) -> Self {
+        let mut hash = [0u8; 32];
+        for (ii, row) in rows.iter().enumerate() {
+            hash[2 * ii..2 * ii + 2].copy_from_slice(&row.to_le_bytes());
+        }
+        Self(hash)
+    }
+}
+
+impl core::fmt::Display for BootKeyHash {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+/// The state of a boot key slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootKeyState {
+    /// No fingerprint has been written, and the slot is not marked valid.
+    Blank,
+
+    /// A fingerprint has been written, but the slot is not marked valid, so
+    /// is not used.
+    Programmed,
+
+    /// The slot is marked valid in KEY_VALID, and not revoked.
+    Valid,
+
+    /// The slot is marked invalid in KEY_INVALID.  This overrides KEY_VALID.
+    Revoked,
+}
+
+impl core::fmt::Display for BootKeyState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BootKeyState::Blank => write!(f, "blank"),
+            BootKeyState::Programmed => write!(f, "programmed, not valid"),
+            BootKeyState::Valid => write!(f, "valid"),
+            BootKeyState::Revoked => write!(f, "revoked"),
+        }
+    }
+}
+
+/// The boot key fingerprints and their state, as decoded from OTP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootKeys {
+    hashes: [Option<BootKeyHash>; NUM_BOOTKEYS],
+    boot_flags1: BootFlags1,
+}
+
+impl BootKeys {
+    /// Decodes the boot key fingerprints and BOOT_FLAGS1 from a complete OTP
+    /// dump, consisting of both ECC and non-ECC data.
+    ///
+    /// If `strict` is true, all three copies of BOOT_FLAGS1 must match, and
+    /// the fingerprint rows must ECC decode from `non_ecc_data` without an
+    /// uncorrectable error, to the same value as provided in `ecc_data`.
+    /// Otherwise BOOT_FLAGS1 is voted on bit by bit, as the bootrom does.
+    pub fn from_full_otp_data(
+        non_ecc_data: &[u32; TOTAL_OTP_ROWS],
+        ecc_data: &[u16; TOTAL_OTP_ROWS],
+        strict: bool,
+    ) -> Result<Self, Error> {
+        let vote = vote::vote_2_of_3([
+            non_ecc_data[OTP_ROW_BOOT_FLAGS1 as usize],
+            non_ecc_data[OTP_ROW_BOOT_FLAGS1_R1 as usize],
+            non_ecc_data[OTP_ROW_BOOT_FLAGS1_R2 as usize],
+        ]);
+        if strict && !vote.is_unanimous() {
+            return Err(Error::NonMatchingBootFlags(OTP_ROW_BOOT_FLAGS1));
+        }
+
+        check_ecc_rows(
+            non_ecc_data,
+            ecc_data,
+            OTP_ROW_BOOTKEY0_0 as usize,
+            NUM_BOOTKEYS * BOOTKEY_ROWS,
+            strict,
+            &mut Vec::new(),
+        )?;
+        let hashes = core::array::from_fn(|slot| {
+            let start = key_row(slot) as usize;
+            let rows: &[u16; BOOTKEY_ROWS] = ecc_data[start..start + BOOTKEY_ROWS]
+                .try_into()
+                .expect("Slice is BOOTKEY_ROWS long");
+            rows.iter()
+                .any(|row| *row != 0)
+                .then(|| BootKeyHash::from_rows(rows))
+        });
+
+        Ok(Self {
+            hashes,
+            boot_flags1: BootFlags1::from(&vote),
+        })
+    }
+
+    /// Decodes the boot keys from a complete raw (non-ECC) OTP dump only.
+    /// See [`from_full_otp_data`](`Self::from_full_otp_data`).
+    pub fn from_raw_otp_data(
+        raw_data: &[u32; TOTAL_OTP_ROWS],
+        strict: bool,
+    ) -> Result<Self, Error> {
+        let ecc_data: [u16; TOTAL_OTP_ROWS] =
+            core::array::from_fn(|row| ecc::decode(raw_data[row]).data());
+        Self::from_full_otp_data(raw_data, &ecc_data, strict)
+    }
+
+    /// Returns the fingerprint in the given slot (0-3), if written.
+    pub fn hash(&self, slot: usize) -> Option<&BootKeyHash> {
+        self.hashes.get(slot).and_then(Option::as_ref)
+    }
+
+    /// Returns the state of the given slot (0-3).
+    pub fn state(&self, slot: usize) -> BootKeyState {
+        let bit = 1 << slot;
+        if self.boot_flags1.key_invalid() & bit != 0 {
+            BootKeyState::Revoked
+        } else if self.boot_flags1.key_valid() & bit != 0 {
+            BootKeyState::Valid
+        } else if self.hash(slot).is_some() {
+            BootKeyState::Programmed
+        } else {
+            BootKeyState::Blank
+        }
+    }
+}
+
+/// Returns the ordered list of OTP writes required to store each of `keys`,
+/// as (slot, fingerprint), in `image`, mark them as valid, and revoke the
+/// slots set in the `revoke` bitmask.
+///
+/// The fingerprints are written first, followed by any copies of
+/// BOOT_FLAGS1 which change.  Existing BOOT_FLAGS1 bits are preserved.
+/// Returns [`Error::BootKeyNotPossible`] if a slot already holds a
+/// different fingerprint, or a key is to be stored in a revoked slot.
+pub fn plan_boot_keys(
+    image: &OtpImage,
+    keys: &[(usize, BootKeyHash)],
+    revoke: u8,
+) -> Result<Vec<OtpWrite>, Error> {
+    let current = BootKeys::from_raw_otp_data(
+        image
+            .raw_rows()
+            .try_into()
+            .expect("OtpImage always holds TOTAL_OTP_ROWS rows"),
+        false,
+    )?;
+    if revoke >> NUM_BOOTKEYS != 0 {
+        return Err(Error::BootKeyNotPossible(format!(
+            "No such boot key slots: {revoke:#x}"
+        )));
+    }
+
+    let mut writes = Vec::new();
+    let mut key_valid = 0;
+    for (slot, hash) in keys {
+        if *slot >= NUM_BOOTKEYS {
+            return Err(Error::BootKeyNotPossible(format!(
+                "No such boot key slot {slot}"
+            )));
+        }
+        if current.state(*slot) == BootKeyState::Revoked || revoke & (1 << slot) != 0 {
+            return Err(Error::BootKeyNotPossible(format!(
+                "Boot key slot {slot} is revoked"
+            )));
+        }
+        match current.hash(*slot) {
+            Some(existing) if existing != hash => {
+                return Err(Error::BootKeyNotPossible(format!(
+                    "Boot key slot {slot} already holds fingerprint {existing}"
+                )));
+            }
+            Some(_) => {}
+            None => writes.extend(
+                hash.rows()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, value)| **value != 0)
+                    .map(|(ii, value)| OtpWrite::ecc(key_row(*slot) + ii as u16, *value)),
+            ),
+        }
+        key_valid |= 1 << slot;
+    }
+
+    let mut new_flags = BootFlags1::empty();
+    new_flags.set_key_valid(key_valid);
+    new_flags.set_key_invalid(revoke);
+    for row in [
+        OTP_ROW_BOOT_FLAGS1,
+        OTP_ROW_BOOT_FLAGS1_R1,
+        OTP_ROW_BOOT_FLAGS1_R2,
+    ] {
+        let copy = image.read_raw(row).unwrap_or(0);
+        let value = copy | new_flags.bits();
+        if value != copy {
+            writes.push(OtpWrite::raw(row, value));
+        }
+    }
+
+    Ok(writes)
+}
+
+// Returns the first row of the given boot key slot.
+fn key_row(slot: usize) -> u16 {
+    OTP_ROW_BOOTKEY0_0 + (slot * BOOTKEY_ROWS) as u16
+}
+
+// Decodes standard base64, ignoring padding.
+fn base64_decode(data: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for ch in data.trim_end_matches('=').bytes() {
+        let value = match ch {
+            b'A'..=b'Z' => ch - b'A',
+            b'a'..=b'z' => ch - b'a' + 26,
+            b'0'..=b'9' => ch - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    // secp256k1 generator point, used as a public key
+    const KEY_PEM: &str = "-----BEGIN PUBLIC KEY-----
+MFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAEeb5mfvncu6xVoGKVzocLBwKb/NstzijZ
+WfKBWxb4F5hIOtp3JqPEZV2k+/wOEQio/Re0SKaFVBmcR9CP+xDUuA==
+-----END PUBLIC KEY-----";
+
+    #[test]
+    fn test_boot_key_hash() {
+        let from_pem = BootKeyHash::from_pem(KEY_PEM).unwrap();
+        assert_eq!(
+            from_pem.to_string(),
+            "09c0b2d1a486c439a87bcba6b46a7a1a23f3897cc83a94521a96da5c23bc58db"
+        );
+
+        let der = base64_decode(&KEY_PEM.lines().skip(1).take(2).collect::<String>()).unwrap();
+        let sec1 = &der[der.len() - 65..];
+        assert_eq!(BootKeyHash::from_public_key(sec1).unwrap(), from_pem);
+        assert_eq!(BootKeyHash::from_public_key(&sec1[1..]).unwrap(), from_pem);
+        assert!(BootKeyHash::from_public_key(&sec1[..33]).is_err());
+        assert!(BootKeyHash::from_pem("not a key").is_err());
+
+        let rows = from_pem.rows();
+        assert_eq!(rows[0], 0xc009);
+        assert_eq!(rows[15], 0xdb58);
+        assert_eq!(BootKeyHash::from_rows(&rows), from_pem);
+    }
+
+    #[test]
+    fn test_plan_boot_keys() {
+        let mut image = OtpImage::new();
+        let key = BootKeyHash::from_pem(KEY_PEM).unwrap();
+        let other = BootKeyHash::from_hash([0x5a; 32]);
+
+        let writes = plan_boot_keys(&image, &[(1, key)], 0).unwrap();
+        assert_eq!(writes.len(), BOOTKEY_ROWS + 3);
+        assert_eq!(writes[0], OtpWrite::ecc(0x090, key.rows()[0]));
+        assert_eq!(
+            writes[BOOTKEY_ROWS],
+            OtpWrite::raw(OTP_ROW_BOOT_FLAGS1, 0x2)
+        );
+        image.apply_plan(&writes).unwrap();
+
+        let keys = BootKeys::from_raw_otp_data(image.raw_rows().try_into().unwrap(), true).unwrap();
+        assert_eq!(keys.hash(1), Some(&key));
+        assert_eq!(keys.state(0), BootKeyState::Blank);
+        assert_eq!(keys.state(1), BootKeyState::Valid);
+
+        // Adding a second key keeps the first valid
+        let writes = plan_boot_keys(&image, &[(1, key), (2, other)], 0).unwrap();
+        assert_eq!(
+            writes[BOOTKEY_ROWS],
+            OtpWrite::raw(OTP_ROW_BOOT_FLAGS1, 0x6)
+        );
+        image.apply_plan(&writes).unwrap();
+
+        // Revoke the first
+        let writes = plan_boot_keys(&image, &[], 0b0010).unwrap();
+        assert_eq!(writes[0], OtpWrite::raw(OTP_ROW_BOOT_FLAGS1, 0x206));
+        image.apply_plan(&writes).unwrap();
+        let keys = BootKeys::from_raw_otp_data(image.raw_rows().try_into().unwrap(), true).unwrap();
+        assert_eq!(keys.state(1), BootKeyState::Revoked);
+        assert_eq!(keys.state(2), BootKeyState::Valid);
+
+        // Impossible changes
+        assert!(plan_boot_keys(&image, &[(2, key)], 0).is_err());
+        assert!(plan_boot_keys(&image, &[(1, key)], 0).is_err());
+        assert!(plan_boot_keys(&image, &[(4, key)], 0).is_err());
+
+        // A written but unused key
+        image.apply_plan(&[OtpWrite::ecc(0x0b0, 0x1234)]).unwrap();
+        let keys =
+            BootKeys::from_raw_otp_data(image.raw_rows().try_into().unwrap(), false).unwrap();
+        assert_eq!(keys.state(3), BootKeyState::Programmed);
+    }
+}
@@ -0,0 +1,493 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Critical flags, CRIT0 and CRIT1.
+//!
+//! These control secure boot, debug and which processor architectures may
+//! run.  Each is stored in eight raw rows - CRIT0 at 0x038-0x03f and CRIT1 at
+//! 0x040-0x047 - and a bit is treated as set if it is set in at least three
+//! of the copies.  See [`vote_3_of_8`](crate::vote::vote_3_of_8).
+//!
+//! Setting these flags is irreversible, and can leave a device unable to
+//! boot or be debugged, so generating the writes to do so requires an
+//! [`IrreversibleAck`].
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::image::OtpImage;
+use crate::plan::OtpWrite;
+use crate::vote::{self, CritBitDisagreement};
+use crate::whitelabel::TOTAL_OTP_ROWS;
+
+/// OTP row index for CRIT0.  CRIT0_R1 to CRIT0_R7 follow it.
+pub const OTP_ROW_CRIT0: u16 = 0x038;
+/// OTP row index for CRIT1.  CRIT1_R1 to CRIT1_R7 follow it.
+pub const OTP_ROW_CRIT1: u16 = 0x040;
+/// Number of copies of each critical flags row.
+pub const CRIT_COPIES: usize = 8;
+
+/// Errors that can occur while decoding or planning the critical flags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The eight copies of the critical flags starting at the given OTP row
+    /// (CRIT0 or CRIT1) do not all match.
+    NonMatchingCritFlags(u16),
+
+    /// The requested critical flags cannot be written to OTP.  The String
+    /// contains details.
+    CritFlagsNotPossible(String),
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::NonMatchingCritFlags(row) => {
+                write!(
+                    f,
+                    "The copies of the critical flags at OTP row {row:#05x} do not match"
+                )
+            }
+            Error::CritFlagsNotPossible(s) => write!(f, "Critical flags change not possible: {s}"),
+        }
+    }
+}
+
+/// CRIT0 - the processor architectures which are permanently disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Crit0 {
+    /// ARM_DISABLE - the Arm cores are disabled.
+    pub arm_disable: bool,
+
+    /// RISCV_DISABLE - the RISC-V cores are disabled.
+    pub riscv_disable: bool,
+}
+
+impl Crit0 {
+    /// Decodes the CRIT0 value.
+    pub fn from_bits(bits: u32) -> Self {
+        Self {
+            arm_disable: bits & (1 << 0) != 0,
+            riscv_disable: bits & (1 << 1) != 0,
+        }
+    }
+
+    /// Returns the CRIT0 value.
+    pub fn bits(&self) -> u32 {
+        (self.arm_disable as u32) | ((self.riscv_disable as u32) << 1)
+    }
+}
+
+/// CRIT1 - secure boot, debug and glitch detector configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Crit1 {
+    /// SECURE_BOOT_ENABLE - only boot binaries signed with a key in
+    /// BOOTKEY0-3.  See [`bootkey`](crate::bootkey).
+    pub secure_boot_enable: bool,
+
+    /// SECURE_DEBUG_DISABLE - disable debug access to secure code.
+    pub secure_debug_disable: bool,
+
+    /// DEBUG_DISABLE - disable all debug access.
+    pub debug_disable: bool,
+
+    /// BOOT_ARCH - boot the RISC-V cores, rather than the Arm cores.
+    pub boot_arch_riscv: bool,
+
+    /// GLITCH_DETECTOR_ENABLE - arm the glitch detectors at boot.
+    pub glitch_detector_enable: bool,
+
+    /// GLITCH_DETECTOR_SENS - the glitch detector sensitivity (2 bits).
+    pub glitch_detector_sens: u8,
+}
+
+impl Crit1 {
+    /// Decodes the CRIT1 value.
+    pub fn from_bits(bits: u32) -> Self {
+        Self {
+            secure_boot_enable: bits & (1 << 0) != 0,
+            secure_debug_disable: bits & (1 << 1) != 0,
+            debug_disable: bits & (1 << 2) != 0,
+            boot_arch_riscv: bits & (1 << 3) != 0,
+            glitch_detector_enable: bits & (1 << 4) != 0,
+            glitch_detector_sens: ((bits >> 5) & 0x3) as u8,
+        }
+    }
+
+    /// Returns the CRIT1 value.
+    pub fn bits(&self) -> Result<u32, Error> {
+        if self.glitch_detector_sens > 0x3 {
+            return Err(Error::CritFlagsNotPossible(format!(
+                "glitch_detector_sens value {} does not fit in 2 bits",
+                self.glitch_detector_sens
+            )));
+        }
+        Ok((self.secure_boot_enable as u32)
+            | ((self.secure_debug_disable as u32) << 1)
+            | ((self.debug_disable as u32) << 2)
+            | ((self.boot_arch_riscv as u32) << 3)
+            | ((self.glitch_detector_enable as u32) << 4)
+            | ((self.glitch_detector_sens as u32) << 5))
+    }
+}
+
+/// A bit which does not hold the same value in all copies of a critical
+/// flags row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CritDisagreement {
+    row: u16,
+    disagreement: CritBitDisagreement,
+}
+
+impl CritDisagreement {
+    /// Returns the first row of the critical flags - [`OTP_ROW_CRIT0`] or
+    /// [`OTP_ROW_CRIT1`].
+    pub fn row(&self) -> u16 {
+        self.row
+    }
+
+    /// Returns the disagreeing bit.
+    pub fn disagreement(&self) -> CritBitDisagreement {
+        self.disagreement
+    }
+}
+
+impl core::fmt::Display for CritDisagreement {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name = if self.row == OTP_ROW_CRIT0 {
+            "CRIT0"
+        } else {
+            "CRIT1"
+        };
+        write!(f, "{name}: {}", self.disagreement)
+    }
+}
+
+/// The effective critical flags, as decoded from OTP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CritFlags {
+    crit0: Crit0,
+    crit1: Crit1,
+    disagreements: Vec<CritDisagreement>,
+}
+
+impl CritFlags {
+    /// Decodes CRIT0 and CRIT1 from a complete raw (non-ECC) OTP dump.
+    ///
+    /// If `strict` is true, all eight copies of each must match.  Otherwise
+    /// they are voted on, as the hardware does, and any disagreements are
+    /// available from [`disagreements`](`Self::disagreements`).
+    pub fn from_raw_otp_data(
+        raw_data: &[u32; TOTAL_OTP_ROWS],
+        strict: bool,
+    ) -> Result<Self, Error> {
+        let mut disagreements = Vec::new();
+        let mut voted = |row: u16| {
+            let start = row as usize;
+            let copies: [u32; CRIT_COPIES] = raw_data[start..start + CRIT_COPIES]
+                .try_into()
+                .expect("Slice is CRIT_COPIES long");
+            let vote = vote::vote_3_of_8(copies);
+            if strict && !vote.is_unanimous() {
+                return Err(Error::NonMatchingCritFlags(row));
+            }
+            disagreements.extend(vote.disagreements().iter().map(|disagreement| {
+                CritDisagreement {
+                    row,
+                    disagreement: *disagreement,
+                }
+            }));
+            Ok(vote.value())
+        };
+        let crit0 = Crit0::from_bits(voted(OTP_ROW_CRIT0)?);
+        let crit1 = Crit1::from_bits(voted(OTP_ROW_CRIT1)?);
+
+        Ok(Self {
+            crit0,
+            crit1,
+            disagreements,
+        })
+    }
+
+    /// Decodes CRIT0 and CRIT1 from an [`OtpImage`].  See
+    /// [`from_raw_otp_data`](`Self::from_raw_otp_data`).
+    pub fn from_image(image: &OtpImage, strict: bool) -> Result<Self, Error> {
+        let raw: &[u32; TOTAL_OTP_ROWS] = image
+            .raw_rows()
+            .try_into()
+            .expect("OtpImage always holds TOTAL_OTP_ROWS rows");
+        Self::from_raw_otp_data(raw, strict)
+    }
+
+    /// Returns the effective CRIT0 flags.
+    pub fn crit0(&self) -> &Crit0 {
+        &self.crit0
+    }
+
+    /// Returns the effective CRIT1 flags.
+    pub fn crit1(&self) -> &Crit1 {
+        &self.crit1
+    }
+
+    /// Returns every bit which differs between the copies of CRIT0 and
+    /// CRIT1.
+    pub fn disagreements(&self) -> &[CritDisagreement] {
+        &self.disagreements
+    }
+}
+
+/// Acknowledges that setting critical flags is irreversible, and may leave
+/// the device permanently unable to boot or be debugged.  Required by
+/// [`plan_crit_flags`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IrreversibleAck(());
+
+impl IrreversibleAck {
+    /// Creates the acknowledgement.
+    pub fn i_understand_this_is_irreversible() -> Self {
+        Self(())
+    }
+}
+
+/// Returns the raw writes required to set the flags in `crit0` and `crit1`
+/// in all eight copies of CRIT0 and CRIT1 in `image`.
+///
+/// Flags already set are left set - flags can never be cleared, so a flag
+/// which is false in `crit0` or `crit1`, but set in OTP, is an error.  Only
+/// copies which change are written.
+pub fn plan_crit_flags(
+    image: &OtpImage,
+    crit0: &Crit0,
+    crit1: &Crit1,
+    _ack: IrreversibleAck,
+) -> Result<Vec<OtpWrite>, Error> {
+    let current = CritFlags::from_image(image, false)?;
+    let mut writes = Vec::new();
+    for (name, row, current, wanted) in [
+        ("CRIT0", OTP_ROW_CRIT0, current.crit0.bits(), crit0.bits()),
+        ("CRIT1", OTP_ROW_CRIT1, current.crit1.bits()?, crit1.bits()?),
+    ] {
+        if current & !wanted != 0 {
+            return Err(Error::CritFlagsNotPossible(format!(
+                "{name} bits {:#x} are already set, and can't be cleared",
+                current & !wanted
+            )));
+        }
+        for copy_row in row..row + CRIT_COPIES as u16 {
+            let copy = image.read_raw(copy_row).unwrap_or(0);
+            if copy | wanted != copy {
+                writes.push(OtpWrite::raw(copy_row, copy | wanted));
+            }
+        }
+    }
+    Ok(writes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crit_flags() {
+        let mut image = OtpImage::new();
+        let crit0 = Crit0::default();
+        let crit1 = Crit1 {
+            secure_boot_enable: true,
+            glitch_detector_enable: true,
+            glitch_detector_sens: 3,
+            ..Default::default()
+        };
+        let ack = IrreversibleAck::i_understand_this_is_irreversible();
+        let writes = plan_crit_flags(&image, &crit0, &crit1, ack).unwrap();
+        assert_eq!(writes.len(), CRIT_COPIES);
+        assert_eq!(writes[0], OtpWrite::raw(OTP_ROW_CRIT1, 0x71));
+        image.apply_plan(&writes).unwrap();
+
+        let flags = CritFlags::from_image(&image, true).unwrap();
+        assert_eq!(flags.crit0(), &crit0);
+        assert_eq!(flags.crit1(), &crit1);
+
+        // Clearing a flag is refused
+        assert!(matches!(
+            plan_crit_flags(&image, &crit0, &Crit1::default(), ack),
+            Err(Error::CritFlagsNotPossible(_))
+        ));
+
+        // Disabling RISC-V, only partially applied
+        image.write_raw(OTP_ROW_CRIT0, 0x2).unwrap();
+        image.write_raw(OTP_ROW_CRIT0 + 5, 0x2).unwrap();
+        let flags = CritFlags::from_image(&image, false).unwrap();
+        assert!(!flags.crit0().riscv_disable);
+        assert_eq!(flags.disagreements().len(), 1);
+        assert_eq!(flags.disagreements()[0].row(), OTP_ROW_CRIT0);
+        assert_eq!(
+            flags.disagreements()[0].disagreement().copies_set(),
+            0b0010_0001
+        );
+        assert!(matches!(
+            CritFlags::from_image(&image, true),
+            Err(Error::NonMatchingCritFlags(OTP_ROW_CRIT0))
+        ));
+
+        // Completing it only writes the copies missing the flag
+        let crit0 = Crit0 {
+            riscv_disable: true,
+            ..Default::default()
+        };
+        let writes = plan_crit_flags(&image, &crit0, &crit1, ack).unwrap();
+        assert_eq!(writes.len(), CRIT_COPIES - 2);
+        image.apply_plan(&writes).unwrap();
+        assert!(
+            CritFlags::from_image(&image, true)
+                .unwrap()
+                .crit0()
+                .riscv_disable
+        );
+    }
+
+    #[test]
+    fn test_bit_positions() {
+        let crit1_bits = [
+            (
+                Crit1 {
+                    secure_boot_enable: true,
+                    ..Default::default()
+                },
+                1 << 0,
+            ),
+            (
+                Crit1 {
+                    secure_debug_disable: true,
+                    ..Default::default()
+                },
+                1 << 1,
+            ),
+            (
+                Crit1 {
+                    debug_disable: true,
+                    ..Default::default()
+                },
+                1 << 2,
+            ),
+            (
+                Crit1 {
+                    boot_arch_riscv: true,
+                    ..Default::default()
+                },
+                1 << 3,
+            ),
+            (
+                Crit1 {
+                    glitch_detector_enable: true,
+                    ..Default::default()
+                },
+                1 << 4,
+            ),
+            (
+                Crit1 {
+                    glitch_detector_sens: 1,
+                    ..Default::default()
+                },
+                1 << 5,
+            ),
+            (
+                Crit1 {
+                    glitch_detector_sens: 2,
+                    ..Default::default()
+                },
+                1 << 6,
+            ),
+        ];
+        for (crit1, bits) in crit1_bits {
+            assert_eq!(crit1.bits(), Ok(bits), "{crit1:?}");
+            assert_eq!(Crit1::from_bits(bits), crit1);
+        }
+
+        let crit0 = Crit0 {
+            arm_disable: true,
+            ..Default::default()
+        };
+        assert_eq!(crit0.bits(), 1 << 0);
+        let crit0 = Crit0 {
+            riscv_disable: true,
+            ..Default::default()
+        };
+        assert_eq!(crit0.bits(), 1 << 1);
+
+        // Bits outside the fields are ignored, and oversized fields refused
+        assert_eq!(Crit1::from_bits(0xff_ff80), Crit1::default());
+        let crit1 = Crit1 {
+            glitch_detector_sens: 4,
+            ..Default::default()
+        };
+        assert!(matches!(crit1.bits(), Err(Error::CritFlagsNotPossible(_))));
+    }
+
+    #[test]
+    fn test_3_of_8_voting() {
+        // DEBUG_DISABLE in three copies, BOOT_ARCH in two, and
+        // SECURE_BOOT_ENABLE in all eight
+        let mut image = OtpImage::new();
+        for copy in 0..CRIT_COPIES as u16 {
+            let mut value = 1 << 0;
+            if [1, 4, 7].contains(&copy) {
+                value |= 1 << 2;
+            }
+            if [2, 6].contains(&copy) {
+                value |= 1 << 3;
+            }
+            image.write_raw(OTP_ROW_CRIT1 + copy, value).unwrap();
+        }
+
+        let flags = CritFlags::from_image(&image, false).unwrap();
+        assert!(flags.crit1().secure_boot_enable);
+        assert!(flags.crit1().debug_disable);
+        assert!(!flags.crit1().boot_arch_riscv);
+        let disagreements = flags.disagreements();
+        assert_eq!(disagreements.len(), 2);
+        assert!(disagreements.iter().all(|d| d.row() == OTP_ROW_CRIT1));
+        assert_eq!(disagreements[0].disagreement().bit(), 2);
+        assert_eq!(disagreements[0].disagreement().copies_set(), 0b1001_0010);
+        assert_eq!(disagreements[1].disagreement().bit(), 3);
+        assert_eq!(disagreements[1].disagreement().copies_set(), 0b0100_0100);
+
+        // Strict decoding reports the disagreeing copies
+        assert_eq!(
+            CritFlags::from_image(&image, true),
+            Err(Error::NonMatchingCritFlags(OTP_ROW_CRIT1))
+        );
+    }
+
+    #[test]
+    fn test_set_bit_cannot_be_cleared() {
+        // DEBUG_DISABLE is set, by 3 of 8 copies
+        let mut image = OtpImage::new();
+        for copy in [0, 3, 5] {
+            image.write_raw(OTP_ROW_CRIT1 + copy, 1 << 2).unwrap();
+        }
+        let ack = IrreversibleAck::i_understand_this_is_irreversible();
+
+        // So it can't be left clear
+        let crit1 = Crit1 {
+            secure_boot_enable: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            plan_crit_flags(&image, &Crit0::default(), &crit1, ack),
+            Err(Error::CritFlagsNotPossible(
+                "CRIT1 bits 0x4 are already set, and can't be cleared".into()
+            ))
+        );
+
+        // But keeping it writes it to the other copies too
+        let crit1 = Crit1 {
+            debug_disable: true,
+            ..crit1
+        };
+        let writes = plan_crit_flags(&image, &Crit0::default(), &crit1, ack).unwrap();
+        assert_eq!(writes.len(), CRIT_COPIES);
+        assert!(writes.iter().all(|write| write.value() == 0x5));
+    }
+}
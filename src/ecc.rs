@@ -0,0 +1,394 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! RP2350 OTP ECC encoding and decoding.
+//!
+//! Each OTP row is 24 bits wide.  When a row is written in ECC mode, the
+//! bottom 16 bits hold the data, bits 16-21 hold a 6-bit Hamming code
+//! (SEC-DED - single error correct, double error detect) and bits 22-23 hold
+//! the bit polarity reversal (BRP) bits.
+//!
+//! If a row being programmed in ECC mode already has a bit set which would
+//! need to be zero, the hardware instead writes the inverse of the 22-bit
+//! data and Hamming code, and sets both BRP bits.  On read, if both BRP bits
+//! are set, the row is inverted before the Hamming code is checked.
+//!
+//! See the RP2350 datasheet, section 13.6, for the details.
+
+/// Mask for the 16 data bits within a raw OTP row.
+pub const ECC_DATA_MASK: u32 = 0x0000_FFFF;
+
+/// Mask for the 6 Hamming code bits within a raw OTP row.
+pub const ECC_PARITY_MASK: u32 = 0x003F_0000;
+
+/// Mask for the 2 bit polarity reversal (BRP) bits within a raw OTP row.
+pub const ECC_BRP_MASK: u32 = 0x00C0_0000;
+
+/// Mask for all 24 valid bits within a raw OTP row.
+pub const RAW_ROW_MASK: u32 = 0x00FF_FFFF;
+
+// Bit position of the Hamming code within a raw row.
+const ECC_PARITY_SHIFT: u32 = 16;
+
+// Mask for the data and Hamming code bits - the bits which are inverted when
+// the BRP bits are set.
+const ECC_WORD_MASK: u32 = ECC_DATA_MASK | ECC_PARITY_MASK;
+
+// Number of bits covered by the Hamming code, including the code itself.
+const ECC_WORD_BITS: u8 = 22;
+
+// Masks of the data bits contributing to each of the first 5 Hamming code
+// bits.  The 6th bit is overall parity, for double error detection.
+const HAMMING_MASKS: [u16; 5] = [
+    0b1010_1101_0101_1011,
+    0b0011_0110_0110_1101,
+    0b1100_0111_1000_1110,
+    0b0000_0111_1111_0000,
+    0b1111_1000_0000_0000,
+];
+
+/// Status of a raw OTP row after ECC decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EccStatus {
+    /// The row decoded cleanly.  A blank row decodes as valid, with data 0.
+    Valid,
+
+    /// The row decoded cleanly, but was written inverted, with both BRP bits
+    /// set.  This happens when ECC data is written to a row which already had
+    /// a bit set, due to a manufacturing defect or previous programming.
+    PolarityReversed,
+
+    /// A single bit error was detected and corrected.  `bit` is the bit
+    /// number within the raw 24-bit row which was flipped.
+    /// `polarity_reversed` is true if the row was also inverted (see
+    /// [`EccStatus::PolarityReversed`]).
+    Corrected { bit: u8, polarity_reversed: bool },
+
+    /// More than one bit error was detected, and the data could not be
+    /// corrected.  The data returned is the uncorrected bottom 16 bits.
+    Uncorrectable,
+}
+
+/// The result of ECC decoding a raw OTP row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EccRow {
+    raw: u32,
+    data: u16,
+    status: EccStatus,
+}
+
+impl EccRow {
+    /// Returns the raw 24-bit row value that was decoded.
+    pub fn raw(&self) -> u32 {
+        self.raw
+    }
+
+    /// Returns the (corrected, where possible) 16-bit data.
+    pub fn data(&self) -> u16 {
+        self.data
+    }
+
+    /// Returns the decode status.
+    pub fn status(&self) -> EccStatus {
+        self.status
+    }
+
+    /// Returns true if the data can be trusted - i.e. the row was either
+    /// valid, or any error was corrected.
+    pub fn is_ok(&self) -> bool {
+        self.status != EccStatus::Uncorrectable
+    }
+
+    /// Returns true if the row decoded without any correction or polarity
+    /// reversal being required.
+    pub fn is_clean(&self) -> bool {
+        self.status == EccStatus::Valid
+    }
+}
+
+impl core::fmt::Display for EccStatus {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EccStatus::Valid => write!(f, "valid"),
+            EccStatus::PolarityReversed => write!(f, "polarity reversed"),
+            EccStatus::Corrected {
+                bit,
+                polarity_reversed,
+            } => {
+                write!(f, "corrected single bit error in bit {bit}")?;
+                if *polarity_reversed {
+                    write!(f, " (polarity reversed)")?;
+                }
+                Ok(())
+            }
+            EccStatus::Uncorrectable => write!(f, "uncorrectable ECC error"),
+        }
+    }
+}
+
+/// A warning about a specific OTP row, raised when ECC decoding of the row
+/// required correction, polarity reversal, or failed, or when the decoded
+/// data differs from the row's value read in ECC mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EccWarning {
+    row: u16,
+    decoded: EccRow,
+    ecc_data: Option<u16>,
+}
+
+impl EccWarning {
+    /// Creates a new EccWarning for the given OTP row index.
+    pub fn new(row: u16, decoded: EccRow) -> Self {
+        Self {
+            row,
+            decoded,
+            ecc_data: None,
+        }
+    }
+
+    /// Creates a new EccWarning for the given OTP row index, whose raw value
+    /// decodes to different data than `ecc_data`, the value read from the
+    /// same row in ECC mode.
+    pub fn mismatch(row: u16, decoded: EccRow, ecc_data: u16) -> Self {
+        Self {
+            row,
+            decoded,
+            ecc_data: Some(ecc_data),
+        }
+    }
+
+    /// Returns the OTP row index the warning applies to.
+    pub fn row(&self) -> u16 {
+        self.row
+    }
+
+    /// Returns the raw 24-bit row value.
+    pub fn raw(&self) -> u32 {
+        self.decoded.raw()
+    }
+
+    /// Returns the (corrected, where possible) 16-bit data.
+    pub fn data(&self) -> u16 {
+        self.decoded.data()
+    }
+
+    /// Returns the decode status.
+    pub fn status(&self) -> EccStatus {
+        self.decoded.status()
+    }
+
+    /// Returns the value read from the row in ECC mode, if it differs from
+    /// the data decoded from the raw value.
+    pub fn ecc_data(&self) -> Option<u16> {
+        self.ecc_data
+    }
+}
+
+impl core::fmt::Display for EccWarning {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "OTP row {:#05x}: raw value {:#08x}: {}",
+            self.row,
+            self.raw(),
+            self.status()
+        )?;
+        if let Some(ecc_data) = self.ecc_data {
+            write!(
+                f,
+                ", decoding to {:#06x}, but {ecc_data:#06x} in ECC mode",
+                self.data()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Returns 1 if an odd number of bits are set, 0 otherwise.
+fn parity(value: u32) -> u32 {
+    value.count_ones() & 1
+}
+
+/// Returns the 6-bit Hamming code for the given 16-bit data value.
+pub fn ecc_parity(data: u16) -> u8 {
+    let mut code = 0u32;
+    for (ii, mask) in HAMMING_MASKS.iter().enumerate() {
+        code |= parity((data & mask) as u32) << ii;
+    }
+
+    // Final bit makes the overall parity of the 22-bit word even.
+    let overall = parity(data as u32) ^ parity(code);
+    code |= overall << HAMMING_MASKS.len();
+
+    code as u8
+}
+
+/// Encodes a 16-bit data value into the 24-bit raw row value that the RP2350
+/// writes when programming the row in ECC mode.  The BRP bits are clear.
+pub fn encode(data: u16) -> u32 {
+    (data as u32) | ((ecc_parity(data) as u32) << ECC_PARITY_SHIFT)
+}
+
+/// Decodes a raw 24-bit OTP row, as read in raw (non-ECC) mode, returning the
+/// 16-bit data the RP2350 would return when reading the row in ECC mode,
+/// along with the decode status.
+///
+/// Bits above bit 23 are ignored.
+pub fn decode(raw: u32) -> EccRow {
+    let raw = raw & RAW_ROW_MASK;
+    let brp = (raw & ECC_BRP_MASK) >> 22;
+    let word = raw & ECC_WORD_MASK;
+
+    let (data, status) = match brp {
+        0b00 => decode_word(word, false),
+        0b11 => decode_word(word ^ ECC_WORD_MASK, true),
+        _ => {
+            // A single BRP bit is set.  Treat this as a single bit error in
+            // the BRP bits, so the rest of the word must be exactly right in
+            // one polarity or the other.
+            let brp_bit = if brp == 0b01 { 22 } else { 23 };
+            if is_codeword(word) {
+                (
+                    word as u16,
+                    EccStatus::Corrected {
+                        bit: brp_bit,
+                        polarity_reversed: false,
+                    },
+                )
+            } else if is_codeword(word ^ ECC_WORD_MASK) {
+                (
+                    (word ^ ECC_WORD_MASK) as u16,
+                    EccStatus::Corrected {
+                        bit: brp_bit ^ 1,
+                        polarity_reversed: true,
+                    },
+                )
+            } else {
+                (word as u16, EccStatus::Uncorrectable)
+            }
+        }
+    };
+
+    EccRow { raw, data, status }
+}
+
+// Returns true if the 22-bit word is a valid data + Hamming code pair.
+fn is_codeword(word: u32) -> bool {
+    encode(word as u16) == word
+}
+
+// Decodes a 22-bit word (with any polarity reversal already undone).
+fn decode_word(word: u32, polarity_reversed: bool) -> (u16, EccStatus) {
+    if is_codeword(word) {
+        let status = if polarity_reversed {
+            EccStatus::PolarityReversed
+        } else {
+            EccStatus::Valid
+        };
+        return (word as u16, status);
+    }
+
+    // Find the single bit flip, if any, which produces a valid codeword.  A
+    // SEC-DED code guarantees at most one such bit exists.
+    for bit in 0..ECC_WORD_BITS {
+        let candidate = word ^ (1 << bit);
+        if is_codeword(candidate) {
+            return (
+                candidate as u16,
+                EccStatus::Corrected {
+                    bit,
+                    polarity_reversed,
+                },
+            );
+        }
+    }
+
+    (word as u16, EccStatus::Uncorrectable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rows 0x000-0x00b from a stock RP2350, as shown in docs/TECHNICAL.md.
+    const SAMPLE_RAW_ROWS: [u32; 12] = [
+        0x00145b6b, 0x002a2f65, 0x00159c23, 0x0027de3f, 0x00346986, 0x0034fd39, 0x001a45eb,
+        0x0021f33c, 0x0032b1e3, 0x0009ecfb, 0x0037d5cc, 0x0023372e,
+    ];
+
+    #[test]
+    fn test_encode_sample() {
+        for raw in SAMPLE_RAW_ROWS {
+            assert_eq!(encode((raw & ECC_DATA_MASK) as u16), raw);
+        }
+        assert_eq!(encode(0), 0);
+    }
+
+    #[test]
+    fn test_decode_sample() {
+        for raw in SAMPLE_RAW_ROWS {
+            let row = decode(raw);
+            assert_eq!(row.status(), EccStatus::Valid);
+            assert_eq!(row.data(), (raw & ECC_DATA_MASK) as u16);
+        }
+        let blank = decode(0);
+        assert!(blank.is_clean());
+        assert_eq!(blank.data(), 0);
+    }
+
+    #[test]
+    fn test_decode_single_bit_errors() {
+        let raw = SAMPLE_RAW_ROWS[0];
+        for bit in 0..ECC_WORD_BITS {
+            let row = decode(raw ^ (1 << bit));
+            assert_eq!(
+                row.status(),
+                EccStatus::Corrected {
+                    bit,
+                    polarity_reversed: false
+                }
+            );
+            assert_eq!(row.data(), 0x5b6b);
+        }
+
+        // A single BRP bit set
+        let row = decode(raw | (1 << 23));
+        assert_eq!(
+            row.status(),
+            EccStatus::Corrected {
+                bit: 23,
+                polarity_reversed: false
+            }
+        );
+        assert_eq!(row.data(), 0x5b6b);
+    }
+
+    #[test]
+    fn test_decode_double_bit_errors() {
+        let raw = SAMPLE_RAW_ROWS[3];
+        let row = decode(raw ^ 0b101);
+        assert_eq!(row.status(), EccStatus::Uncorrectable);
+        assert!(!row.is_ok());
+    }
+
+    #[test]
+    fn test_decode_polarity_reversed() {
+        let raw = encode(0x1234);
+        let reversed = (raw ^ ECC_WORD_MASK) | ECC_BRP_MASK;
+        let row = decode(reversed);
+        assert_eq!(row.status(), EccStatus::PolarityReversed);
+        assert_eq!(row.data(), 0x1234);
+
+        // And with a further bit error
+        let row = decode(reversed ^ (1 << 4));
+        assert_eq!(
+            row.status(),
+            EccStatus::Corrected {
+                bit: 4,
+                polarity_reversed: true
+            }
+        );
+        assert_eq!(row.data(), 0x1234);
+    }
+}
@@ -0,0 +1,174 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Checks for OTP layouts affected by RP2350 errata.
+//!
+//! RP2350-E17 means ECC checking is performed on each adjacent even/odd pair
+//! of rows together, rather than on each row individually.  So a pair of rows
+//! should not:
+//! - mix raw and ECC data, or
+//! - hold two sets of ECC data protected by different enable flags - such as
+//!   the end of the white label data and some unrelated ECC data.
+//!
+//! See docs/TECHNICAL.md for more details.
+
+use alloc::vec::Vec;
+
+use crate::image::{OtpImage, RowMode};
+use crate::whitelabel::{OTP_ROW_UNRESERVED_END, OTP_ROW_UNRESERVED_START};
+
+/// A potential RP2350-E17 problem with an OTP layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum E17Issue {
+    /// The white label data starts at the given odd row, so shares its first
+    /// even/odd pair with the preceding row.
+    OddStartAddress(u16),
+
+    /// The even/odd row pair starting at the given even row mixes raw and ECC
+    /// data.
+    MixedModes(u16),
+
+    /// The white label data shares an even/odd row pair with unrelated ECC
+    /// data in the given row.
+    SharedPair(u16),
+}
+
+impl core::fmt::Display for E17Issue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            E17Issue::OddStartAddress(row) => {
+                write!(f, "White label data starts at odd OTP row {row:#05x}")
+            }
+            E17Issue::MixedModes(row) => write!(
+                f,
+                "OTP rows {row:#05x} and {:#05x} mix raw and ECC data",
+                row + 1
+            ),
+            E17Issue::SharedPair(row) => write!(
+                f,
+                "White label data shares an even/odd row pair with unrelated ECC data in OTP row {row:#05x}"
+            ),
+        }
+    }
+}
+
+/// Checks placing `num_rows` rows of white label data at `white_label_addr`
+/// for RP2350-E17 problems, given the existing contents of OTP in `image`.
+/// Use a blank [`OtpImage`] to check a planned placement on its own.
+///
+/// An odd `white_label_addr` is reported unless `allow_odd_start` is true.
+/// The rows either side of the white label data which share an even/odd pair
+/// with it are reported if they hold raw data, or any ECC data.
+///
+/// Returns every problem found - an empty Vec if the placement is safe.
+pub fn check_white_label_placement(
+    image: &OtpImage,
+    white_label_addr: u16,
+    num_rows: usize,
+    allow_odd_start: bool,
+) -> Vec<E17Issue> {
+    placement_issues(white_label_addr, num_rows, allow_odd_start, |row| {
+        image.row_mode(row).unwrap_or(RowMode::Blank)
+    })
+}
+
+/// Checks every even/odd row pair in the unreserved region of OTP
+/// (0x0c0-0xf3f) for a mix of raw and ECC data.  The reserved regions are not
+/// checked, as some rows written by Raspberry Pi are known to mix them.
+///
+/// Two ECC rows protected by different enable flags cannot be detected from
+/// an image alone - use [`check_white_label_placement`] for the white label
+/// data.
+pub fn check_image(image: &OtpImage) -> Vec<E17Issue> {
+    (OTP_ROW_UNRESERVED_START..OTP_ROW_UNRESERVED_END)
+        .step_by(2)
+        .filter(|row| {
+            let modes = (image.row_mode(*row), image.row_mode(*row + 1));
+            matches!(
+                modes,
+                (Ok(RowMode::Ecc), Ok(RowMode::Raw)) | (Ok(RowMode::Raw), Ok(RowMode::Ecc))
+            )
+        })
+        .map(E17Issue::MixedModes)
+        .collect()
+}
+
+// Implements check_white_label_placement, using `mode` to get the mode of the
+// rows adjacent to the white label data.
+pub(crate) fn placement_issues(
+    white_label_addr: u16,
+    num_rows: usize,
+    allow_odd_start: bool,
+    mode: impl Fn(u16) -> RowMode,
+) -> Vec<E17Issue> {
+    let mut issues = Vec::new();
+    let start = white_label_addr as usize;
+    let end = start + num_rows;
+
+    if start % 2 == 1 && !allow_odd_start {
+        issues.push(E17Issue::OddStartAddress(white_label_addr));
+    }
+
+    // The rows outside the white label data which share a pair with it
+    let mut neighbours = Vec::new();
+    if start % 2 == 1 {
+        neighbours.push(start - 1);
+    }
+    if num_rows > 0 && end % 2 == 1 {
+        neighbours.push(end);
+    }
+
+    for row in neighbours {
+        let row = row as u16;
+        match mode(row) {
+            RowMode::Blank => {}
+            RowMode::Ecc => issues.push(E17Issue::SharedPair(row)),
+            RowMode::Raw => issues.push(E17Issue::MixedModes(row & !1)),
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_white_label_placement() {
+        let mut image = OtpImage::new();
+        assert!(check_white_label_placement(&image, 0x100, 16, false).is_empty());
+        assert!(check_white_label_placement(&image, 0x100, 17, false).is_empty());
+        assert_eq!(
+            check_white_label_placement(&image, 0x101, 16, false),
+            [E17Issue::OddStartAddress(0x101)]
+        );
+        assert!(check_white_label_placement(&image, 0x101, 16, true).is_empty());
+
+        // Data either side of the white label data
+        image.write_raw(0x100, 0x00ff_0000).unwrap();
+        image.write_ecc(0x113, 0x1234).unwrap();
+        assert_eq!(
+            check_white_label_placement(&image, 0x101, 18, true),
+            [E17Issue::MixedModes(0x100), E17Issue::SharedPair(0x113)]
+        );
+        assert_eq!(
+            check_white_label_placement(&image, 0x101, 17, true),
+            [E17Issue::MixedModes(0x100)]
+        );
+        assert!(check_white_label_placement(&image, 0x102, 16, false).is_empty());
+    }
+
+    #[test]
+    fn test_check_image() {
+        let mut image = OtpImage::new();
+        image.write_ecc(0x200, 0x1234).unwrap();
+        image.write_ecc(0x201, 0x1234).unwrap();
+        image.write_raw(0x203, 0x0000_0001).unwrap();
+        assert!(check_image(&image).is_empty());
+
+        image.write_ecc(0x202, 0x1234).unwrap();
+        assert_eq!(check_image(&image), [E17Issue::MixedModes(0x202)]);
+    }
+}
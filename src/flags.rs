@@ -0,0 +1,637 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Typed models of the RP2350's raw OTP flags rows.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::boot::{
+    OTP_ROW_BOOT_FLAGS0, OTP_ROW_BOOT_FLAGS0_R1, OTP_ROW_BOOT_FLAGS0_R2, OTP_ROW_BOOT_FLAGS1,
+    OTP_ROW_BOOT_FLAGS1_R1, OTP_ROW_BOOT_FLAGS1_R2,
+};
+use crate::plan::OtpWrite;
+use crate::vote::VoteResult;
+#[cfg(feature = "serde_json")]
+use crate::whitelabel::Error;
+use crate::whitelabel::fields::FIELDS;
+use crate::whitelabel::{
+    OTP_ROW_USB_BOOT_FLAGS, OTP_ROW_USB_BOOT_FLAGS_R1, OTP_ROW_USB_BOOT_FLAGS_R2,
+};
+
+// Implements the methods and traits shared by the flags types, each of which
+// is a newtype around the u32 value of a triple redundant raw OTP row, with a
+// DEFINED_MASK of the bits it defines.
+macro_rules! raw_flags_impl {
+    ($name:ident, [$row:expr, $row_r1:expr, $row_r2:expr]) => {
+        impl $name {
+            /// Creates flags with no bits set.
+            pub const fn empty() -> Self {
+                Self(0)
+            }
+
+            /// Creates flags from a raw value, keeping any reserved bits.
+            pub const fn from_bits_retain(bits: u32) -> Self {
+                Self(bits)
+            }
+
+            /// Creates flags from a raw value, returning None if any reserved
+            /// bits are set.
+            pub const fn from_bits(bits: u32) -> Option<Self> {
+                if bits & !Self::DEFINED_MASK == 0 {
+                    Some(Self(bits))
+                } else {
+                    None
+                }
+            }
+
+            /// Creates flags by voting on each bit of the three raw copies, as
+            /// the bootrom does.  Use [`vote_2_of_3`](crate::vote::vote_2_of_3)
+            /// directly to find out which bits disagree.
+            pub fn from_copies(copies: [u32; 3]) -> Self {
+                Self(crate::vote::vote_2_of_3(copies).value())
+            }
+
+            /// Returns the raw value.
+            pub const fn bits(&self) -> u32 {
+                self.0
+            }
+
+            /// Returns any reserved bits which are set.
+            pub const fn reserved_bits(&self) -> u32 {
+                self.0 & !Self::DEFINED_MASK
+            }
+
+            /// Returns true if every bit set in `other` is also set in these flags.
+            pub const fn contains(&self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Sets the bits in `other`.
+            pub fn insert(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+
+            /// Clears the bits in `other`.
+            pub fn remove(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+
+            /// Sets or clears the bits in `other`.
+            pub fn set(&mut self, other: Self, value: bool) {
+                if value {
+                    self.insert(other);
+                } else {
+                    self.remove(other);
+                }
+            }
+
+            /// Returns the raw writes required to store these flags in all three
+            /// copies of their OTP row.  As these are raw rows, bits can be added
+            /// to previously written flags, but never cleared.
+            pub fn to_otp_writes(&self) -> Vec<OtpWrite> {
+                [$row, $row_r1, $row_r2]
+                    .iter()
+                    .map(|row| OtpWrite::raw(*row, self.0))
+                    .collect()
+            }
+        }
+
+        impl From<u32> for $name {
+            fn from(bits: u32) -> Self {
+                Self(bits)
+            }
+        }
+
+        impl From<$name> for u32 {
+            fn from(flags: $name) -> Self {
+                flags.0
+            }
+        }
+
+        impl From<&VoteResult> for $name {
+            fn from(vote: &VoteResult) -> Self {
+                Self(vote.value())
+            }
+        }
+
+        impl core::ops::BitOr for $name {
+            type Output = Self;
+
+            fn bitor(self, rhs: Self) -> Self {
+                Self(self.0 | rhs.0)
+            }
+        }
+
+        impl core::ops::BitOrAssign for $name {
+            fn bitor_assign(&mut self, rhs: Self) {
+                self.0 |= rhs.0;
+            }
+        }
+
+        impl core::fmt::Display for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                write!(f, "{:#010x}", self.0)
+            }
+        }
+    };
+}
+
+/// The USB_BOOT_FLAGS register, stored in raw OTP rows 0x059-0x05b
+/// (USB_BOOT_FLAGS, USB_BOOT_FLAGS_R1 and USB_BOOT_FLAGS_R2).
+///
+/// Bits 0-15 mark the corresponding entry in the USB white label structure
+/// as valid.  Bits 16-21 and 24-31 are reserved.
+///
+/// Note that the options to disable the BOOTSEL mass storage and PICOBOOT
+/// USB interfaces are in [`BootFlags0`], not USB_BOOT_FLAGS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(try_from = "UsbBootFlagsJson", into = "UsbBootFlagsJson")]
+pub struct UsbBootFlags(u32);
+
+impl UsbBootFlags {
+    /// WL_USB_DEVICE_VID_VALUE_VALID.
+    pub const USB_VENDOR_ID_VALID: Self = Self(1 << 0);
+    /// WL_USB_DEVICE_PID_VALUE_VALID.
+    pub const USB_PRODUCT_ID_VALID: Self = Self(1 << 1);
+    /// WL_USB_DEVICE_BCD_DEVICE_VALUE_VALID.
+    pub const USB_BCD_DEVICE_VALID: Self = Self(1 << 2);
+    /// WL_USB_DEVICE_LANG_ID_VALUE_VALID.
+    pub const USB_LANGUAGE_ID_VALID: Self = Self(1 << 3);
+    /// WL_USB_DEVICE_MANUFACTURER_STRDEF_VALID.
+    pub const USB_MANUFACTURER_VALID: Self = Self(1 << 4);
+    /// WL_USB_DEVICE_PRODUCT_STRDEF_VALID.
+    pub const USB_PRODUCT_VALID: Self = Self(1 << 5);
+    /// WL_USB_DEVICE_SERIAL_NUMBER_STRDEF_VALID.
+    pub const USB_SERIAL_NUMBER_VALID: Self = Self(1 << 6);
+    /// WL_USB_CONFIG_ATTRIBUTES_MAX_POWER_VALUES_VALID.
+    pub const USB_ATTR_POWER_VALID: Self = Self(1 << 7);
+    /// WL_VOLUME_LABEL_STRDEF_VALID.
+    pub const VOLUME_LABEL_VALID: Self = Self(1 << 8);
+    /// WL_SCSI_INQUIRY_VENDOR_STRDEF_VALID.
+    pub const SCSI_VENDOR_VALID: Self = Self(1 << 9);
+    /// WL_SCSI_INQUIRY_PRODUCT_STRDEF_VALID.
+    pub const SCSI_PRODUCT_VALID: Self = Self(1 << 10);
+    /// WL_SCSI_INQUIRY_VERSION_STRDEF_VALID.
+    pub const SCSI_VERSION_VALID: Self = Self(1 << 11);
+    /// WL_INDEX_HTM_REDIRECT_URL_STRDEF_VALID.
+    pub const REDIRECT_URL_VALID: Self = Self(1 << 12);
+    /// WL_INDEX_HTM_REDIRECT_NAME_STRDEF_VALID.
+    pub const REDIRECT_NAME_VALID: Self = Self(1 << 13);
+    /// WL_INFO_UF2_TXT_MODEL_STRDEF_VALID.
+    pub const UF2_MODEL_VALID: Self = Self(1 << 14);
+    /// WL_INFO_UF2_TXT_BOARD_ID_STRDEF_VALID.
+    pub const UF2_BOARD_ID_VALID: Self = Self(1 << 15);
+    /// WHITE_LABEL_ADDR_VALID - USB_WHITE_LABEL_ADDR points to a valid white
+    /// label structure.
+    pub const WHITE_LABEL_ADDR_VALID: Self = Self(1 << 22);
+    /// DP_DM_SWAP - swap the USB DP and DM lines during USB boot, to support
+    /// boards with mirrored USB routing.
+    pub const DP_DM_SWAP: Self = Self(1 << 23);
+
+    /// Mask of the white label field valid bits, 0-15.
+    pub const WHITE_LABEL_FIELDS_MASK: u32 = 0x0000_FFFF;
+
+    /// Mask of all defined bits.
+    pub const DEFINED_MASK: u32 =
+        Self::WHITE_LABEL_FIELDS_MASK | Self::WHITE_LABEL_ADDR_VALID.0 | Self::DP_DM_SWAP.0;
+
+    /// Returns true if the white label field at `index` (0-15) is marked as
+    /// valid.
+    pub fn white_label_field_valid(&self, index: usize) -> bool {
+        index < 16 && self.0 & (1 << index) != 0
+    }
+
+    #[cfg(feature = "serde_json")]
+    /// Creates flags from their JSON representation.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    #[cfg(feature = "serde_json")]
+    /// Returns the JSON representation of these flags.
+    pub fn to_json(&self) -> Result<serde_json::Value, Error> {
+        Ok(serde_json::to_value(self)?)
+    }
+}
+
+raw_flags_impl!(
+    UsbBootFlags,
+    [
+        OTP_ROW_USB_BOOT_FLAGS,
+        OTP_ROW_USB_BOOT_FLAGS_R1,
+        OTP_ROW_USB_BOOT_FLAGS_R2
+    ]
+);
+
+// JSON representation of UsbBootFlags.  The white label fields marked as
+// valid are listed by name, using the same names as the white label JSON
+// schema's internal field names.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UsbBootFlagsJson {
+    #[serde(default)]
+    white_label_fields_valid: Vec<String>,
+
+    #[serde(default)]
+    white_label_addr_valid: bool,
+
+    #[serde(default)]
+    dp_dm_swap: bool,
+
+    // Any reserved bits set, as a hex string.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reserved: Option<String>,
+}
+
+impl From<UsbBootFlags> for UsbBootFlagsJson {
+    fn from(flags: UsbBootFlags) -> Self {
+        Self {
+            white_label_fields_valid: FIELDS
+                .iter()
+                .filter(|field| flags.white_label_field_valid(field.index()))
+                .map(|field| String::from(field.name()))
+                .collect(),
+            white_label_addr_valid: flags.contains(UsbBootFlags::WHITE_LABEL_ADDR_VALID),
+            dp_dm_swap: flags.contains(UsbBootFlags::DP_DM_SWAP),
+            reserved: reserved_to_json(flags.reserved_bits()),
+        }
+    }
+}
+
+impl TryFrom<UsbBootFlagsJson> for UsbBootFlags {
+    type Error = String;
+
+    fn try_from(json: UsbBootFlagsJson) -> Result<Self, Self::Error> {
+        let mut flags = Self::empty();
+        for name in json.white_label_fields_valid.iter() {
+            let field = FIELDS
+                .iter()
+                .find(|field| field.name() == name)
+                .ok_or_else(|| format!("Unknown white label field: {name}"))?;
+            flags.insert(Self(1 << field.index()));
+        }
+        flags.set(Self::WHITE_LABEL_ADDR_VALID, json.white_label_addr_valid);
+        flags.set(Self::DP_DM_SWAP, json.dp_dm_swap);
+        flags.insert(Self(reserved_from_json(json.reserved, Self::DEFINED_MASK)?));
+        Ok(flags)
+    }
+}
+
+/// The BOOT_FLAGS0 register, stored in raw OTP rows 0x048-0x04a (BOOT_FLAGS0,
+/// BOOT_FLAGS0_R1 and BOOT_FLAGS0_R2).
+///
+/// Several of these flags enable the configuration held in the ECC rows
+/// modelled by [`BootConfig`](crate::boot::BootConfig).  Bits 0 and 22-31
+/// are reserved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(try_from = "BootFlags0Json", into = "BootFlags0Json")]
+pub struct BootFlags0(u32);
+
+impl BootFlags0 {
+    /// ENABLE_BOOTSEL_NON_DEFAULT_PLL_XOSC_CFG - use BOOTSEL_PLL_CFG and
+    /// BOOTSEL_XOSC_CFG to configure the USB PLL and crystal oscillator in
+    /// BOOTSEL mode.
+    pub const ENABLE_BOOTSEL_NON_DEFAULT_PLL_XOSC_CFG: Self = Self(1 << 1);
+    /// ENABLE_BOOTSEL_LED - enable the BOOTSEL activity LED configured by
+    /// BOOTSEL_LED_CFG.
+    pub const ENABLE_BOOTSEL_LED: Self = Self(1 << 2);
+    /// FLASH_IO_VOLTAGE_1V8 - the flash IO voltage is 1.8V rather than 3.3V.
+    pub const FLASH_IO_VOLTAGE_1V8: Self = Self(1 << 3);
+    /// FAST_SIGCHECK_ROSC_DIV - raise the ring oscillator frequency during
+    /// signature checks.
+    pub const FAST_SIGCHECK_ROSC_DIV: Self = Self(1 << 4);
+    /// FLASH_DEVINFO_ENABLE - use FLASH_DEVINFO to describe the attached
+    /// flash devices.
+    pub const FLASH_DEVINFO_ENABLE: Self = Self(1 << 5);
+    /// OVERRIDE_FLASH_PARTITION_SLOT_SIZE - use FLASH_PARTITION_SLOT_SIZE
+    /// rather than the default partition table slot size.
+    pub const OVERRIDE_FLASH_PARTITION_SLOT_SIZE: Self = Self(1 << 6);
+    /// SINGLE_FLASH_BINARY - only look for a single binary at the start of
+    /// flash.
+    pub const SINGLE_FLASH_BINARY: Self = Self(1 << 7);
+    /// DISABLE_AUTO_SWITCH_ARCH - don't switch between Arm and RISC-V to boot
+    /// a binary for the other architecture.
+    pub const DISABLE_AUTO_SWITCH_ARCH: Self = Self(1 << 8);
+    /// SECURE_PARTITION_TABLE - require the partition table to be signed.
+    pub const SECURE_PARTITION_TABLE: Self = Self(1 << 9);
+    /// HASHED_PARTITION_TABLE - require the partition table to be hashed.
+    pub const HASHED_PARTITION_TABLE: Self = Self(1 << 10);
+    /// ROLLBACK_REQUIRED - require binaries to have a rollback version.
+    pub const ROLLBACK_REQUIRED: Self = Self(1 << 11);
+    /// DISABLE_FLASH_BOOT - don't boot from flash.
+    pub const DISABLE_FLASH_BOOT: Self = Self(1 << 12);
+    /// DISABLE_OTP_BOOT - don't boot from OTP, even if ENABLE_OTP_BOOT is set.
+    pub const DISABLE_OTP_BOOT: Self = Self(1 << 13);
+    /// ENABLE_OTP_BOOT - boot from OTP.
+    pub const ENABLE_OTP_BOOT: Self = Self(1 << 14);
+    /// DISABLE_POWER_SCRATCH - ignore boot instructions in the POWMAN scratch
+    /// registers.
+    pub const DISABLE_POWER_SCRATCH: Self = Self(1 << 15);
+    /// DISABLE_WATCHDOG_SCRATCH - ignore boot instructions in the watchdog
+    /// scratch registers.
+    pub const DISABLE_WATCHDOG_SCRATCH: Self = Self(1 << 16);
+    /// DISABLE_BOOTSEL_USB_MSD_IFC - disable the BOOTSEL USB mass storage
+    /// interface.
+    pub const DISABLE_BOOTSEL_USB_MSD_IFC: Self = Self(1 << 17);
+    /// DISABLE_BOOTSEL_USB_PICOBOOT_IFC - disable the BOOTSEL USB PICOBOOT
+    /// interface.
+    pub const DISABLE_BOOTSEL_USB_PICOBOOT_IFC: Self = Self(1 << 18);
+    /// DISABLE_BOOTSEL_UART_BOOT - disable BOOTSEL UART boot.
+    pub const DISABLE_BOOTSEL_UART_BOOT: Self = Self(1 << 19);
+    /// DISABLE_XIP_ACCESS_ON_SRAM_ENTRY - disable XIP access when entering a
+    /// binary in SRAM.
+    pub const DISABLE_XIP_ACCESS_ON_SRAM_ENTRY: Self = Self(1 << 20);
+    /// DISABLE_SRAM_WINDOW_BOOT - don't boot from SRAM windows.
+    pub const DISABLE_SRAM_WINDOW_BOOT: Self = Self(1 << 21);
+
+    /// Mask of all defined bits.
+    pub const DEFINED_MASK: u32 = 0x003f_fffe;
+
+    // The JSON names of each flag.
+    const NAMES: [(&'static str, Self); 21] = [
+        (
+            "enable_bootsel_non_default_pll_xosc_cfg",
+            Self::ENABLE_BOOTSEL_NON_DEFAULT_PLL_XOSC_CFG,
+        ),
+        ("enable_bootsel_led", Self::ENABLE_BOOTSEL_LED),
+        ("flash_io_voltage_1v8", Self::FLASH_IO_VOLTAGE_1V8),
+        ("fast_sigcheck_rosc_div", Self::FAST_SIGCHECK_ROSC_DIV),
+        ("flash_devinfo_enable", Self::FLASH_DEVINFO_ENABLE),
+        (
+            "override_flash_partition_slot_size",
+            Self::OVERRIDE_FLASH_PARTITION_SLOT_SIZE,
+        ),
+        ("single_flash_binary", Self::SINGLE_FLASH_BINARY),
+        ("disable_auto_switch_arch", Self::DISABLE_AUTO_SWITCH_ARCH),
+        ("secure_partition_table", Self::SECURE_PARTITION_TABLE),
+        ("hashed_partition_table", Self::HASHED_PARTITION_TABLE),
+        ("rollback_required", Self::ROLLBACK_REQUIRED),
+        ("disable_flash_boot", Self::DISABLE_FLASH_BOOT),
+        ("disable_otp_boot", Self::DISABLE_OTP_BOOT),
+        ("enable_otp_boot", Self::ENABLE_OTP_BOOT),
+        ("disable_power_scratch", Self::DISABLE_POWER_SCRATCH),
+        ("disable_watchdog_scratch", Self::DISABLE_WATCHDOG_SCRATCH),
+        (
+            "disable_bootsel_usb_msd_ifc",
+            Self::DISABLE_BOOTSEL_USB_MSD_IFC,
+        ),
+        (
+            "disable_bootsel_usb_picoboot_ifc",
+            Self::DISABLE_BOOTSEL_USB_PICOBOOT_IFC,
+        ),
+        ("disable_bootsel_uart_boot", Self::DISABLE_BOOTSEL_UART_BOOT),
+        (
+            "disable_xip_access_on_sram_entry",
+            Self::DISABLE_XIP_ACCESS_ON_SRAM_ENTRY,
+        ),
+        ("disable_sram_window_boot", Self::DISABLE_SRAM_WINDOW_BOOT),
+    ];
+}
+
+raw_flags_impl!(
+    BootFlags0,
+    [
+        OTP_ROW_BOOT_FLAGS0,
+        OTP_ROW_BOOT_FLAGS0_R1,
+        OTP_ROW_BOOT_FLAGS0_R2
+    ]
+);
+
+// JSON representation of BootFlags0.  Set flags are listed by name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BootFlags0Json {
+    #[serde(default)]
+    flags: Vec<String>,
+
+    // Any reserved bits set, as a hex string.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reserved: Option<String>,
+}
+
+impl From<BootFlags0> for BootFlags0Json {
+    fn from(flags: BootFlags0) -> Self {
+        Self {
+            flags: BootFlags0::NAMES
+                .iter()
+                .filter(|(_, flag)| flags.contains(*flag))
+                .map(|(name, _)| String::from(*name))
+                .collect(),
+            reserved: reserved_to_json(flags.reserved_bits()),
+        }
+    }
+}
+
+impl TryFrom<BootFlags0Json> for BootFlags0 {
+    type Error = String;
+
+    fn try_from(json: BootFlags0Json) -> Result<Self, Self::Error> {
+        let mut flags = Self(reserved_from_json(json.reserved, Self::DEFINED_MASK)?);
+        for name in json.flags.iter() {
+            let (_, flag) = Self::NAMES
+                .iter()
+                .find(|(flag_name, _)| flag_name == name)
+                .ok_or_else(|| format!("Unknown BOOT_FLAGS0 flag: {name}"))?;
+            flags.insert(*flag);
+        }
+        Ok(flags)
+    }
+}
+
+/// The BOOT_FLAGS1 register, stored in raw OTP rows 0x04b-0x04d (BOOT_FLAGS1,
+/// BOOT_FLAGS1_R1 and BOOT_FLAGS1_R2).
+///
+/// Bits 4-7, 12-15 and 20-31 are reserved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(try_from = "BootFlags1Json", into = "BootFlags1Json")]
+pub struct BootFlags1(u32);
+
+impl BootFlags1 {
+    /// DOUBLE_TAP - enter BOOTSEL mode if RUN is pulsed twice within the
+    /// DOUBLE_TAP_DELAY period.
+    pub const DOUBLE_TAP: Self = Self(1 << 19);
+
+    /// Mask of all defined bits.
+    pub const DEFINED_MASK: u32 = 0x000f_0f0f;
+
+    const KEY_VALID_SHIFT: u32 = 0;
+    const KEY_INVALID_SHIFT: u32 = 8;
+    const DOUBLE_TAP_DELAY_SHIFT: u32 = 16;
+
+    /// Returns KEY_VALID - a bit for each of BOOTKEY0-3 which holds a valid
+    /// boot key fingerprint.
+    pub const fn key_valid(&self) -> u8 {
+        ((self.0 >> Self::KEY_VALID_SHIFT) & 0xf) as u8
+    }
+
+    /// Sets KEY_VALID.  Only the bottom 4 bits are used.
+    pub fn set_key_valid(&mut self, keys: u8) {
+        self.set_field(Self::KEY_VALID_SHIFT, 0xf, keys);
+    }
+
+    /// Returns KEY_INVALID - a bit for each of BOOTKEY0-3 which has been
+    /// revoked.  This takes priority over KEY_VALID.
+    pub const fn key_invalid(&self) -> u8 {
+        ((self.0 >> Self::KEY_INVALID_SHIFT) & 0xf) as u8
+    }
+
+    /// Sets KEY_INVALID.  Only the bottom 4 bits are used.
+    pub fn set_key_invalid(&mut self, keys: u8) {
+        self.set_field(Self::KEY_INVALID_SHIFT, 0xf, keys);
+    }
+
+    /// Returns DOUBLE_TAP_DELAY - how long the bootrom waits for a second
+    /// RUN pulse when DOUBLE_TAP is set.
+    pub const fn double_tap_delay(&self) -> u8 {
+        ((self.0 >> Self::DOUBLE_TAP_DELAY_SHIFT) & 0x7) as u8
+    }
+
+    /// Sets DOUBLE_TAP_DELAY.  Only the bottom 3 bits are used.
+    pub fn set_double_tap_delay(&mut self, delay: u8) {
+        self.set_field(Self::DOUBLE_TAP_DELAY_SHIFT, 0x7, delay);
+    }
+
+    fn set_field(&mut self, shift: u32, mask: u32, value: u8) {
+        self.0 = (self.0 & !(mask << shift)) | ((value as u32 & mask) << shift);
+    }
+}
+
+raw_flags_impl!(
+    BootFlags1,
+    [
+        OTP_ROW_BOOT_FLAGS1,
+        OTP_ROW_BOOT_FLAGS1_R1,
+        OTP_ROW_BOOT_FLAGS1_R2
+    ]
+);
+
+// JSON representation of BootFlags1.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BootFlags1Json {
+    #[serde(default)]
+    key_valid: u8,
+
+    #[serde(default)]
+    key_invalid: u8,
+
+    #[serde(default)]
+    double_tap_delay: u8,
+
+    #[serde(default)]
+    double_tap: bool,
+
+    // Any reserved bits set, as a hex string.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reserved: Option<String>,
+}
+
+impl From<BootFlags1> for BootFlags1Json {
+    fn from(flags: BootFlags1) -> Self {
+        Self {
+            key_valid: flags.key_valid(),
+            key_invalid: flags.key_invalid(),
+            double_tap_delay: flags.double_tap_delay(),
+            double_tap: flags.contains(BootFlags1::DOUBLE_TAP),
+            reserved: reserved_to_json(flags.reserved_bits()),
+        }
+    }
+}
+
+impl TryFrom<BootFlags1Json> for BootFlags1 {
+    type Error = String;
+
+    fn try_from(json: BootFlags1Json) -> Result<Self, Self::Error> {
+        if json.key_valid > 0xf || json.key_invalid > 0xf {
+            return Err("key_valid and key_invalid must be 4 bit values".to_string());
+        }
+        if json.double_tap_delay > 0x7 {
+            return Err("double_tap_delay must be a 3 bit value".to_string());
+        }
+        let mut flags = Self(reserved_from_json(json.reserved, Self::DEFINED_MASK)?);
+        flags.set_key_valid(json.key_valid);
+        flags.set_key_invalid(json.key_invalid);
+        flags.set_double_tap_delay(json.double_tap_delay);
+        flags.set(Self::DOUBLE_TAP, json.double_tap);
+        Ok(flags)
+    }
+}
+
+// Returns the JSON representation of any reserved bits set.
+fn reserved_to_json(reserved: u32) -> Option<String> {
+    (reserved != 0).then(|| format!("{reserved:#010x}"))
+}
+
+// Parses the JSON representation of reserved bits, which must not overlap
+// the `defined` bits.
+fn reserved_from_json(reserved: Option<String>, defined: u32) -> Result<u32, String> {
+    match reserved {
+        None => Ok(0),
+        Some(reserved) => reserved
+            .strip_prefix("0x")
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .filter(|bits| bits & defined == 0)
+            .ok_or_else(|| format!("Invalid reserved bits: {reserved}")),
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usb_boot_flags() {
+        let mut flags = UsbBootFlags::from(0x0040_ff73);
+        assert!(flags.contains(UsbBootFlags::WHITE_LABEL_ADDR_VALID));
+        assert!(flags.contains(UsbBootFlags::USB_VENDOR_ID_VALID));
+        assert!(!flags.contains(UsbBootFlags::USB_BCD_DEVICE_VALID));
+        assert!(!flags.contains(UsbBootFlags::DP_DM_SWAP));
+        assert_eq!(flags.reserved_bits(), 0);
+
+        flags.insert(UsbBootFlags::DP_DM_SWAP);
+        assert_eq!(flags.bits(), 0x00c0_ff73);
+        assert_eq!(UsbBootFlags::from_bits(0x0020_0000), None);
+        assert_eq!(
+            UsbBootFlags::from_bits_retain(0x0120_0000).reserved_bits(),
+            0x0120_0000
+        );
+
+        let writes = flags.to_otp_writes();
+        assert_eq!(writes.len(), 3);
+        assert_eq!(
+            writes[2],
+            OtpWrite::raw(OTP_ROW_USB_BOOT_FLAGS_R2, 0x00c0_ff73)
+        );
+    }
+
+    #[test]
+    fn test_usb_boot_flags_json() {
+        let flags = UsbBootFlags::USB_VENDOR_ID_VALID
+            | UsbBootFlags::UF2_BOARD_ID_VALID
+            | UsbBootFlags::DP_DM_SWAP
+            | UsbBootFlags::from_bits_retain(0x0001_0000);
+        let json = flags.to_json().unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "white_label_fields_valid": ["usb_vendor_id", "uf2_board_id"],
+                "white_label_addr_valid": false,
+                "dp_dm_swap": true,
+                "reserved": "0x00010000",
+            })
+        );
+        let parsed = UsbBootFlags::from_json(&json.to_string()).unwrap();
+        assert_eq!(parsed, flags);
+
+        // Non-white label options only
+        let flags = UsbBootFlags::from_json(r#"{"dp_dm_swap": true}"#).unwrap();
+        assert_eq!(flags, UsbBootFlags::DP_DM_SWAP);
+
+        assert!(UsbBootFlags::from_json(r#"{"white_label_fields_valid": ["x"]}"#).is_err());
+        assert!(UsbBootFlags::from_json(r#"{"reserved": "0x00400000"}"#).is_err());
+    }
+}
@@ -0,0 +1,512 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Allocation-free white label encoding and decoding, for use on the RP2350
+//! itself.
+//!
+//! [`WhiteLabelFields`] holds the same 16 fields as `WhiteLabelStruct`, but
+//! as borrowed `&str`s, and encodes into, and decodes from, fixed size
+//! buffers.  It doesn't require the `alloc` feature.
+//!
+//! The field table, [`FIELDS`], and the string encoding are shared with
+//! `WhiteLabelStruct`.  Strings are placed as by its default
+//! `LayoutPolicy::Auto`, so both generate identical OTP rows, unless another
+//! policy is set on the `WhiteLabelStruct`.
+//!
+//! ```rust
+//! use pico_otp::heapless::{DECODE_BUFFER_LEN, MAX_WHITE_LABEL_ROWS, WhiteLabelFields};
+//!
+//! let fields = WhiteLabelFields {
+//!     vendor_id: Some(0x1234),
+//!     manufacturer: Some("Acme"),
+//!     ..Default::default()
+//! };
+//! let mut rows = [0u16; MAX_WHITE_LABEL_ROWS];
+//! let num_rows = fields.encode(&mut rows).unwrap();
+//! let usb_boot_flags = fields.usb_boot_flags();
+//!
+//! // Write rows[..num_rows] to OTP, and then USB_BOOT_FLAGS.
+//!
+//! let mut buf = [0u8; DECODE_BUFFER_LEN];
+//! let decoded = WhiteLabelFields::decode(usb_boot_flags, &rows[..num_rows], &mut buf).unwrap();
+//! assert_eq!(decoded, fields);
+//! ```
+
+use crate::whitelabel::fields::{FIELDS, NUM_FIELDS};
+use crate::whitelabel::strdef::{
+    MAX_STRING_OFFSET, auto_last, encode_string, last_offset, strdef, strdef_char_count,
+    strdef_is_utf16, strdef_offset, strdef_row_count, string_layout,
+};
+
+/// Maximum number of OTP rows white label data can occupy - the 16 row
+/// structure, plus strings, the last of which can start at offset 255 and be
+/// up to 64 rows long.
+pub const MAX_WHITE_LABEL_ROWS: usize = 319;
+
+/// Size of the buffer required by [`WhiteLabelFields::decode`].  Each OTP
+/// row decodes to at most 3 bytes of UTF-8.
+pub const DECODE_BUFFER_LEN: usize = 3 * MAX_WHITE_LABEL_ROWS;
+
+// Number of rows in the white label structure, one per field, before the
+// strings.
+const NUM_INDEX_ROWS: usize = NUM_FIELDS;
+// WHITE_LABEL_ADDR_VALID bit within USB_BOOT_FLAGS.
+const WHITE_LABEL_ADDR_VALID: u32 = 1 << 22;
+
+/// Errors from allocation-free encoding and decoding.  Each error affecting
+/// a field includes its index within the white label structure (0-15).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The string has more characters than the field supports.
+    StringTooLong(usize),
+
+    /// The string isn't ASCII, and the field only supports ASCII.
+    Utf16NotSupported(usize),
+
+    /// The string would start more than 255 rows after the start of the
+    /// structure, so can't be referenced by its STRDEF.
+    TooManyRows(usize),
+
+    /// Too few rows were provided to decode the field.  The field is `None`
+    /// if the structure itself was truncated.
+    TooFewRows(Option<usize>),
+
+    /// The field's string data is not valid ASCII or UTF-16.
+    InvalidString(usize),
+
+    /// The buffer provided was too small.
+    BufferTooSmall,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::StringTooLong(index) => write!(f, "field {index}: string is too long"),
+            Error::Utf16NotSupported(index) => {
+                write!(f, "field {index}: string must be ASCII")
+            }
+            Error::TooManyRows(index) => write!(
+                f,
+                "field {index}: string starts more than {MAX_STRING_OFFSET} rows into the white label data"
+            ),
+            Error::TooFewRows(Some(index)) => {
+                write!(f, "field {index}: string extends beyond the rows provided")
+            }
+            Error::TooFewRows(None) => {
+                write!(f, "too few rows provided: need at least {NUM_INDEX_ROWS}")
+            }
+            Error::InvalidString(index) => write!(f, "field {index}: invalid string data"),
+            Error::BufferTooSmall => write!(f, "buffer too small"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// The white label fields, without allocation.  `None` fields aren't white
+/// labelled, so the bootrom uses its default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WhiteLabelFields<'a> {
+    /// USB device vendor ID.
+    pub vendor_id: Option<u16>,
+    /// USB device product ID.
+    pub product_id: Option<u16>,
+    /// USB device version, as BCD.
+    pub bcd_device: Option<u16>,
+    /// USB device language ID.
+    pub language_id: Option<u16>,
+    /// USB device manufacturer.  May be UTF-16.
+    pub manufacturer: Option<&'a str>,
+    /// USB device product.  May be UTF-16.
+    pub product: Option<&'a str>,
+    /// USB device serial number.  May be UTF-16.
+    pub serial_number: Option<&'a str>,
+    /// USB device attributes (low byte) and max power (high byte).
+    pub attr_power: Option<u16>,
+    /// Mass storage volume label.
+    pub volume_label: Option<&'a str>,
+    /// SCSI inquiry vendor.
+    pub scsi_vendor: Option<&'a str>,
+    /// SCSI inquiry product.
+    pub scsi_product: Option<&'a str>,
+    /// SCSI inquiry version.
+    pub scsi_version: Option<&'a str>,
+    /// INDEX.HTM redirect URL.
+    pub redirect_url: Option<&'a str>,
+    /// INDEX.HTM redirect name.
+    pub redirect_name: Option<&'a str>,
+    /// INFO_UF2.TXT model.
+    pub uf2_model: Option<&'a str>,
+    /// INFO_UF2.TXT board ID.
+    pub uf2_board_id: Option<&'a str>,
+}
+
+// Value of a single field.
+#[derive(Debug, Clone, Copy)]
+enum Value<'a> {
+    U16(u16),
+    String(&'a str),
+}
+
+impl<'a> WhiteLabelFields<'a> {
+    // Returns the value of each field, by field index.
+    fn values(&self) -> [Option<Value<'a>>; NUM_FIELDS] {
+        let u16 = |value: Option<u16>| value.map(Value::U16);
+        let string = |value: Option<&'a str>| value.map(Value::String);
+        [
+            u16(self.vendor_id),
+            u16(self.product_id),
+            u16(self.bcd_device),
+            u16(self.language_id),
+            string(self.manufacturer),
+            string(self.product),
+            string(self.serial_number),
+            u16(self.attr_power),
+            string(self.volume_label),
+            string(self.scsi_vendor),
+            string(self.scsi_product),
+            string(self.scsi_version),
+            string(self.redirect_url),
+            string(self.redirect_name),
+            string(self.uf2_model),
+            string(self.uf2_board_id),
+        ]
+    }
+
+    // Creates the fields from each field's value, by field index.
+    fn from_values(values: [Option<Value<'a>>; NUM_FIELDS]) -> Self {
+        let u16 = |index: usize| match values[index] {
+            Some(Value::U16(value)) => Some(value),
+            _ => None,
+        };
+        let string = |index: usize| match values[index] {
+            Some(Value::String(value)) => Some(value),
+            _ => None,
+        };
+        Self {
+            vendor_id: u16(0),
+            product_id: u16(1),
+            bcd_device: u16(2),
+            language_id: u16(3),
+            manufacturer: string(4),
+            product: string(5),
+            serial_number: string(6),
+            attr_power: u16(7),
+            volume_label: string(8),
+            scsi_vendor: string(9),
+            scsi_product: string(10),
+            scsi_version: string(11),
+            redirect_url: string(12),
+            redirect_name: string(13),
+            uf2_model: string(14),
+            uf2_board_id: string(15),
+        }
+    }
+
+    /// Returns the value to write to USB_BOOT_FLAGS, and its two copies -
+    /// marking each field present, and the white label address, as valid.
+    pub fn usb_boot_flags(&self) -> u32 {
+        self.values()
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| value.is_some())
+            .fold(WHITE_LABEL_ADDR_VALID, |flags, (index, _)| {
+                flags | (1 << index)
+            })
+    }
+
+    /// Encodes the fields into `buf`, in the same layout as
+    /// `WhiteLabelStruct` with its default `LayoutPolicy::Auto`, returning
+    /// the number of rows used.
+    ///
+    /// Strings are placed in field order if that fits.  Otherwise the string
+    /// with the most rows is placed last - on a tie, the one with the highest
+    /// field index.  The `FieldOrder` and `Compact` layout policies aren't
+    /// supported.
+    ///
+    /// The rows are written to OTP, using ECC, starting at the row pointed
+    /// to by USB_WHITE_LABEL_ADDR.
+    pub fn encode(&self, buf: &mut [u16; MAX_WHITE_LABEL_ROWS]) -> Result<usize, Error> {
+        buf.fill(0);
+
+        // Write the u16 fields, and check the strings
+        let mut strings = [None; NUM_FIELDS];
+        for (field, value) in FIELDS.iter().zip(self.values()) {
+            let index = field.index();
+            match value {
+                None => {}
+                Some(Value::U16(value)) => buf[index] = value,
+                Some(Value::String(string)) => {
+                    let (char_count, row_count, is_utf16) = string_layout(string);
+                    if char_count > field.max_length().unwrap_or(0) {
+                        return Err(Error::StringTooLong(index));
+                    }
+                    if is_utf16 && !field.supports_utf16() {
+                        return Err(Error::Utf16NotSupported(index));
+                    }
+                    strings[index] = Some((string, char_count, row_count, is_utf16));
+                }
+            }
+        }
+
+        // Choose the last string, as `WhiteLabelStruct` does.  Every other
+        // string starts before it, so only its offset needs checking.
+        let rows = strings.map(|string| string.map(|(_, _, rows, _)| rows));
+        let last = auto_last(&rows);
+        if let Some(index) = last.filter(|&index| last_offset(&rows, index) > MAX_STRING_OFFSET) {
+            return Err(Error::TooManyRows(index));
+        }
+
+        // Write the strings
+        let mut offset = NUM_INDEX_ROWS;
+        let order = (0..NUM_FIELDS)
+            .filter(|&index| strings[index].is_some() && Some(index) != last)
+            .chain(last);
+        for index in order {
+            let Some((string, char_count, row_count, is_utf16)) = strings[index] else {
+                unreachable!("String not present");
+            };
+            buf[index] = strdef(char_count as u8, is_utf16, offset as u8);
+            encode_string(string, &mut buf[offset..offset + row_count]);
+            offset += row_count;
+        }
+        Ok(offset)
+    }
+
+    /// Decodes the fields from white label `rows`, read from OTP starting at
+    /// the row pointed to by USB_WHITE_LABEL_ADDR, and the raw
+    /// `usb_boot_flags` value.  Strings are decoded into `buf`.
+    ///
+    /// Unlike `WhiteLabelStruct`, which parses as much as possible and
+    /// returns warnings, any problem with a field present in
+    /// `usb_boot_flags` is an error.
+    pub fn decode(
+        usb_boot_flags: u32,
+        rows: &[u16],
+        buf: &'a mut [u8; DECODE_BUFFER_LEN],
+    ) -> Result<Self, Error> {
+        if rows.len() < NUM_INDEX_ROWS {
+            return Err(Error::TooFewRows(None));
+        }
+        let present = |index: usize| usb_boot_flags & (1 << index) != 0;
+
+        let mut values = [None; NUM_FIELDS];
+        let mut remaining: &'a mut [u8] = buf;
+        for (field, value) in FIELDS.iter().zip(values.iter_mut()) {
+            let index = field.index();
+            if !present(index) {
+                continue;
+            }
+            if field.is_u16() {
+                *value = Some(Value::U16(rows[index]));
+            } else if rows[index] != 0 {
+                let (string, rest) = decode_string(rows, index, remaining)?;
+                *value = Some(Value::String(string));
+                remaining = rest;
+            }
+        }
+        Ok(Self::from_values(values))
+    }
+}
+
+// Decodes the string whose STRDEF is at `index` into the start of `buf`,
+// returning it, and the unused remainder of `buf`.
+fn decode_string<'b>(
+    rows: &[u16],
+    index: usize,
+    buf: &'b mut [u8],
+) -> Result<(&'b str, &'b mut [u8]), Error> {
+    let strdef = rows[index];
+    let offset = strdef_offset(strdef) as usize;
+    let char_count = strdef_char_count(strdef) as usize;
+    let end = offset + strdef_row_count(strdef) as usize;
+    if offset < NUM_INDEX_ROWS {
+        return Err(Error::InvalidString(index));
+    }
+    let string_rows = rows
+        .get(offset..end)
+        .ok_or(Error::TooFewRows(Some(index)))?;
+
+    let mut len = 0;
+    if strdef_is_utf16(strdef) {
+        for ch in char::decode_utf16(string_rows.iter().copied()) {
+            let ch = ch.map_err(|_| Error::InvalidString(index))?;
+            let dest = buf
+                .get_mut(len..len + ch.len_utf8())
+                .ok_or(Error::BufferTooSmall)?;
+            len += ch.encode_utf8(dest).len();
+        }
+    } else {
+        let bytes = string_rows.iter().flat_map(|row| row.to_le_bytes());
+        for byte in bytes.take(char_count) {
+            if !byte.is_ascii() {
+                return Err(Error::InvalidString(index));
+            }
+            *buf.get_mut(len).ok_or(Error::BufferTooSmall)? = byte;
+            len += 1;
+        }
+    }
+
+    let (string, rest) = buf.split_at_mut(len);
+    let string = core::str::from_utf8(string).map_err(|_| Error::InvalidString(index))?;
+    Ok((string, rest))
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+    use crate::whitelabel::WhiteLabelStruct;
+
+    // Returns the fields of `wls`.
+    fn fields_of(wls: &WhiteLabelStruct) -> WhiteLabelFields<'_> {
+        WhiteLabelFields {
+            vendor_id: wls.vid(),
+            product_id: wls.pid(),
+            bcd_device: wls.bcd_device(),
+            language_id: wls.language_id(),
+            manufacturer: wls.manufacturer().map(|s| s.as_str()),
+            product: wls.product().map(|s| s.as_str()),
+            serial_number: wls.serial_number().map(|s| s.as_str()),
+            attr_power: wls.attr_power(),
+            volume_label: wls.volume_label().map(|s| s.as_str()),
+            scsi_vendor: wls.scsi_vendor().map(|s| s.as_str()),
+            scsi_product: wls.scsi_product().map(|s| s.as_str()),
+            scsi_version: wls.scsi_version().map(|s| s.as_str()),
+            redirect_url: wls.redirect_url().map(|s| s.as_str()),
+            redirect_name: wls.redirect_name().map(|s| s.as_str()),
+            uf2_model: wls.uf2_model().map(|s| s.as_str()),
+            uf2_board_id: wls.uf2_board_id().map(|s| s.as_str()),
+        }
+    }
+
+    #[test]
+    fn test_matches_white_label_struct() {
+        let json = include_str!("../json/sample-wl.json");
+        let wls = WhiteLabelStruct::from_json(json).unwrap();
+        let otp_data = wls.to_otp_data_strict().unwrap();
+
+        let fields = fields_of(&wls);
+        let mut rows = [0u16; MAX_WHITE_LABEL_ROWS];
+        let num_rows = fields.encode(&mut rows).unwrap();
+        assert_eq!(&rows[..num_rows], otp_data.rows().as_slice());
+        assert_eq!(fields.usb_boot_flags(), otp_data.usb_boot_flags());
+
+        let mut buf = [0u8; DECODE_BUFFER_LEN];
+        let decoded =
+            WhiteLabelFields::decode(otp_data.usb_boot_flags(), otp_data.rows(), &mut buf).unwrap();
+        assert_eq!(decoded, fields);
+
+        // Truncated string data
+        let mut buf = [0u8; DECODE_BUFFER_LEN];
+        assert_eq!(
+            WhiteLabelFields::decode(
+                otp_data.usb_boot_flags(),
+                &otp_data.rows()[..num_rows - 1],
+                &mut buf
+            ),
+            Err(Error::TooFewRows(Some(15)))
+        );
+    }
+
+    #[test]
+    fn test_matches_auto_layout() {
+        // In field order, the board ID would start at row 268
+        let long = "x".repeat(127);
+        let utf16 = "é".repeat(30);
+        let mut wls = WhiteLabelStruct::default();
+        wls.set_manufacturer(utf16.clone()).unwrap();
+        wls.set_product(utf16).unwrap();
+        wls.set_redirect_url(long.clone()).unwrap();
+        wls.set_redirect_name(long.clone()).unwrap();
+        wls.set_uf2_model(long).unwrap();
+        wls.set_uf2_board_id("abc").unwrap();
+        let otp_data = wls.to_otp_data_strict().unwrap();
+
+        let fields = fields_of(&wls);
+        let mut rows = [0u16; MAX_WHITE_LABEL_ROWS];
+        let num_rows = fields.encode(&mut rows).unwrap();
+        assert_eq!(&rows[..num_rows], otp_data.rows().as_slice());
+        assert_eq!(strdef_offset(rows[14]), 16 + 60 + 64 + 64 + 2);
+
+        let mut buf = [0u8; DECODE_BUFFER_LEN];
+        let decoded = WhiteLabelFields::decode(fields.usb_boot_flags(), &rows, &mut buf).unwrap();
+        assert_eq!(decoded, fields);
+    }
+
+    #[test]
+    fn test_matches_auto_layout_generated() {
+        // Pseudo-random string fields, mostly near their maximum length, so
+        // that many don't fit in field order, and some don't fit at all
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut next = |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % n as u64) as usize
+        };
+        for _ in 0..2000 {
+            let mut wls = WhiteLabelStruct::default();
+            for field in FIELDS.iter().filter(|field| !field.is_u16()) {
+                if next(6) == 0 {
+                    continue;
+                }
+                let max = field.max_length().unwrap();
+                let len = (max - next(max.min(8)))
+                    .saturating_sub((next(6) == 0) as usize * next(max))
+                    .max(1);
+                let ch = if field.supports_utf16() && next(2) == 0 {
+                    "é"
+                } else {
+                    "a"
+                };
+                wls.set(field, ch.repeat(len).into()).unwrap();
+            }
+
+            let fields = fields_of(&wls);
+            let mut rows = [0u16; MAX_WHITE_LABEL_ROWS];
+            match (fields.encode(&mut rows), wls.to_otp_data_strict()) {
+                (Ok(num_rows), Ok(otp_data)) => {
+                    assert_eq!(&rows[..num_rows], otp_data.rows().as_slice());
+                }
+                (Err(Error::TooManyRows(_)), Err(_)) => {}
+                (encoded, otp_data) => panic!("{encoded:?} != {otp_data:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let mut rows = [0u16; MAX_WHITE_LABEL_ROWS];
+        let fields = WhiteLabelFields {
+            volume_label: Some("TOO LONG LABEL"),
+            ..Default::default()
+        };
+        assert_eq!(fields.encode(&mut rows), Err(Error::StringTooLong(8)));
+
+        let fields = WhiteLabelFields {
+            scsi_vendor: Some("水"),
+            ..Default::default()
+        };
+        assert_eq!(fields.encode(&mut rows), Err(Error::Utf16NotSupported(9)));
+
+        // The last string may start at offset 255, and extend beyond it
+        let long = "x".repeat(127);
+        let fields = WhiteLabelFields {
+            redirect_url: Some(&long),
+            redirect_name: Some(&long),
+            uf2_model: Some(&long),
+            uf2_board_id: Some(&long),
+            ..Default::default()
+        };
+        assert_eq!(fields.encode(&mut rows), Ok(16 + 4 * 64));
+        // But these don't fit in any order
+        let utf16 = "é".repeat(30);
+        let fields = WhiteLabelFields {
+            manufacturer: Some(&utf16),
+            product: Some(&utf16),
+            ..fields
+        };
+        assert_eq!(fields.encode(&mut rows), Err(Error::TooManyRows(15)));
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Chip identity and factory calibration data, from OTP page 0.
+//!
+//! Page 0 is written and locked read-only during factory testing.  It holds,
+//! in ECC rows:
+//! - CHIPID0-3 (0x000-0x003) - the chip's serial number, reported over USB
+//!   by the bootrom unless overridden by white label data.
+//! - RANDID0-7 (0x004-0x00b) - a per-device random number.
+//! - ROSC_CALIB (0x010) and LPOSC_CALIB (0x011) - the measured oscillator
+//!   frequencies.
+//! - NUM_GPIOS (0x018) - the number of bank 0 GPIOs, which depends on the
+//!   package.
+//! - INFO_CRC0-1 (0x036-0x037) - a CRC of the factory data.
+//!
+//! Factory regulator calibration is out of scope, and not decoded.  No OTP
+//! row or format for it is documented alongside the rows above, and
+//! guessing at one would risk reporting a wrong value as a calibration.
+//!
+//! See docs/TECHNICAL.md for an example.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::ecc;
+use crate::whitelabel::{Error, TOTAL_OTP_ROWS, check_ecc_rows};
+
+/// OTP row index for CHIPID0.  CHIPID1-3 follow it.
+pub const OTP_ROW_CHIPID0: u16 = 0x000;
+/// OTP row index for RANDID0.  RANDID1-7 follow it.
+pub const OTP_ROW_RANDID0: u16 = 0x004;
+/// OTP row index for ROSC_CALIB
+pub const OTP_ROW_ROSC_CALIB: u16 = 0x010;
+/// OTP row index for LPOSC_CALIB
+pub const OTP_ROW_LPOSC_CALIB: u16 = 0x011;
+/// OTP row index for NUM_GPIOS
+pub const OTP_ROW_NUM_GPIOS: u16 = 0x018;
+/// OTP row index for INFO_CRC0.  INFO_CRC1 follows it.
+pub const OTP_ROW_INFO_CRC0: u16 = 0x036;
+
+/// The chip identity and factory calibration data from OTP page 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChipIdentity {
+    chip_id: [u16; 4],
+    rand_id: [u16; 8],
+    rosc_calib: u16,
+    lposc_calib: u16,
+    num_gpios: u16,
+    info_crc: [u16; 2],
+}
+
+impl ChipIdentity {
+    /// Decodes the chip identity from a complete OTP dump, consisting of both
+    /// ECC and non-ECC data.
+    ///
+    /// If `strict` is true, every row used must ECC decode from
+    /// `non_ecc_data` without an uncorrectable error, to the same value as
+    /// provided in `ecc_data`.
+    pub fn from_full_otp_data(
+        non_ecc_data: &[u32; TOTAL_OTP_ROWS],
+        ecc_data: &[u16; TOTAL_OTP_ROWS],
+        strict: bool,
+    ) -> Result<Self, Error> {
+        let mut warnings = Vec::new();
+        for (start, count) in [
+            (OTP_ROW_CHIPID0, 12),
+            (OTP_ROW_ROSC_CALIB, 2),
+            (OTP_ROW_NUM_GPIOS, 1),
+            (OTP_ROW_INFO_CRC0, 2),
+        ] {
+            check_ecc_rows(
+                non_ecc_data,
+                ecc_data,
+                start as usize,
+                count,
+                strict,
+                &mut warnings,
+            )?;
+        }
+
+        let row = |row: u16| ecc_data[row as usize];
+        Ok(Self {
+            chip_id: core::array::from_fn(|ii| row(OTP_ROW_CHIPID0 + ii as u16)),
+            rand_id: core::array::from_fn(|ii| row(OTP_ROW_RANDID0 + ii as u16)),
+            rosc_calib: row(OTP_ROW_ROSC_CALIB),
+            lposc_calib: row(OTP_ROW_LPOSC_CALIB),
+            num_gpios: row(OTP_ROW_NUM_GPIOS),
+            info_crc: core::array::from_fn(|ii| row(OTP_ROW_INFO_CRC0 + ii as u16)),
+        })
+    }
+
+    /// Decodes the chip identity from a complete raw (non-ECC) OTP dump only.
+    /// See [`from_full_otp_data`](`Self::from_full_otp_data`).
+    pub fn from_raw_otp_data(
+        raw_data: &[u32; TOTAL_OTP_ROWS],
+        strict: bool,
+    ) -> Result<Self, Error> {
+        let ecc_data: [u16; TOTAL_OTP_ROWS] =
+            core::array::from_fn(|row| ecc::decode(raw_data[row]).data());
+        Self::from_full_otp_data(raw_data, &ecc_data, strict)
+    }
+
+    /// Returns the CHIPID0-3 rows.
+    pub fn chip_id_rows(&self) -> &[u16; 4] {
+        &self.chip_id
+    }
+
+    /// Returns the 64-bit chip ID, with CHIPID3 as the most significant
+    /// 16 bits.
+    pub fn chip_id(&self) -> u64 {
+        self.chip_id
+            .iter()
+            .rev()
+            .fold(0, |id, row| (id << 16) | *row as u64)
+    }
+
+    /// Returns the RANDID0-7 rows.
+    pub fn rand_id_rows(&self) -> &[u16; 8] {
+        &self.rand_id
+    }
+
+    /// Returns ROSC_CALIB - the ring oscillator frequency in kHz, measured
+    /// during manufacturing.
+    pub fn rosc_calib_khz(&self) -> u16 {
+        self.rosc_calib
+    }
+
+    /// Returns LPOSC_CALIB - the low power oscillator frequency in Hz,
+    /// measured during manufacturing.
+    pub fn lposc_calib_hz(&self) -> u16 {
+        self.lposc_calib
+    }
+
+    /// Returns NUM_GPIOS - the number of bank 0 GPIOs.
+    pub fn num_gpios(&self) -> u16 {
+        self.num_gpios
+    }
+
+    /// Returns the INFO_CRC0-1 rows.
+    pub fn info_crc_rows(&self) -> &[u16; 2] {
+        &self.info_crc
+    }
+
+    /// Returns the USB serial number string the bootrom reports when the
+    /// white label serial number isn't set - the chip ID as 16 upper case
+    /// hex digits, such as `DE3F9C232F655B6B`.
+    pub fn serial_number(&self) -> String {
+        format!("{:016X}", self.chip_id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chip_identity() {
+        // The stock RP2350 shown in docs/TECHNICAL.md
+        let mut raw = [0u32; TOTAL_OTP_ROWS];
+        let rows = [
+            0x5b6b, 0x2f65, 0x9c23, 0xde3f, 0x6986, 0xfd39, 0x45eb, 0xf33c, 0xb1e3, 0xecfb, 0xd5cc,
+            0x372e,
+        ];
+        for (ii, row) in rows.iter().enumerate() {
+            raw[ii] = ecc::encode(*row);
+        }
+        assert_eq!(raw[3], 0x0027de3f);
+        raw[OTP_ROW_NUM_GPIOS as usize] = ecc::encode(30);
+
+        let identity = ChipIdentity::from_raw_otp_data(&raw, true).unwrap();
+        assert_eq!(identity.chip_id(), 0xde3f_9c23_2f65_5b6b);
+        assert_eq!(identity.serial_number(), "DE3F9C232F655B6B");
+        assert_eq!(identity.rand_id_rows()[7], 0x372e);
+        assert_eq!(identity.num_gpios(), 30);
+
+        // Uncorrectable ECC error in the chip ID
+        raw[1] ^= 0x3;
+        assert!(matches!(
+            ChipIdentity::from_raw_otp_data(&raw, true),
+            Err(Error::UncorrectableEcc(1))
+        ));
+        assert!(ChipIdentity::from_raw_otp_data(&raw, false).is_ok());
+    }
+
+    #[test]
+    fn test_serial_number() {
+        // Always 16 upper case hex digits, CHIPID3 first, zero padded
+        let mut raw = [0u32; TOTAL_OTP_ROWS];
+        for (ii, row) in [0x00ab, 0x0000, 0x0c0d, 0x0001].iter().enumerate() {
+            raw[ii] = ecc::encode(*row);
+        }
+        let identity = ChipIdentity::from_raw_otp_data(&raw, true).unwrap();
+        assert_eq!(identity.chip_id_rows(), &[0x00ab, 0x0000, 0x0c0d, 0x0001]);
+        assert_eq!(identity.serial_number(), "00010C0D000000AB");
+
+        // A blank chip ID is still 16 digits
+        let identity = ChipIdentity::from_raw_otp_data(&[0; TOTAL_OTP_ROWS], true).unwrap();
+        assert_eq!(identity.serial_number(), "0000000000000000");
+    }
+
+    #[test]
+    fn test_undecodable_rows() {
+        let mut raw = [0u32; TOTAL_OTP_ROWS];
+        for (ii, row) in raw.iter_mut().take(12).enumerate() {
+            *row = ecc::encode(0x1111 * ii as u16);
+        }
+
+        // Uncorrectable ECC error in RANDID3
+        let randid3 = OTP_ROW_RANDID0 as usize + 3;
+        raw[randid3] ^= 0x3;
+        assert!(matches!(
+            ChipIdentity::from_raw_otp_data(&raw, true),
+            Err(Error::UncorrectableEcc(0x007))
+        ));
+        let identity = ChipIdentity::from_raw_otp_data(&raw, false).unwrap();
+        assert_eq!(identity.chip_id(), 0x3333_2222_1111_0000);
+        raw[randid3] ^= 0x3;
+
+        // ECC view which doesn't match the raw view of CHIPID2
+        let ecc_data: [u16; TOTAL_OTP_ROWS] =
+            core::array::from_fn(|row| ecc::decode(raw[row]).data() ^ (row == 2) as u16);
+        assert!(matches!(
+            ChipIdentity::from_full_otp_data(&raw, &ecc_data, true),
+            Err(Error::EccMismatch(0x002))
+        ));
+        let identity = ChipIdentity::from_full_otp_data(&raw, &ecc_data, false).unwrap();
+        assert_eq!(identity.chip_id_rows()[2], 0x2223);
+    }
+}
@@ -0,0 +1,600 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Contains the OtpImage object, an offline model of the RP2350's entire OTP
+//! memory.
+//!
+//! [`OtpImage`] enforces the same rules as the hardware:
+//! - Bits can only be written from 0 to 1, never back again.
+//! - Each row is written either in ECC mode or raw mode, never both.
+//! - A row can only be written once in ECC mode, as the ECC parity bits
+//!   would otherwise become invalid.
+//!
+//! This allows a complete programming plan to be staged and validated before
+//! writing anything to a real device.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::boot::{BootConfig, Error as BootConfigError};
+use crate::ecc::{self, EccRow, EccStatus, RAW_ROW_MASK};
+use crate::plan::{OtpWrite, WriteMode};
+use crate::whitelabel::{Error as WhiteLabelError, OtpData, TOTAL_OTP_ROWS};
+
+/// Number of rows in each OTP page.
+pub const OTP_PAGE_ROWS: usize = 64;
+
+/// Number of OTP pages.
+pub const OTP_PAGES: usize = TOTAL_OTP_ROWS / OTP_PAGE_ROWS;
+
+/// Errors that can occur while reading or writing an [`OtpImage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The row index is beyond the end of OTP.
+    InvalidRow(u16),
+
+    /// The raw value has bits set above bit 23.
+    InvalidRawValue { row: u16, value: u32 },
+
+    /// The row has already been written in ECC mode, so cannot be written
+    /// again.
+    EccRowAlreadyWritten(u16),
+
+    /// The operation's mode (ECC or raw) conflicts with the mode the row has
+    /// already been written in.
+    RowModeConflict { row: u16, mode: RowMode },
+
+    /// The write would require one or more bits which are already set to be
+    /// cleared.  `current` is the existing raw row value, `requested` the raw
+    /// value which would have to be written.
+    BitClear {
+        row: u16,
+        current: u32,
+        requested: u32,
+    },
+
+    /// The row holds an uncorrectable ECC error, so cannot be read in ECC
+    /// mode.
+    UncorrectableEcc(u16),
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidRow(row) => write!(f, "Invalid OTP row {row:#05x}"),
+            Error::InvalidRawValue { row, value } => write!(
+                f,
+                "Invalid raw value {value:#010x} for OTP row {row:#05x}: only 24 bits available"
+            ),
+            Error::EccRowAlreadyWritten(row) => {
+                write!(f, "OTP row {row:#05x} has already been written in ECC mode")
+            }
+            Error::RowModeConflict { row, mode } => {
+                write!(
+                    f,
+                    "OTP row {row:#05x} has already been written in {mode} mode"
+                )
+            }
+            Error::BitClear {
+                row,
+                current,
+                requested,
+            } => write!(
+                f,
+                "Writing {requested:#08x} to OTP row {row:#05x} would clear bits already set in {current:#08x}"
+            ),
+            Error::UncorrectableEcc(row) => {
+                write!(f, "Uncorrectable ECC error in OTP row {row:#05x}")
+            }
+        }
+    }
+}
+
+/// The mode an OTP row has been written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowMode {
+    /// The row has not been written.
+    Blank,
+
+    /// The row has been written in ECC mode.
+    Ecc,
+
+    /// The row has been written in raw mode.
+    Raw,
+}
+
+impl RowMode {
+    // Infers the mode a row was written in from its raw value.  See
+    // [`OtpImage::from_raw_data`].
+    pub(crate) fn infer(raw: u32) -> Self {
+        if raw & RAW_ROW_MASK == 0 {
+            RowMode::Blank
+        } else {
+            match ecc::decode(raw).status() {
+                EccStatus::Valid | EccStatus::PolarityReversed => RowMode::Ecc,
+                _ => RowMode::Raw,
+            }
+        }
+    }
+}
+
+impl core::fmt::Display for RowMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RowMode::Blank => write!(f, "blank"),
+            RowMode::Ecc => write!(f, "ECC"),
+            RowMode::Raw => write!(f, "raw"),
+        }
+    }
+}
+
+/// An image of all 4096 rows of the RP2350's OTP memory.
+///
+/// Use [`OtpImage::new`] to create a blank image, or
+/// [`OtpImage::from_raw_data`] to create one from a dump of a real device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtpImage {
+    // Raw 24-bit value of each row.
+    rows: Vec<u32>,
+
+    // Mode each row has been written in.
+    modes: Vec<RowMode>,
+}
+
+impl Default for OtpImage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OtpImage {
+    /// Creates a blank OTP image, as on a device with no OTP rows written.
+    pub fn new() -> Self {
+        Self {
+            rows: vec![0; TOTAL_OTP_ROWS],
+            modes: vec![RowMode::Blank; TOTAL_OTP_ROWS],
+        }
+    }
+
+    /// Creates an OTP image from a complete raw (non-ECC) OTP dump.
+    ///
+    /// OTP does not record the mode each row was written in, so this is
+    /// inferred:
+    /// - Zero rows are blank.
+    /// - Non-zero rows which ECC decode without an error (including with
+    ///   polarity reversal) are ECC rows.
+    /// - All other rows are raw rows.
+    ///
+    /// Raw rows which happen to hold a valid ECC codeword will therefore be
+    /// treated as ECC rows.  Use [`set_row_mode`](`Self::set_row_mode`) to
+    /// correct this where the layout is known.
+    ///
+    /// Bits above bit 23 of each row are ignored.
+    pub fn from_raw_data(raw_data: &[u32; TOTAL_OTP_ROWS]) -> Self {
+        let rows: Vec<u32> = raw_data.iter().map(|raw| raw & RAW_ROW_MASK).collect();
+        let modes = rows.iter().map(|raw| RowMode::infer(*raw)).collect();
+        Self { rows, modes }
+    }
+
+    /// Overrides the inferred mode of a row.  Useful after
+    /// [`from_raw_data`](`Self::from_raw_data`) where the layout of the
+    /// device's OTP is known.
+    ///
+    /// Marking a row holding data as [`RowMode::Blank`] indicates the bits
+    /// set are stray - due to a manufacturing defect, for example - and the
+    /// row is otherwise unwritten.
+    pub fn set_row_mode(&mut self, row: u16, mode: RowMode) -> Result<(), Error> {
+        let index = Self::index(row)?;
+        self.modes[index] = mode;
+        Ok(())
+    }
+
+    // Returns the index for a row, checking it is in range.
+    fn index(row: u16) -> Result<usize, Error> {
+        let index = row as usize;
+        if index >= TOTAL_OTP_ROWS {
+            return Err(Error::InvalidRow(row));
+        }
+        Ok(index)
+    }
+
+    /// Writes a 16-bit value to a row in ECC mode.
+    ///
+    /// The row must not have been written before.  However, as on the
+    /// hardware, a blank row with stray bits already set may be written if
+    /// those bits can be accommodated - either because they are also set in
+    /// the ECC encoded value, or by polarity reversal.
+    pub fn write_ecc(&mut self, row: u16, data: u16) -> Result<(), Error> {
+        let index = Self::index(row)?;
+        match self.modes[index] {
+            RowMode::Blank => {}
+            RowMode::Ecc => return Err(Error::EccRowAlreadyWritten(row)),
+            RowMode::Raw => {
+                return Err(Error::RowModeConflict {
+                    row,
+                    mode: RowMode::Raw,
+                });
+            }
+        }
+
+        let current = self.rows[index];
+        let encoded = ecc::encode(data);
+        let reversed = (encoded ^ (ecc::ECC_DATA_MASK | ecc::ECC_PARITY_MASK)) | ecc::ECC_BRP_MASK;
+        let value = if current & !encoded == 0 {
+            encoded
+        } else if current & !reversed == 0 {
+            reversed
+        } else {
+            return Err(Error::BitClear {
+                row,
+                current,
+                requested: encoded,
+            });
+        };
+
+        self.rows[index] = value;
+        self.modes[index] = RowMode::Ecc;
+        Ok(())
+    }
+
+    /// Rewrites a row already written in ECC mode with a new 16-bit value, by
+    /// writing the new value's ECC encoding in raw mode.
+    ///
+    /// This is only possible if the new encoding has every bit of the
+    /// existing one set, and is how USB_WHITE_LABEL_ADDR is pointed at a new
+    /// copy of the white label data.  The row remains an ECC row.  Blank rows
+    /// may also be written this way.
+    pub fn rewrite_ecc(&mut self, row: u16, data: u16) -> Result<(), Error> {
+        let index = Self::index(row)?;
+        if self.modes[index] == RowMode::Raw {
+            return Err(Error::RowModeConflict {
+                row,
+                mode: RowMode::Raw,
+            });
+        }
+
+        let current = self.rows[index];
+        let encoded = ecc::encode(data);
+        if current & !encoded != 0 {
+            return Err(Error::BitClear {
+                row,
+                current,
+                requested: encoded,
+            });
+        }
+
+        self.rows[index] = encoded;
+        self.modes[index] = RowMode::Ecc;
+        Ok(())
+    }
+
+    /// Writes a 24-bit value to a row in raw mode.
+    ///
+    /// `value` is the value the row must hold after the write.  As bits can
+    /// only be set, every bit already set in the row must also be set in
+    /// `value`.  Raw rows may be written multiple times, so long as this
+    /// holds - this is how flags rows are updated.
+    ///
+    /// Rows already written in ECC mode cannot be written in raw mode.  Use
+    /// [`OtpImage::rewrite_ecc`] to change an ECC row's value.
+    pub fn write_raw(&mut self, row: u16, value: u32) -> Result<(), Error> {
+        let index = Self::index(row)?;
+        if value & !RAW_ROW_MASK != 0 {
+            return Err(Error::InvalidRawValue { row, value });
+        }
+        if self.modes[index] == RowMode::Ecc {
+            return Err(Error::RowModeConflict {
+                row,
+                mode: RowMode::Ecc,
+            });
+        }
+
+        let current = self.rows[index];
+        if current & !value != 0 {
+            return Err(Error::BitClear {
+                row,
+                current,
+                requested: value,
+            });
+        }
+
+        self.rows[index] = value;
+        if value != 0 {
+            self.modes[index] = RowMode::Raw;
+        }
+        Ok(())
+    }
+
+    /// Applies a single write from a programming plan.
+    pub fn apply(&mut self, write: &OtpWrite) -> Result<(), Error> {
+        match write.mode() {
+            WriteMode::Ecc => match u16::try_from(write.value()) {
+                Ok(data) => self.write_ecc(write.row(), data),
+                Err(_) => Err(Error::InvalidRawValue {
+                    row: write.row(),
+                    value: write.value(),
+                }),
+            },
+            WriteMode::Raw => self.write_raw(write.row(), write.value()),
+            WriteMode::EccRewrite => match u16::try_from(write.value()) {
+                Ok(data) => self.rewrite_ecc(write.row(), data),
+                Err(_) => Err(Error::InvalidRawValue {
+                    row: write.row(),
+                    value: write.value(),
+                }),
+            },
+        }
+    }
+
+    /// Applies a programming plan, such as one from
+    /// [`OtpData::programming_plan`], in order.  Stops at, and returns, the
+    /// first error, leaving the image with the preceding writes applied.
+    pub fn apply_plan(&mut self, plan: &[OtpWrite]) -> Result<(), Error> {
+        plan.iter().try_for_each(|write| self.apply(write))
+    }
+
+    /// Reads a row in ECC mode, returning the (corrected, where necessary)
+    /// 16-bit value.  Blank rows read as 0.
+    ///
+    /// Returns an error if the row was written in raw mode, or holds an
+    /// uncorrectable ECC error.
+    pub fn read_ecc(&self, row: u16) -> Result<u16, Error> {
+        let decoded = self.decode_ecc(row)?;
+        if self.modes[row as usize] == RowMode::Raw {
+            return Err(Error::RowModeConflict {
+                row,
+                mode: RowMode::Raw,
+            });
+        }
+        if !decoded.is_ok() {
+            return Err(Error::UncorrectableEcc(row));
+        }
+        Ok(decoded.data())
+    }
+
+    /// ECC decodes a row, whatever mode it was written in, returning the full
+    /// decode result.
+    pub fn decode_ecc(&self, row: u16) -> Result<EccRow, Error> {
+        let index = Self::index(row)?;
+        Ok(ecc::decode(self.rows[index]))
+    }
+
+    /// Reads a row in raw mode, returning the 24-bit value.
+    pub fn read_raw(&self, row: u16) -> Result<u32, Error> {
+        let index = Self::index(row)?;
+        Ok(self.rows[index])
+    }
+
+    /// Returns the mode a row has been written in.
+    pub fn row_mode(&self, row: u16) -> Result<RowMode, Error> {
+        let index = Self::index(row)?;
+        Ok(self.modes[index])
+    }
+
+    /// Returns true if the row has not been written.
+    pub fn is_blank(&self, row: u16) -> Result<bool, Error> {
+        Ok(self.row_mode(row)? == RowMode::Blank)
+    }
+
+    /// Returns the raw 24-bit values of all rows.
+    pub fn raw_rows(&self) -> &[u32] {
+        &self.rows
+    }
+
+    /// Returns the values of all rows as read in ECC mode.  Rows which
+    /// cannot be ECC decoded (including raw rows) return their uncorrected
+    /// bottom 16 bits, as the hardware does.
+    pub fn ecc_rows(&self) -> Vec<u16> {
+        self.rows
+            .iter()
+            .map(|raw| ecc::decode(*raw).data())
+            .collect()
+    }
+
+    /// Extracts the USB white label data from this image.  See
+    /// [`OtpData::from_raw_otp_data`].
+    pub fn white_label_data(&self, strict: bool) -> Result<OtpData, WhiteLabelError> {
+        let raw: &[u32; TOTAL_OTP_ROWS] = self
+            .rows
+            .as_slice()
+            .try_into()
+            .expect("OtpImage always holds TOTAL_OTP_ROWS rows");
+        OtpData::from_raw_otp_data(raw, strict)
+    }
+
+    /// Extracts the bootrom configuration from this image.  See
+    /// [`BootConfig::from_raw_otp_data`].
+    pub fn boot_config(&self, strict: bool) -> Result<BootConfig, BootConfigError> {
+        let raw: &[u32; TOTAL_OTP_ROWS] = self
+            .rows
+            .as_slice()
+            .try_into()
+            .expect("OtpImage always holds TOTAL_OTP_ROWS rows");
+        BootConfig::from_raw_otp_data(raw, strict)
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_read_ecc() {
+        let mut image = OtpImage::new();
+        assert!(image.is_blank(0x100).unwrap());
+        assert_eq!(image.read_ecc(0x100), Ok(0));
+
+        image.write_ecc(0x100, 0x5b6b).unwrap();
+        assert_eq!(image.row_mode(0x100), Ok(RowMode::Ecc));
+        assert_eq!(image.read_ecc(0x100), Ok(0x5b6b));
+        assert_eq!(image.read_raw(0x100), Ok(0x00145b6b));
+
+        // ECC rows can only be written once, even with the same value
+        assert_eq!(
+            image.write_ecc(0x100, 0x5b6b),
+            Err(Error::EccRowAlreadyWritten(0x100))
+        );
+        assert!(matches!(
+            image.write_raw(0x100, 0x00ff_ffff),
+            Err(Error::RowModeConflict { row: 0x100, .. })
+        ));
+
+        // Even if the raw value is a valid ECC encoding with all the existing
+        // bits set
+        let old = image.read_raw(0x100).unwrap();
+        let new = (0..=u16::MAX)
+            .map(ecc::encode)
+            .find(|raw| *raw != old && raw & old == old)
+            .unwrap();
+        assert!(matches!(
+            image.write_raw(0x100, new),
+            Err(Error::RowModeConflict { row: 0x100, .. })
+        ));
+        assert_eq!(image.read_ecc(0x100), Ok(0x5b6b));
+    }
+
+    #[test]
+    fn test_rewrite_ecc() {
+        let mut image = OtpImage::new();
+        image.write_ecc(0x100, 0x5b6b).unwrap();
+        let old = image.read_raw(0x100).unwrap();
+
+        // A value whose encoding would clear existing bits is rejected
+        let mismatched = (0..=u16::MAX)
+            .find(|data| ecc::encode(*data) & old != old)
+            .unwrap();
+        assert_eq!(
+            image.rewrite_ecc(0x100, mismatched),
+            Err(Error::BitClear {
+                row: 0x100,
+                current: old,
+                requested: ecc::encode(mismatched),
+            })
+        );
+        assert_eq!(image.read_ecc(0x100), Ok(0x5b6b));
+
+        // One whose encoding has all the existing bits set is accepted
+        let new = (0..=u16::MAX)
+            .find(|data| *data != 0x5b6b && ecc::encode(*data) & old == old)
+            .unwrap();
+        image.rewrite_ecc(0x100, new).unwrap();
+        assert_eq!(image.row_mode(0x100), Ok(RowMode::Ecc));
+        assert_eq!(image.read_ecc(0x100), Ok(new));
+
+        // Raw rows can't be rewritten this way
+        image.write_raw(0x101, 0x0000_0001).unwrap();
+        assert!(matches!(
+            image.rewrite_ecc(0x101, 0xffff),
+            Err(Error::RowModeConflict { row: 0x101, .. })
+        ));
+    }
+
+    #[test]
+    fn test_write_read_raw() {
+        let mut image = OtpImage::new();
+        image.write_raw(0x059, 0x0040_0000).unwrap();
+        image.write_raw(0x059, 0x0040_0001).unwrap();
+        assert_eq!(image.read_raw(0x059), Ok(0x0040_0001));
+        assert_eq!(image.row_mode(0x059), Ok(RowMode::Raw));
+
+        // Bits can't be cleared
+        assert!(matches!(
+            image.write_raw(0x059, 0x0000_0001),
+            Err(Error::BitClear { row: 0x059, .. })
+        ));
+
+        // Only 24 bits available
+        assert!(matches!(
+            image.write_raw(0x05a, 0x0100_0000),
+            Err(Error::InvalidRawValue { .. })
+        ));
+
+        // Raw rows can't be written or read as ECC
+        assert!(image.write_ecc(0x059, 1).is_err());
+        assert!(image.read_ecc(0x059).is_err());
+
+        assert_eq!(image.write_raw(0x1000, 0), Err(Error::InvalidRow(0x1000)));
+    }
+
+    #[test]
+    fn test_write_ecc_over_set_bit() {
+        let mut raw = [0u32; TOTAL_OTP_ROWS];
+        // A stray bit which is also set in the encoded value
+        raw[0x100] = 1;
+        // A stray bit which is clear in the encoded value
+        raw[0x101] = 1 << 2;
+        // Two stray bits, which can't be accommodated
+        raw[0x102] = 0b101;
+        let mut image = OtpImage::from_raw_data(&raw);
+
+        // Stray bits are inferred as raw rows
+        assert_eq!(image.row_mode(0x100), Ok(RowMode::Raw));
+        for row in 0x100..=0x102 {
+            image.set_row_mode(row, RowMode::Blank).unwrap();
+        }
+
+        image.write_ecc(0x100, 0x5b6b).unwrap();
+        assert_eq!(image.read_ecc(0x100), Ok(0x5b6b));
+
+        image.write_ecc(0x101, 0x5b6b).unwrap();
+        assert_eq!(image.read_ecc(0x101), Ok(0x5b6b));
+        assert_eq!(
+            image.decode_ecc(0x101).unwrap().status(),
+            EccStatus::PolarityReversed
+        );
+
+        assert!(matches!(
+            image.write_ecc(0x102, 0x5b6b),
+            Err(Error::BitClear { row: 0x102, .. })
+        ));
+    }
+
+    #[test]
+    fn test_from_raw_data() {
+        let mut raw = [0u32; TOTAL_OTP_ROWS];
+        raw[0x000] = 0x00145b6b;
+        raw[0x059] = 0x0040_ff77;
+        let mut image = OtpImage::from_raw_data(&raw);
+        assert_eq!(image.row_mode(0x000), Ok(RowMode::Ecc));
+        assert_eq!(image.row_mode(0x059), Ok(RowMode::Raw));
+        assert_eq!(image.row_mode(0x05a), Ok(RowMode::Blank));
+        assert_eq!(image.ecc_rows()[0], 0x5b6b);
+
+        image.set_row_mode(0x000, RowMode::Raw).unwrap();
+        image.write_raw(0x000, 0x00ff_5b6b).unwrap();
+    }
+
+    #[test]
+    fn test_white_label_data() {
+        let otp_data = OtpData::from_json(include_str!("../json/sample-wl.json")).unwrap();
+        let mut image = OtpImage::new();
+        for (ii, row) in otp_data.rows().iter().enumerate() {
+            image.write_ecc(0x100 + ii as u16, *row).unwrap();
+        }
+        image.write_ecc(0x05c, 0x100).unwrap();
+        for row in 0x059..=0x05b {
+            image.write_raw(row, otp_data.usb_boot_flags()).unwrap();
+        }
+
+        let parsed = image.white_label_data(true).unwrap();
+        assert_eq!(parsed.to_json().unwrap(), otp_data.to_json().unwrap());
+    }
+
+    #[test]
+    fn test_apply_plan() {
+        let otp_data = OtpData::from_json(include_str!("../json/sample-wl.json")).unwrap();
+        let plan = otp_data.programming_plan(0x100, false, None).unwrap();
+        let mut image = OtpImage::new();
+        image.apply_plan(&plan).unwrap();
+
+        let parsed = image.white_label_data(true).unwrap();
+        assert_eq!(parsed.to_json().unwrap(), otp_data.to_json().unwrap());
+
+        // The plan can't be applied twice, as the ECC rows are already written
+        assert_eq!(
+            image.apply_plan(&plan),
+            Err(Error::EccRowAlreadyWritten(0x05c))
+        );
+    }
+}
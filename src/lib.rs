@@ -22,7 +22,8 @@
 //! and Raspberry Pi's standard USB white labelling JSON schema.
 //!
 //! It is `no_std` compatible, making it suitable for WASM, embedded and other
-//! minimal environments.
+//! minimal environments.  Most of it requires `alloc`, but the [`ecc`],
+//! [`heapless`] and [`plan`] modules don't.
 //!
 //! It is used by [`pico⚡flash`](https://picoflash.org) to apply whitelabelling
 //! configurations to Raspberry Pi Pico 2 and other RP2350 devices.
@@ -32,14 +33,72 @@
 //! - Generate OTP binary data from whitelabel JSON configurations.
 //! - Parses and validates OTP binary data dumps, extracting whitelabel
 //!   information.
-//! - Supports `picotool` whitelabel schema.
-//! - `no_std` compatible (requires `alloc`).
+//! - Computes and checks the RP2350's OTP ECC parity bits, so raw (24-bit)
+//!   OTP data can be generated and verified.
+//! - Models the RP2350's entire OTP memory, enforcing its write rules, so
+//!   programming can be staged and validated offline.
+//! - Checks OTP layouts for problems caused by errata RP2350-E17.
+//! - Finds free, E17-safe, unlocked OTP regions to store whitelabel data in.
+//! - Plans updates to devices which already have whitelabel data.
+//! - Votes on each bit of redundant raw OTP rows, as the bootrom does.
+//! - Decodes and generates the USB_BOOT_FLAGS register, including options
+//!   unrelated to whitelabelling, such as DP/DM swap.
+//! - Decodes and generates the bootrom configuration rows, such as
+//!   BOOT_FLAGS0 and BOOT_FLAGS1.
+//! - Decodes and generates OTP page locks.
+//! - Provisions and decodes secure boot key fingerprints.
+//! - Decodes and, with explicit acknowledgement, generates the critical
+//!   secure boot and debug flags.
+//! - Decodes the chip ID, including the default USB serial number, and
+//!   factory calibration data.
+//! - Resolves the USB identity a device presents in BOOTSEL mode, merging
+//!   whitelabel data with the bootrom defaults, and generates the USB
+//!   descriptors and SCSI INQUIRY response it reports.
+//! - Renders the INFO_UF2.TXT and INDEX.HTM files served in BOOTSEL mode.
+//! - Encodes and decodes whitelabel data without allocation, for firmware
+//!   running on the RP2350 itself.
+//! - Generates the ordered list of OTP writes needed to program whitelabel
+//!   data at a chosen OTP row.
+//! - Describes each whitelabel field - its type, default value, maximum
+//!   length, supported encodings and `picotool` JSON path - and gets and sets
+//!   fields generically.
+//! - Reports the whitelabel row budget - rows used and remaining, and the
+//!   maximum length each string field could be set to.
+//! - Places whitelabel strings to fit as much as possible - the longest last,
+//!   and optionally sharing rows between identical or suffix strings - or in
+//!   `picotool`'s order.
+//! - Supports `picotool` whitelabel schema, with a compatibility mode
+//!   following `picotool`'s string placement and `bcd` conversion, as
+//!   understood by `pico-otp` - not yet verified against `picotool` itself.
+//! - `no_std` compatible.  The [`ecc`], [`heapless`] and [`plan`] modules
+//!   don't require `alloc` - see [Cargo Features](#cargo-features).
+//!
+//! # Cargo Features
+//!
+//! - `alloc` - everything other than the [`ecc`], [`heapless`] and [`plan`]
+//!   modules, and the white label [`fields`](whitelabel::fields) table.
+//! - `serde_json` - JSON import and export, including the `picotool`
+//!   whitelabel schema.  Implies `alloc`.
+//! - `std` - enables `std` support in dependencies.  Implies `alloc`.
+//! - `cli` - builds the `pico-otp` binary.  Implies `std` and `serde_json`.
+//!
+//! Only `alloc` is enabled by default, so the library can be used in firmware
+//! without JSON support.  To use JSON:
+//!
+//! ```toml
+//! pico-otp = { version = "0.2", features = ["serde_json"] }
+//! ```
+//!
+//! To install the `pico-otp` binary, run
+//! `cargo install pico-otp --features cli`.
 //!
 //! # Example - JSON fragment to OTP data
 //!
 //! ```rust
+//! # #[cfg(feature = "serde_json")]
 //! use pico_otp::OtpData;
 //!
+//! # #[cfg(feature = "serde_json")]
 //! # fn main() -> Result<(), pico_otp::WhiteLabelError> {
 //! // Load the JSON file
 //! let json = r#"
@@ -58,13 +117,21 @@
 //!
 //! // And the boot flags
 //! let usb_boot_flags = otp_data.usb_boot_flags();
+//!
+//! // Or, get every OTP write required, in order, to store the white label
+//! // data at a suitable row index.  0x100 is a common offset choice, as it's
+//! // what picotool uses.
+//! let plan = otp_data.programming_plan(0x100, false, None)?;
+//! for write in plan.iter() {
+//!     // Write write.value() to row write.row(), in write.mode() (ECC or raw)
+//! }
 //! #   Ok(())
 //! # }
+//! # #[cfg(not(feature = "serde_json"))]
+//! # fn main() {}
 //!
-//! // Now, write the OTP data at a suitable row index, using ECC mode.
-//! // 0x100 is a common offset choice, as it's what picotool uses.
-//! //
-//! // You will also need to:
+//! // If writing the OTP data yourself, write it at a suitable row index,
+//! // using ECC mode.  You will also need to:
 //! // - Set the USB_WHITE_LABEL_ADDR row (0x05c) to point to 0x100, or
 //! //   whatever row you chose.
 //! // - Write usb_boot_flags to rows 0x059, 0x05a and 0x05b - USB_BOOT_FLAGS,
@@ -75,8 +142,10 @@
 //! # Example - JSON file to OTP data
 //!
 //! ```rust
+//! # #[cfg(feature = "serde_json")]
 //! use pico_otp::OtpData;
 //!
+//! # #[cfg(feature = "serde_json")]
 //! # fn main() -> Result<(), pico_otp::WhiteLabelError> {
 //! // Load the JSON file
 //! let json = std::fs::read_to_string("json/sample-wl.json")
@@ -92,6 +161,8 @@
 //! let usb_boot_flags = otp_data.usb_boot_flags();
 //! #   Ok(())
 //! # }
+//! # #[cfg(not(feature = "serde_json"))]
+//! # fn main() {}
 //!
 //! // Now write these to OTP memory on the RP2350.
 //! ```
@@ -99,10 +170,12 @@
 //! # Example - Rust code to OTP data
 //!
 //! ```rust
+//! # #[cfg(feature = "alloc")]
 //! use pico_otp::{WhiteLabelStruct, OtpData};
 //!
 //! // Create the WhiteLabelStruct, customizing every possible value,
 //! // including Unicode strings for those that support it.
+//! # #[cfg(feature = "alloc")]
 //! # fn main() -> Result<(), pico_otp::WhiteLabelError> {
 //! let mut wls = WhiteLabelStruct::default();
 //! wls.set_vid(0x1234);
@@ -129,18 +202,82 @@
 //! let ecc_rows = otp_data.to_le_ecc_bytes();
 //! #   Ok(())
 //! # }
+//! # #[cfg(not(feature = "alloc"))]
+//! # fn main() {}
 //!
 //! // Now write these to OTP memory on the RP2350.
 //! ```
 
 #![no_std]
+// The tests predate running clippy over test targets, and are left as written.
+#![cfg_attr(
+    test,
+    allow(
+        clippy::bool_assert_comparison,
+        clippy::expect_fun_call,
+        clippy::needless_bool,
+        clippy::needless_borrow,
+        clippy::unnecessary_to_owned,
+        clippy::unused_unit
+    )
+)]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
+#[cfg(feature = "alloc")]
+pub mod boot;
+#[cfg(feature = "alloc")]
+pub use boot::{BootConfig, Error as BootConfigError};
+#[cfg(feature = "alloc")]
+pub mod bootkey;
+#[cfg(feature = "alloc")]
+pub use bootkey::{BootKeyHash, BootKeyState, BootKeys, Error as BootKeyError};
+#[cfg(feature = "alloc")]
+pub mod crit;
+#[cfg(feature = "alloc")]
+pub use crit::{CritFlags, Error as CritFlagsError};
+pub mod ecc;
+#[cfg(feature = "alloc")]
+pub mod errata;
+#[cfg(feature = "alloc")]
+pub use errata::E17Issue;
+#[cfg(feature = "alloc")]
+pub mod flags;
+#[cfg(feature = "alloc")]
+pub use flags::{BootFlags0, BootFlags1, UsbBootFlags};
+pub mod heapless;
+#[cfg(feature = "alloc")]
+pub mod identity;
+#[cfg(feature = "alloc")]
+pub use identity::ChipIdentity;
+#[cfg(feature = "alloc")]
+pub mod image;
+#[cfg(feature = "alloc")]
+pub mod lock;
+#[cfg(feature = "alloc")]
+pub mod msd;
+#[cfg(feature = "alloc")]
+pub use image::{Error as OtpImageError, OtpImage, RowMode};
+#[cfg(feature = "alloc")]
+pub use lock::{Error as PageLockError, LockLevel, PageLock, PageLocks};
+pub mod plan;
+pub use plan::{OtpWrite, WriteMode};
+#[cfg(feature = "alloc")]
+pub mod region;
+#[cfg(feature = "alloc")]
+pub mod usb;
+#[cfg(feature = "alloc")]
+pub mod vote;
+#[cfg(feature = "alloc")]
+pub use region::{RegionSearch, RegionStrategy};
+#[cfg(feature = "alloc")]
+pub use usb::{Error as UsbError, UsbIdentity};
 pub mod whitelabel;
-pub use whitelabel::{Error as WhiteLabelError, OtpData, WhiteLabelStruct};
+#[cfg(feature = "alloc")]
+pub use whitelabel::{Error as WhiteLabelError, OtpData, UpdateKind, UpdatePlan, WhiteLabelStruct};
 
-#[cfg(test)]
+#[cfg(all(test, feature = "serde_json"))]
 mod tests {
     extern crate std;
     use super::*;
@@ -0,0 +1,450 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! OTP page locks.
+//!
+//! Each of the 64 OTP pages has two raw lock rows, PAGEn_LOCK0 and
+//! PAGEn_LOCK1, starting at 0xf80.  Each lock row holds three copies of an
+//! 8-bit lock value, in bits 0-7, 8-15 (R1) and 16-23 (R2), which are voted
+//! on bit by bit, like other redundant raw rows - see [`vote`].
+//!
+//! PAGEn_LOCK0 holds the hardware key configuration:
+//! - KEY_W (bits 0-2) - the key (1-6) required to write the page, or 0.
+//! - KEY_R (bits 3-5) - the key (1-6) required to read the page, or 0.
+//! - NO_KEY_STATE (bit 6) - whether the page is read-only or inaccessible
+//!   when a key is required but hasn't been entered.
+//!
+//! PAGEn_LOCK1 holds the lock levels, each thermometer coded so it can only
+//! ever be made less permissive:
+//! - LOCK_S (bits 0-1) - for secure accesses, including the bootrom.
+//! - LOCK_NS (bits 2-3) - for non-secure accesses.
+//! - LOCK_BL (bits 4-5) - for bootloader (such as PICOBOOT) accesses.  This
+//!   is enforced by the bootloader, not the hardware.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::image::{OTP_PAGE_ROWS, OTP_PAGES, OtpImage};
+use crate::plan::OtpWrite;
+use crate::vote::{self, BitDisagreement};
+use crate::whitelabel::TOTAL_OTP_ROWS;
+
+/// OTP row index for PAGE0_LOCK0.  PAGEn_LOCK0 is at this row plus 2n, and
+/// PAGEn_LOCK1 immediately follows it.
+pub const OTP_ROW_PAGE0_LOCK0: u16 = 0xf80;
+
+/// Errors that can occur while decoding or planning OTP page locks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The three copies within the given OTP page lock row do not all match.
+    NonMatchingPageLock(u16),
+
+    /// The requested OTP page lock cannot be applied, usually because it is
+    /// less restrictive than the existing lock.  The String contains details.
+    PageLockNotPossible(String),
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::NonMatchingPageLock(row) => {
+                write!(f, "The copies in OTP page lock row {row:#05x} do not match")
+            }
+            Error::PageLockNotPossible(s) => write!(f, "OTP page lock not possible: {s}"),
+        }
+    }
+}
+
+/// The access permitted to an OTP page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum LockLevel {
+    /// The page can be read and written.
+    #[default]
+    ReadWrite,
+
+    /// The page can be read, but not written.
+    ReadOnly,
+
+    /// The page can neither be read nor written.
+    Inaccessible,
+}
+
+impl LockLevel {
+    // Decodes a 2-bit lock field.  The reserved value 2 is treated as
+    // inaccessible, as the hardware does.
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0x3 {
+            0 => LockLevel::ReadWrite,
+            1 => LockLevel::ReadOnly,
+            _ => LockLevel::Inaccessible,
+        }
+    }
+
+    fn bits(&self) -> u8 {
+        match self {
+            LockLevel::ReadWrite => 0,
+            LockLevel::ReadOnly => 1,
+            LockLevel::Inaccessible => 3,
+        }
+    }
+}
+
+impl core::fmt::Display for LockLevel {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LockLevel::ReadWrite => write!(f, "read/write"),
+            LockLevel::ReadOnly => write!(f, "read-only"),
+            LockLevel::Inaccessible => write!(f, "inaccessible"),
+        }
+    }
+}
+
+/// The lock configuration of a single OTP page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PageLock {
+    /// KEY_W - the hardware key (1-6) required to write the page, or 0 if
+    /// none.
+    pub key_w: u8,
+
+    /// KEY_R - the hardware key (1-6) required to read the page, or 0 if
+    /// none.
+    pub key_r: u8,
+
+    /// NO_KEY_STATE - true if the page is inaccessible, rather than
+    /// read-only, when a key is required but hasn't been entered.
+    pub no_key_inaccessible: bool,
+
+    /// LOCK_S - the access permitted to secure code, including the bootrom.
+    pub secure: LockLevel,
+
+    /// LOCK_NS - the access permitted to non-secure code.
+    pub non_secure: LockLevel,
+
+    /// LOCK_BL - the access permitted to the bootloader, such as via
+    /// PICOBOOT.
+    pub bootloader: LockLevel,
+}
+
+impl PageLock {
+    /// Decodes a page lock from its voted 8-bit PAGEn_LOCK0 and PAGEn_LOCK1
+    /// values.
+    pub fn from_lock_values(lock0: u8, lock1: u8) -> Self {
+        Self {
+            key_w: lock0 & 0x7,
+            key_r: (lock0 >> 3) & 0x7,
+            no_key_inaccessible: lock0 & (1 << 6) != 0,
+            secure: LockLevel::from_bits(lock1),
+            non_secure: LockLevel::from_bits(lock1 >> 2),
+            bootloader: LockLevel::from_bits(lock1 >> 4),
+        }
+    }
+
+    /// Returns the 8-bit PAGEn_LOCK0 and PAGEn_LOCK1 values for this lock.
+    pub fn lock_values(&self) -> Result<(u8, u8), Error> {
+        if self.key_w > 6 || self.key_r > 6 {
+            return Err(Error::PageLockNotPossible(format!(
+                "Key indexes must be 1-6, or 0 for none: KEY_W {}, KEY_R {}",
+                self.key_w, self.key_r
+            )));
+        }
+        let lock0 = self.key_w | (self.key_r << 3) | ((self.no_key_inaccessible as u8) << 6);
+        let lock1 =
+            self.secure.bits() | (self.non_secure.bits() << 2) | (self.bootloader.bits() << 4);
+        Ok((lock0, lock1))
+    }
+
+    /// Returns true if the bootrom can read this page, both as secure code
+    /// and as the bootloader - so it can hold data such as the white label
+    /// data.
+    pub fn readable_by_bootrom(&self) -> bool {
+        self.secure != LockLevel::Inaccessible
+            && self.bootloader != LockLevel::Inaccessible
+            && !(self.key_r != 0 && self.no_key_inaccessible)
+    }
+
+    /// Returns true if this page can be written by the bootloader without a
+    /// key - as required to program it using PICOBOOT.
+    pub fn writable_by_bootloader(&self) -> bool {
+        self.secure == LockLevel::ReadWrite
+            && self.bootloader == LockLevel::ReadWrite
+            && self.key_w == 0
+    }
+}
+
+/// A bit which does not hold the same value in all three copies within a
+/// lock row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockDisagreement {
+    row: u16,
+    disagreement: BitDisagreement,
+}
+
+impl LockDisagreement {
+    /// Returns the lock row.
+    pub fn row(&self) -> u16 {
+        self.row
+    }
+
+    /// Returns the disagreeing bit, numbered within the 8-bit lock value.
+    pub fn disagreement(&self) -> BitDisagreement {
+        self.disagreement
+    }
+}
+
+impl core::fmt::Display for LockDisagreement {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "OTP row {:#05x}: {}", self.row, self.disagreement)
+    }
+}
+
+/// The lock configuration of all 64 OTP pages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageLocks {
+    pages: [PageLock; OTP_PAGES],
+    disagreements: Vec<LockDisagreement>,
+}
+
+impl Default for PageLocks {
+    fn default() -> Self {
+        Self {
+            pages: [PageLock::default(); OTP_PAGES],
+            disagreements: Vec::new(),
+        }
+    }
+}
+
+impl PageLocks {
+    /// Decodes the lock rows from a complete raw (non-ECC) OTP dump.
+    ///
+    /// If `strict` is true, all three copies within every lock row must
+    /// match.  Otherwise they are voted on bit by bit, and any disagreements
+    /// are available from [`disagreements`](`Self::disagreements`).
+    pub fn from_raw_otp_data(
+        raw_data: &[u32; TOTAL_OTP_ROWS],
+        strict: bool,
+    ) -> Result<Self, Error> {
+        let mut locks = Self::default();
+        for page in 0..OTP_PAGES {
+            let lock0_row = lock_row(page);
+            let mut values = [0u8; 2];
+            for (ii, row) in [lock0_row, lock0_row + 1].into_iter().enumerate() {
+                let raw = raw_data[row as usize];
+                let vote = vote::vote_2_of_3([raw & 0xff, (raw >> 8) & 0xff, (raw >> 16) & 0xff]);
+                if strict && !vote.is_unanimous() {
+                    return Err(Error::NonMatchingPageLock(row));
+                }
+                locks
+                    .disagreements
+                    .extend(
+                        vote.disagreements()
+                            .iter()
+                            .map(|disagreement| LockDisagreement {
+                                row,
+                                disagreement: *disagreement,
+                            }),
+                    );
+                values[ii] = vote.value() as u8;
+            }
+            locks.pages[page] = PageLock::from_lock_values(values[0], values[1]);
+        }
+        Ok(locks)
+    }
+
+    /// Decodes the lock rows from an [`OtpImage`].  See
+    /// [`from_raw_otp_data`](`Self::from_raw_otp_data`).
+    pub fn from_image(image: &OtpImage, strict: bool) -> Result<Self, Error> {
+        let raw: &[u32; TOTAL_OTP_ROWS] = image
+            .raw_rows()
+            .try_into()
+            .expect("OtpImage always holds TOTAL_OTP_ROWS rows");
+        Self::from_raw_otp_data(raw, strict)
+    }
+
+    /// Returns the lock configuration of the given page (0-63).
+    pub fn page(&self, page: usize) -> Option<&PageLock> {
+        self.pages.get(page)
+    }
+
+    /// Returns the lock configuration of every page.
+    pub fn pages(&self) -> &[PageLock] {
+        &self.pages
+    }
+
+    /// Returns every bit which differs between the copies in a lock row, in
+    /// row order.
+    pub fn disagreements(&self) -> &[LockDisagreement] {
+        &self.disagreements
+    }
+
+    /// Returns the pages containing any of the `num_rows` rows starting at
+    /// `white_label_addr` which the bootrom or bootloader cannot read, so
+    /// cannot hold the white label data.  See
+    /// [`PageLock::readable_by_bootrom`].
+    ///
+    /// To check a lock which has not yet been applied, first apply it to a
+    /// copy of the image with [`plan_page_lock`], then decode that.
+    pub fn unreadable_white_label_pages(&self, white_label_addr: u16, num_rows: usize) -> Vec<u8> {
+        let start = white_label_addr as usize;
+        let end = (start + num_rows.max(1)).min(TOTAL_OTP_ROWS);
+        (start / OTP_PAGE_ROWS..end.div_ceil(OTP_PAGE_ROWS))
+            .filter(|page| !self.pages[*page].readable_by_bootrom())
+            .map(|page| page as u8)
+            .collect()
+    }
+}
+
+/// Returns the raw writes required to lock `page` of `image` to `lock`.
+///
+/// Lock rows are raw, so bits can only be set - a lock can only be made
+/// less permissive, and key indexes can only change if the new index's bits
+/// include the old one's.  Returns [`Error::PageLockNotPossible`] if the
+/// requested lock would require any bit to be cleared, including bits set
+/// in only one copy of a lock row.  Rows which need no change are not
+/// written.
+pub fn plan_page_lock(
+    image: &OtpImage,
+    page: usize,
+    lock: &PageLock,
+) -> Result<Vec<OtpWrite>, Error> {
+    if page >= OTP_PAGES {
+        return Err(Error::PageLockNotPossible(format!(
+            "No such OTP page {page}"
+        )));
+    }
+    let (lock0, lock1) = lock.lock_values()?;
+    let lock0_row = lock_row(page);
+
+    let mut writes = Vec::new();
+    let mut problems: Vec<String> = Vec::new();
+    for (row, value) in [(lock0_row, lock0), (lock0_row + 1, lock1)] {
+        let current = image.read_raw(row).unwrap_or(0);
+        let wanted = value as u32 * 0x01_0101;
+        if current & !wanted != 0 {
+            problems.push(format!(
+                "OTP row {row:#05x} is {current:#08x}, which can't be changed to {wanted:#08x}"
+            ));
+        } else if current != wanted {
+            writes.push(OtpWrite::raw(row, wanted));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(writes)
+    } else {
+        Err(Error::PageLockNotPossible(format!(
+            "page {page}: {}",
+            problems.join(", ")
+        )))
+    }
+}
+
+// Returns the PAGEn_LOCK0 row for the given page.
+fn lock_row(page: usize) -> u16 {
+    OTP_ROW_PAGE0_LOCK0 + 2 * page as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_lock_values() {
+        let lock = PageLock {
+            key_w: 2,
+            key_r: 1,
+            no_key_inaccessible: true,
+            secure: LockLevel::ReadOnly,
+            non_secure: LockLevel::Inaccessible,
+            bootloader: LockLevel::ReadOnly,
+        };
+        assert_eq!(lock.lock_values().unwrap(), (0x4a, 0x1d));
+        assert_eq!(PageLock::from_lock_values(0x4a, 0x1d), lock);
+        assert!(!lock.readable_by_bootrom());
+
+        // Inaccessible to the bootloader alone is enough
+        let bootloader = PageLock {
+            bootloader: LockLevel::Inaccessible,
+            ..Default::default()
+        };
+        assert!(!bootloader.readable_by_bootrom());
+        assert!(PageLock::default().readable_by_bootrom());
+
+        // The reserved lock level is treated as inaccessible
+        assert_eq!(
+            PageLock::from_lock_values(0, 0x02).secure,
+            LockLevel::Inaccessible
+        );
+
+        let bad_key = PageLock {
+            key_w: 7,
+            ..Default::default()
+        };
+        assert!(bad_key.lock_values().is_err());
+    }
+
+    #[test]
+    fn test_plan_page_lock() {
+        let mut image = OtpImage::new();
+        let read_only = PageLock {
+            bootloader: LockLevel::ReadOnly,
+            ..Default::default()
+        };
+        let writes = plan_page_lock(&image, 4, &read_only).unwrap();
+        assert_eq!(writes, [OtpWrite::raw(0xf89, 0x10_1010)]);
+        image.apply_plan(&writes).unwrap();
+
+        let locks = PageLocks::from_image(&image, true).unwrap();
+        assert_eq!(locks.page(4), Some(&read_only));
+        assert!(locks.unreadable_white_label_pages(0x100, 64).is_empty());
+
+        // Already applied
+        assert!(plan_page_lock(&image, 4, &read_only).unwrap().is_empty());
+
+        // Upgrade to inaccessible
+        let inaccessible = PageLock {
+            secure: LockLevel::Inaccessible,
+            bootloader: LockLevel::Inaccessible,
+            ..Default::default()
+        };
+        let writes = plan_page_lock(&image, 4, &inaccessible).unwrap();
+        image.apply_plan(&writes).unwrap();
+        let locks = PageLocks::from_image(&image, true).unwrap();
+        assert_eq!(locks.unreadable_white_label_pages(0x0f0, 32), [4]);
+        assert!(locks.unreadable_white_label_pages(0x0c0, 64).is_empty());
+
+        // Downgrade refused
+        assert!(matches!(
+            plan_page_lock(&image, 4, &read_only),
+            Err(Error::PageLockNotPossible(_))
+        ));
+    }
+
+    #[test]
+    fn test_page_lock_voting() {
+        let mut image = OtpImage::new();
+        // Page 5's LOCK1 R2 copy is partially programmed
+        image.write_raw(0xf8b, 0x01_0000).unwrap();
+        let locks = PageLocks::from_image(&image, false).unwrap();
+        assert_eq!(locks.page(5), Some(&PageLock::default()));
+        assert_eq!(locks.disagreements().len(), 1);
+        assert_eq!(locks.disagreements()[0].row(), 0xf8b);
+        assert_eq!(locks.disagreements()[0].disagreement().dissenting_copy(), 2);
+        assert!(matches!(
+            PageLocks::from_image(&image, true),
+            Err(Error::NonMatchingPageLock(0xf8b))
+        ));
+
+        // The partially programmed lock can be completed, but not removed
+        let lock = PageLock {
+            secure: LockLevel::ReadOnly,
+            ..Default::default()
+        };
+        assert_eq!(
+            plan_page_lock(&image, 5, &lock).unwrap(),
+            [OtpWrite::raw(0xf8b, 0x01_0101)]
+        );
+        assert!(plan_page_lock(&image, 5, &PageLock::default()).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! The files served by the BOOTSEL mass storage device which white label
+//! data affects.
+//!
+//! The bootrom's virtual drive contains INFO_UF2.TXT, which includes the
+//! `uf2_model` and `uf2_board_id` fields, and INDEX.HTM, which redirects to
+//! `redirect_url`, labelled `redirect_name`.  The fields are inserted
+//! verbatim - they are not escaped - so the same is done here.
+//!
+//! The surrounding text follows the bootrom's templates as understood by
+//! this crate.  It has not been checked against files read from a real
+//! drive, so may not match the bootrom's output byte for byte.  The default
+//! `redirect_url` is not known, so INDEX.HTM is only rendered if it is white
+//! labelled.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::usb::UsbIdentity;
+use crate::whitelabel::WhiteLabelStruct;
+
+/// Name of the file containing the UF2 bootloader information.
+pub const INFO_UF2_TXT_NAME: &str = "INFO_UF2.TXT";
+
+/// Name of the file redirecting to the board's web page.
+pub const INDEX_HTM_NAME: &str = "INDEX.HTM";
+
+/// Returns the expected contents of INFO_UF2.TXT, as served by the bootrom
+/// for a device with white label data `wls`.  Fields not present in `wls` take
+/// their default values.
+pub fn info_uf2_txt(wls: &WhiteLabelStruct) -> Vec<u8> {
+    UsbIdentity::resolve(wls, None).info_uf2_txt().into_bytes()
+}
+
+/// Returns the expected contents of INDEX.HTM, as served by the bootrom for
+/// a device with white label data `wls`, or `None` if `wls` doesn't set
+/// `redirect_url`, as the bootrom's default is not known.  If `redirect_name`
+/// isn't set, it takes its default value.
+pub fn index_htm(wls: &WhiteLabelStruct) -> Option<Vec<u8>> {
+    UsbIdentity::resolve(wls, None)
+        .index_htm()
+        .map(String::into_bytes)
+}
+
+pub(crate) fn render_info_uf2_txt(model: &str, board_id: &str) -> String {
+    format!("UF2 Bootloader v1.0\nModel: {model}\nBoard-ID: {board_id}\n")
+}
+
+pub(crate) fn render_index_htm(url: &str, name: &str) -> String {
+    format!(
+        "<html><head><meta http-equiv=\"refresh\" content=\"0;URL='{url}'\"/></head><body>Redirecting to <a href='{url}'>{name}</a></body></html>\n"
+    )
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files() {
+        let wls = WhiteLabelStruct::default();
+        assert_eq!(
+            info_uf2_txt(&wls),
+            b"UF2 Bootloader v1.0\nModel: Raspberry Pi RP2350\nBoard-ID: RP2350\n"
+        );
+
+        // The example in docs/USB.md
+        let json = r#"{
+            "volume": {
+                "label": "TestPi Boot",
+                "redirect_url": "https://www.raspberrypi.com/news/",
+                "redirect_name": "Some News About Stuff",
+                "model": "My Test Pi",
+                "board_id": "TPI-RP2350"
+            }
+        }"#;
+        let wls = WhiteLabelStruct::from_json(json).unwrap();
+        assert_eq!(
+            info_uf2_txt(&wls),
+            b"UF2 Bootloader v1.0\nModel: My Test Pi\nBoard-ID: TPI-RP2350\n"
+        );
+        assert_eq!(
+            index_htm(&wls).unwrap(),
+            b"<html><head><meta http-equiv=\"refresh\" content=\"0;URL='https://www.raspberrypi.com/news/'\"/></head><body>Redirecting to <a href='https://www.raspberrypi.com/news/'>Some News About Stuff</a></body></html>\n"
+        );
+    }
+
+    #[test]
+    fn test_unset_fields_default() {
+        // Each field set on its own, with the other taking its default
+        let mut wls = WhiteLabelStruct::default();
+        wls.set_uf2_model("Model Only").unwrap();
+        assert_eq!(
+            info_uf2_txt(&wls),
+            b"UF2 Bootloader v1.0\nModel: Model Only\nBoard-ID: RP2350\n"
+        );
+
+        let mut wls = WhiteLabelStruct::default();
+        wls.set_uf2_board_id("BOARD-ONLY").unwrap();
+        assert_eq!(
+            info_uf2_txt(&wls),
+            b"UF2 Bootloader v1.0\nModel: Raspberry Pi RP2350\nBoard-ID: BOARD-ONLY\n"
+        );
+
+        let mut wls = WhiteLabelStruct::default();
+        wls.set_redirect_url("https://example.com/").unwrap();
+        assert_eq!(
+            index_htm(&wls).unwrap(),
+            b"<html><head><meta http-equiv=\"refresh\" content=\"0;URL='https://example.com/'\"/></head><body>Redirecting to <a href='https://example.com/'>raspberrypi.com</a></body></html>\n"
+        );
+
+        // Without a redirect URL, INDEX.HTM is unknown - whether or not the
+        // name is set
+        let mut wls = WhiteLabelStruct::default();
+        assert_eq!(index_htm(&wls), None);
+        wls.set_redirect_name("Name Only").unwrap();
+        assert_eq!(index_htm(&wls), None);
+        assert_eq!(
+            info_uf2_txt(&wls),
+            b"UF2 Bootloader v1.0\nModel: Raspberry Pi RP2350\nBoard-ID: RP2350\n"
+        );
+    }
+
+    #[test]
+    fn test_fields_inserted_verbatim() {
+        let mut wls = WhiteLabelStruct::default();
+        wls.set_uf2_model("A <b>&\"'</b> model").unwrap();
+        wls.set_uf2_board_id("ID\nSecond").unwrap();
+        wls.set_redirect_url("https://e.com/?a=1&b='2'").unwrap();
+        wls.set_redirect_name("<i>Name</i> & co").unwrap();
+        assert_eq!(
+            info_uf2_txt(&wls),
+            b"UF2 Bootloader v1.0\nModel: A <b>&\"'</b> model\nBoard-ID: ID\nSecond\n"
+        );
+        assert_eq!(
+            index_htm(&wls).unwrap(),
+            b"<html><head><meta http-equiv=\"refresh\" content=\"0;URL='https://e.com/?a=1&b='2''\"/></head><body>Redirecting to <a href='https://e.com/?a=1&b='2''><i>Name</i> & co</a></body></html>\n"
+        );
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Contains the objects used to describe OTP programming plans - ordered
+//! lists of OTP row writes, which can be executed against a real device, or
+//! validated offline using `OtpImage::apply_plan` (with the `alloc`
+//! feature).
+
+/// The mode an OTP row is to be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Write the bottom 16 bits of the value in ECC mode.  The hardware
+    /// calculates and writes the ECC parity bits.
+    Ecc,
+
+    /// Write the bottom 24 bits of the value in raw mode.
+    Raw,
+
+    /// Rewrite a row already written in ECC mode with the bottom 16 bits of
+    /// the value, by writing the value's ECC encoding (see
+    /// [`ecc::encode`](crate::ecc::encode)) in raw mode.  Only possible if the
+    /// new encoding has every bit of the existing one set.
+    EccRewrite,
+}
+
+impl core::fmt::Display for WriteMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            WriteMode::Ecc => write!(f, "ECC"),
+            WriteMode::Raw => write!(f, "raw"),
+            WriteMode::EccRewrite => write!(f, "ECC rewrite"),
+        }
+    }
+}
+
+/// A single OTP row write.
+///
+/// For [`WriteMode::Raw`] writes, `value` is the value the row must hold
+/// after the write - including any bits already set by earlier writes in
+/// the plan.  This allows writes to be retried safely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OtpWrite {
+    row: u16,
+    mode: WriteMode,
+    value: u32,
+}
+
+impl OtpWrite {
+    /// Creates an ECC mode write of a 16-bit value.
+    pub fn ecc(row: u16, value: u16) -> Self {
+        Self {
+            row,
+            mode: WriteMode::Ecc,
+            value: value as u32,
+        }
+    }
+
+    /// Creates a rewrite of a row already written in ECC mode with a new
+    /// 16-bit value.  See [`WriteMode::EccRewrite`].
+    pub fn ecc_rewrite(row: u16, value: u16) -> Self {
+        Self {
+            row,
+            mode: WriteMode::EccRewrite,
+            value: value as u32,
+        }
+    }
+
+    /// Creates a raw mode write of a 24-bit value.
+    pub fn raw(row: u16, value: u32) -> Self {
+        Self {
+            row,
+            mode: WriteMode::Raw,
+            value,
+        }
+    }
+
+    /// Returns the OTP row index to write.
+    pub fn row(&self) -> u16 {
+        self.row
+    }
+
+    /// Returns the mode to write the row in.
+    pub fn mode(&self) -> WriteMode {
+        self.mode
+    }
+
+    /// Returns the value to write.  This is at most 16 bits for ECC writes and
+    /// ECC rewrites, and 24 bits for raw writes.
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+}
+
+impl core::fmt::Display for OtpWrite {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.mode {
+            WriteMode::Ecc => write!(f, "{:#05x} ECC {:#06x}", self.row, self.value),
+            WriteMode::Raw => write!(f, "{:#05x} raw {:#08x}", self.row, self.value),
+            WriteMode::EccRewrite => write!(
+                f,
+                "{:#05x} raw {:#08x} (ECC rewrite {:#06x})",
+                self.row,
+                crate::ecc::encode(self.value as u16),
+                self.value
+            ),
+        }
+    }
+}
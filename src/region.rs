@@ -0,0 +1,347 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Finds a free region of OTP to store white label data in.
+//!
+//! Only the unreserved rows (0x0c0-0xf3f) are considered.  A region must be
+//! entirely blank, must be in pages the bootloader can write and the bootrom
+//! can read - see [`lock`](crate::lock) - and must be safe from errata
+//! RP2350-E17 - so it starts on an even row and ends on an odd one, with any
+//! unused row at either end left blank as padding.
+
+use alloc::vec::Vec;
+
+use crate::image::{OTP_PAGE_ROWS, OtpImage, RowMode};
+use crate::lock::PageLocks;
+use crate::whitelabel::{MAX_WHITELABEL_ADDR, OTP_ROW_UNRESERVED_END, OTP_ROW_UNRESERVED_START};
+
+/// How to choose between free regions large enough for the white label data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RegionStrategy {
+    /// Choose the region with the lowest row index.
+    #[default]
+    Lowest,
+
+    /// Choose the smallest region, leaving larger regions free for other
+    /// data.  The lowest is chosen if several are the same size.
+    BestFit,
+}
+
+/// Why a free region was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionReason {
+    /// The region is in the given OTP page, which is locked against either
+    /// writing by the bootloader or reading by the bootrom.
+    PageLocked(u8),
+
+    /// The region is too small.  `usable` is the number of rows left once
+    /// the region is aligned to even/odd row pairs.
+    TooSmall { usable: usize },
+
+    /// The region starts after the highest row index the white label data
+    /// can start at, while leaving room for the maximum size white label data
+    /// before the end of the unreserved rows.
+    AddressTooHigh,
+
+    /// None of the possible start rows in the region can be written to
+    /// USB_WHITE_LABEL_ADDR, given its existing contents.
+    AddressNotWritable,
+}
+
+impl core::fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RejectionReason::PageLocked(page) => write!(f, "OTP page {page} is locked"),
+            RejectionReason::TooSmall { usable } => {
+                write!(f, "too small: only {usable} usable rows")
+            }
+            RejectionReason::AddressTooHigh => write!(
+                f,
+                "starts after the maximum white label address {MAX_WHITELABEL_ADDR:#05x}"
+            ),
+            RejectionReason::AddressNotWritable => {
+                write!(f, "no start row can be written to USB_WHITE_LABEL_ADDR")
+            }
+        }
+    }
+}
+
+/// A free region of OTP rows rejected for storing the white label data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RejectedRegion {
+    start: u16,
+    rows: usize,
+    reason: RejectionReason,
+}
+
+impl RejectedRegion {
+    /// Returns the first row of the region.
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    /// Returns the number of rows in the region.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns why the region was rejected.
+    pub fn reason(&self) -> RejectionReason {
+        self.reason
+    }
+}
+
+impl core::fmt::Display for RejectedRegion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "OTP rows {:#05x}-{:#05x}: {}",
+            self.start,
+            self.start as usize + self.rows - 1,
+            self.reason
+        )
+    }
+}
+
+/// The result of searching for a free region for the white label data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionSearch {
+    address: Option<u16>,
+    rejected: Vec<RejectedRegion>,
+}
+
+impl RegionSearch {
+    /// Returns the row index to store the white label data at, or None if no
+    /// suitable region was found.
+    pub fn address(&self) -> Option<u16> {
+        self.address
+    }
+
+    /// Returns every free region which was rejected, and why, in row order.
+    pub fn rejected(&self) -> &[RejectedRegion] {
+        &self.rejected
+    }
+}
+
+/// Finds a region of OTP in `image` to store `num_rows` rows of white label
+/// data - typically [`OtpData::rows`](crate::OtpData::rows)`.len()`.
+///
+/// A page is treated as locked unless the bootloader can write it without a
+/// key and the bootrom can read it, once the copies in its lock rows have
+/// been voted on.  If the lock rows can't be decoded, every page is treated
+/// as locked.
+pub fn find_white_label_region(
+    image: &OtpImage,
+    num_rows: usize,
+    strategy: RegionStrategy,
+) -> RegionSearch {
+    find_white_label_region_where(image, num_rows, strategy, |_| true)
+}
+
+// Implements find_white_label_region, only choosing start rows for which
+// `accept` returns true.  The lowest acceptable start row in each region is
+// used.
+pub(crate) fn find_white_label_region_where(
+    image: &OtpImage,
+    num_rows: usize,
+    strategy: RegionStrategy,
+    accept: impl Fn(u16) -> bool,
+) -> RegionSearch {
+    // Round up to a whole number of even/odd row pairs, so any final unused
+    // row is left blank.
+    let needed = num_rows.max(1).next_multiple_of(2);
+
+    let mut rejected = Vec::new();
+    let mut best: Option<(u16, usize)> = None;
+
+    for (start, rows, locked_page) in free_runs(image) {
+        let reason = if let Some(page) = locked_page {
+            Some(RejectionReason::PageLocked(page))
+        } else {
+            // Align the start to an even row.
+            let aligned = start.next_multiple_of(2);
+            let usable = (rows - (aligned - start) as usize) & !1;
+            if usable < needed {
+                Some(RejectionReason::TooSmall { usable })
+            } else if aligned > MAX_WHITELABEL_ADDR {
+                Some(RejectionReason::AddressTooHigh)
+            } else {
+                let last = (aligned + (usable - needed) as u16).min(MAX_WHITELABEL_ADDR);
+                match (aligned..=last).step_by(2).find(|addr| accept(*addr)) {
+                    Some(addr) => {
+                        let better = match (strategy, best) {
+                            (_, None) => true,
+                            (RegionStrategy::Lowest, Some(_)) => false,
+                            (RegionStrategy::BestFit, Some((_, best_usable))) => {
+                                usable < best_usable
+                            }
+                        };
+                        if better {
+                            best = Some((addr, usable));
+                        }
+                        None
+                    }
+                    None => Some(RejectionReason::AddressNotWritable),
+                }
+            }
+        };
+        if let Some(reason) = reason {
+            rejected.push(RejectedRegion {
+                start,
+                rows,
+                reason,
+            });
+        }
+    }
+
+    RegionSearch {
+        address: best.map(|(addr, _)| addr),
+        rejected,
+    }
+}
+
+// Returns every run of blank unreserved rows, split at page boundaries where
+// the lock state changes, as (start, rows, locked page).
+fn free_runs(image: &OtpImage) -> Vec<(u16, usize, Option<u8>)> {
+    // A page whose lock can't be decoded may be locked, so is treated as
+    // locked.
+    let locks = PageLocks::from_image(image, false).ok();
+    let mut runs: Vec<(u16, usize, Option<u8>)> = Vec::new();
+    let mut prev_row = None;
+    for row in OTP_ROW_UNRESERVED_START..OTP_ROW_UNRESERVED_END {
+        if image.row_mode(row) != Ok(RowMode::Blank) {
+            continue;
+        }
+        let page = row as usize / OTP_PAGE_ROWS;
+        let locked = locks
+            .as_ref()
+            .and_then(|locks| locks.page(page))
+            .is_none_or(|lock| !lock.writable_by_bootloader() || !lock.readable_by_bootrom())
+            .then_some(page as u8);
+        match runs.last_mut() {
+            Some((_, rows, run_locked)) if prev_row == Some(row - 1) && *run_locked == locked => {
+                *rows += 1;
+            }
+            _ => runs.push((row, 1, locked)),
+        }
+        prev_row = Some(row);
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::OTP_ROW_PAGE0_LOCK0;
+
+    #[test]
+    fn test_blank_image() {
+        let image = OtpImage::new();
+        let search = find_white_label_region(&image, 75, RegionStrategy::Lowest);
+        assert_eq!(search.address(), Some(OTP_ROW_UNRESERVED_START));
+        assert!(search.rejected().is_empty());
+    }
+
+    #[test]
+    fn test_find_region() {
+        let mut image = OtpImage::new();
+        // Customer data at 0x0c0-0x0ff and 0x100, leaving a 51 row gap
+        // at 0x101-0x133 before more data at 0x134.
+        for row in 0x0c0..=0x100 {
+            image.write_ecc(row, 0x1234).unwrap();
+        }
+        image.write_ecc(0x134, 0x1234).unwrap();
+
+        // Lowest - the gap is too small once aligned and padded, so the data
+        // goes after the second block of data
+        let search = find_white_label_region(&image, 51, RegionStrategy::Lowest);
+        assert_eq!(search.address(), Some(0x136));
+        assert_eq!(search.rejected().len(), 1);
+        assert_eq!(search.rejected()[0].start(), 0x101);
+        assert_eq!(search.rejected()[0].rows(), 51);
+        assert_eq!(
+            search.rejected()[0].reason(),
+            RejectionReason::TooSmall { usable: 50 }
+        );
+
+        // Odd sized data needs a padding row
+        let search = find_white_label_region(&image, 49, RegionStrategy::Lowest);
+        assert_eq!(search.address(), Some(0x102));
+
+        // Best fit prefers the small gap
+        image.write_ecc(0x200, 0x1234).unwrap();
+        let search = find_white_label_region(&image, 16, RegionStrategy::Lowest);
+        assert_eq!(search.address(), Some(0x102));
+        let search = find_white_label_region(&image, 40, RegionStrategy::BestFit);
+        assert_eq!(search.address(), Some(0x102));
+        image.write_ecc(0x110, 0x1234).unwrap();
+        let search = find_white_label_region(&image, 40, RegionStrategy::BestFit);
+        assert_eq!(search.address(), Some(0x136));
+    }
+
+    #[test]
+    fn test_locked_pages() {
+        let mut image = OtpImage::new();
+        // Lock pages 3 and 4 (rows 0x0c0-0x13f) - page 3 read-only (one
+        // copy of its lock value is missing, but is outvoted), and page 4
+        // requiring a key to write.  Page 5 is only locked in one copy.
+        image
+            .write_raw(OTP_ROW_PAGE0_LOCK0 + 7, 0x0000_0101)
+            .unwrap();
+        image
+            .write_raw(OTP_ROW_PAGE0_LOCK0 + 8, 0x0001_0101)
+            .unwrap();
+        image
+            .write_raw(OTP_ROW_PAGE0_LOCK0 + 11, 0x0000_0001)
+            .unwrap();
+
+        let search = find_white_label_region(&image, 16, RegionStrategy::Lowest);
+        assert_eq!(search.address(), Some(0x140));
+        assert_eq!(
+            search.rejected(),
+            [
+                RejectedRegion {
+                    start: 0x0c0,
+                    rows: 64,
+                    reason: RejectionReason::PageLocked(3),
+                },
+                RejectedRegion {
+                    start: 0x100,
+                    rows: 64,
+                    reason: RejectionReason::PageLocked(4),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_region() {
+        let mut image = OtpImage::new();
+        for row in OTP_ROW_UNRESERVED_START..OTP_ROW_UNRESERVED_END {
+            if row % 32 == 0 {
+                image.write_ecc(row, 0x1234).unwrap();
+            }
+        }
+        let search = find_white_label_region(&image, 32, RegionStrategy::Lowest);
+        assert_eq!(search.address(), None);
+        assert!(
+            search
+                .rejected()
+                .iter()
+                .all(|r| r.reason() == RejectionReason::TooSmall { usable: 30 })
+        );
+
+        // Free space only at the very end of OTP
+        let mut image = OtpImage::new();
+        for row in OTP_ROW_UNRESERVED_START..0xe80 {
+            image.write_ecc(row, 0x1234).unwrap();
+        }
+        let search = find_white_label_region(&image, 16, RegionStrategy::Lowest);
+        assert_eq!(search.address(), None);
+        assert_eq!(
+            search.rejected()[0].reason(),
+            RejectionReason::AddressTooHigh
+        );
+    }
+}
@@ -0,0 +1,462 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! The effective USB identity of a device in BOOTSEL mode.
+//!
+//! A [`WhiteLabelStruct`] only holds the fields which are overridden.  The
+//! bootrom uses its own default for every other field - see the table in
+//! docs/USB.md.  [`UsbIdentity`] merges the two, giving what a host will
+//! actually see when the device enumerates.
+//!
+//! The default INDEX.HTM redirect URL is not known - docs/USB.md only gives
+//! a placeholder - so it, and INDEX.HTM, are only resolved when white
+//! labelled.
+//!
+//! [`UsbIdentity`] can also generate the binary USB descriptors and SCSI
+//! INQUIRY response, as a reference to compare a real device against.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use serde::Serialize;
+
+use crate::identity::ChipIdentity;
+use crate::msd;
+#[cfg(feature = "serde_json")]
+use crate::whitelabel::Error as WhiteLabelError;
+use crate::whitelabel::WhiteLabelStruct;
+
+/// Default USB device vendor ID (VID).
+pub const DEFAULT_VENDOR_ID: u16 = 0x2e8a;
+/// Default USB device product ID (PID).
+pub const DEFAULT_PRODUCT_ID: u16 = 0x000f;
+/// Default USB device version, as BCD.
+pub const DEFAULT_BCD_DEVICE: u16 = 0x0100;
+/// Default USB device language ID.
+pub const DEFAULT_LANGUAGE_ID: u16 = 0x0409;
+/// Default USB device manufacturer.
+pub const DEFAULT_MANUFACTURER: &str = "Raspberry Pi";
+/// Default USB device product.
+pub const DEFAULT_PRODUCT: &str = "RP2350 Boot";
+/// Default USB device attributes (low byte) and max power (high byte).
+pub const DEFAULT_ATTR_POWER: u16 = 0xfa80;
+/// Default mass storage volume label.
+pub const DEFAULT_VOLUME_LABEL: &str = "RP2350";
+/// Default SCSI inquiry vendor.
+pub const DEFAULT_SCSI_VENDOR: &str = "RPI";
+/// Default SCSI inquiry product.
+pub const DEFAULT_SCSI_PRODUCT: &str = "RP2350";
+/// Default SCSI inquiry version.
+pub const DEFAULT_SCSI_VERSION: &str = "1";
+/// Default INDEX.HTM redirect name.
+pub const DEFAULT_REDIRECT_NAME: &str = "raspberrypi.com";
+/// Default INFO_UF2.TXT model.
+pub const DEFAULT_UF2_MODEL: &str = "Raspberry Pi RP2350";
+/// Default INFO_UF2.TXT board ID.
+pub const DEFAULT_UF2_BOARD_ID: &str = "RP2350";
+
+// The descriptor and SCSI INQUIRY constants below are the values this crate
+// expects the bootrom to report.  They have not yet been checked against a
+// capture from a real device, or the bootrom source.
+
+/// USB specification release number (bcdUSB) expected from the bootrom -
+/// USB 2.1.  Not yet confirmed against a real device.
+pub const BCD_USB: u16 = 0x0210;
+/// Maximum packet size for endpoint 0 (bMaxPacketSize0).
+pub const EP0_MAX_PACKET_SIZE: u8 = 64;
+/// Number of interfaces in the bootrom's configuration - mass storage and
+/// PICOBOOT.
+pub const NUM_INTERFACES: u8 = 2;
+/// Total length of the bootrom's configuration descriptor (wTotalLength) -
+/// the configuration, two interfaces and four bulk endpoints.  Derived from
+/// the interfaces the bootrom is documented as having, rather than read from
+/// a real device, so may not account for any other descriptors it includes.
+pub const CONFIGURATION_TOTAL_LENGTH: u16 = 9 + 2 * (9 + 7 + 7);
+
+/// Index of the manufacturer string descriptor.
+pub const STRING_INDEX_MANUFACTURER: u8 = 1;
+/// Index of the product string descriptor.
+pub const STRING_INDEX_PRODUCT: u8 = 2;
+/// Index of the serial number string descriptor.
+pub const STRING_INDEX_SERIAL_NUMBER: u8 = 3;
+
+/// Length of the standard SCSI INQUIRY response.
+pub const SCSI_INQUIRY_LEN: usize = 36;
+/// SCSI INQUIRY VERSION byte expected from the bootrom - 0x04, which claims
+/// conformance to SPC-2.  Not yet confirmed against a real device.
+pub const SCSI_INQUIRY_VERSION: u8 = 0x04;
+
+// Descriptor types
+const DESCRIPTOR_TYPE_DEVICE: u8 = 1;
+const DESCRIPTOR_TYPE_CONFIGURATION: u8 = 2;
+const DESCRIPTOR_TYPE_STRING: u8 = 3;
+
+/// Errors that can occur while generating USB descriptors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The string for the given string descriptor index is too long to fit
+    /// in a USB string descriptor.
+    StringTooLong(u8),
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::StringTooLong(index) => {
+                write!(f, "String descriptor {index} is too long to encode")
+            }
+        }
+    }
+}
+
+/// The fully resolved USB identity of a device in BOOTSEL mode - the white
+/// label values where set, and the bootrom defaults otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UsbIdentity {
+    /// USB device vendor ID (idVendor).
+    pub vendor_id: u16,
+
+    /// USB device product ID (idProduct).
+    pub product_id: u16,
+
+    /// USB device version (bcdDevice).
+    pub bcd_device: u16,
+
+    /// USB device language ID, reported in string descriptor 0.
+    pub language_id: u16,
+
+    /// USB device manufacturer string.
+    pub manufacturer: String,
+
+    /// USB device product string.
+    pub product: String,
+
+    /// USB device serial number string.  `None` if it isn't white labelled,
+    /// and the chip identity, which the bootrom derives it from, is unknown.
+    pub serial_number: Option<String>,
+
+    /// USB configuration attributes (bmAttributes).
+    pub attributes: u8,
+
+    /// USB configuration maximum power (bMaxPower), in units of 2mA.
+    pub max_power: u8,
+
+    /// Mass storage volume label.
+    pub volume_label: String,
+
+    /// SCSI inquiry vendor.
+    pub scsi_vendor: String,
+
+    /// SCSI inquiry product.
+    pub scsi_product: String,
+
+    /// SCSI inquiry version.
+    pub scsi_version: String,
+
+    /// INDEX.HTM redirect URL.  `None` if it isn't white labelled, as the
+    /// bootrom's default is not known.
+    pub redirect_url: Option<String>,
+
+    /// INDEX.HTM redirect name.
+    pub redirect_name: String,
+
+    /// INFO_UF2.TXT model.
+    pub uf2_model: String,
+
+    /// INFO_UF2.TXT board ID.
+    pub uf2_board_id: String,
+}
+
+impl Default for UsbIdentity {
+    /// Returns the identity of a device with no white label data, and an
+    /// unknown chip identity.
+    fn default() -> Self {
+        Self::resolve(&WhiteLabelStruct::default(), None)
+    }
+}
+
+impl UsbIdentity {
+    /// Resolves the identity a device with white label data `wls` presents.
+    ///
+    /// `chip` is used to derive the serial number if `wls` doesn't override
+    /// it.  See [`ChipIdentity::serial_number`].
+    pub fn resolve(wls: &WhiteLabelStruct, chip: Option<&ChipIdentity>) -> Self {
+        let string = |value: Option<&String>, default: &str| {
+            value.map_or_else(|| default.to_string(), String::clone)
+        };
+        let attr_power = wls.attr_power().unwrap_or(DEFAULT_ATTR_POWER);
+
+        Self {
+            vendor_id: wls.vid().unwrap_or(DEFAULT_VENDOR_ID),
+            product_id: wls.pid().unwrap_or(DEFAULT_PRODUCT_ID),
+            bcd_device: wls.bcd_device().unwrap_or(DEFAULT_BCD_DEVICE),
+            language_id: wls.language_id().unwrap_or(DEFAULT_LANGUAGE_ID),
+            manufacturer: string(wls.manufacturer(), DEFAULT_MANUFACTURER),
+            product: string(wls.product(), DEFAULT_PRODUCT),
+            serial_number: wls
+                .serial_number()
+                .cloned()
+                .or_else(|| chip.map(ChipIdentity::serial_number)),
+            attributes: (attr_power & 0xff) as u8,
+            max_power: (attr_power >> 8) as u8,
+            volume_label: string(wls.volume_label(), DEFAULT_VOLUME_LABEL),
+            scsi_vendor: string(wls.scsi_vendor(), DEFAULT_SCSI_VENDOR),
+            scsi_product: string(wls.scsi_product(), DEFAULT_SCSI_PRODUCT),
+            scsi_version: string(wls.scsi_version(), DEFAULT_SCSI_VERSION),
+            redirect_url: wls.redirect_url().cloned(),
+            redirect_name: string(wls.redirect_name(), DEFAULT_REDIRECT_NAME),
+            uf2_model: string(wls.uf2_model(), DEFAULT_UF2_MODEL),
+            uf2_board_id: string(wls.uf2_board_id(), DEFAULT_UF2_BOARD_ID),
+        }
+    }
+
+    #[cfg(feature = "serde_json")]
+    /// Returns a JSON representation of this identity, including the
+    /// contents of INFO_UF2.TXT and INDEX.HTM (null if unknown).
+    pub fn to_json(&self) -> Result<serde_json::Value, WhiteLabelError> {
+        let mut json = serde_json::to_value(self)?;
+        if let Some(object) = json.as_object_mut() {
+            object.insert("info_uf2_txt".to_string(), self.info_uf2_txt().into());
+            object.insert("index_htm".to_string(), self.index_htm().into());
+        }
+        Ok(json)
+    }
+
+    /// Returns the expected contents of INFO_UF2.TXT on the BOOTSEL mass
+    /// storage device.  See [`msd::info_uf2_txt`].
+    pub fn info_uf2_txt(&self) -> String {
+        msd::render_info_uf2_txt(&self.uf2_model, &self.uf2_board_id)
+    }
+
+    /// Returns the expected contents of INDEX.HTM on the BOOTSEL mass storage
+    /// device, or `None` if the redirect URL is unknown.  See
+    /// [`msd::index_htm`].
+    pub fn index_htm(&self) -> Option<String> {
+        let url = self.redirect_url.as_ref()?;
+        Some(msd::render_index_htm(url, &self.redirect_name))
+    }
+
+    /// Returns the 18 byte USB device descriptor.
+    pub fn device_descriptor(&self) -> [u8; 18] {
+        let mut desc = [0; 18];
+        desc[0] = desc.len() as u8;
+        desc[1] = DESCRIPTOR_TYPE_DEVICE;
+        desc[2..4].copy_from_slice(&BCD_USB.to_le_bytes());
+        // bDeviceClass, bDeviceSubClass and bDeviceProtocol are defined by
+        // the interfaces
+        desc[7] = EP0_MAX_PACKET_SIZE;
+        desc[8..10].copy_from_slice(&self.vendor_id.to_le_bytes());
+        desc[10..12].copy_from_slice(&self.product_id.to_le_bytes());
+        desc[12..14].copy_from_slice(&self.bcd_device.to_le_bytes());
+        desc[14] = STRING_INDEX_MANUFACTURER;
+        desc[15] = STRING_INDEX_PRODUCT;
+        desc[16] = STRING_INDEX_SERIAL_NUMBER;
+        desc[17] = 1;
+        desc
+    }
+
+    /// Returns the 9 byte USB configuration descriptor header, which holds
+    /// the attributes and maximum power.  The interface and endpoint
+    /// descriptors which follow it are not affected by white label data.
+    pub fn configuration_descriptor(&self) -> [u8; 9] {
+        let mut desc = [0; 9];
+        desc[0] = desc.len() as u8;
+        desc[1] = DESCRIPTOR_TYPE_CONFIGURATION;
+        desc[2..4].copy_from_slice(&CONFIGURATION_TOTAL_LENGTH.to_le_bytes());
+        desc[4] = NUM_INTERFACES;
+        desc[5] = 1;
+        desc[7] = self.attributes;
+        desc[8] = self.max_power;
+        desc
+    }
+
+    /// Returns USB string descriptor `index`, or `None` if there is no such
+    /// descriptor.
+    ///
+    /// Descriptor 0 holds the language ID.  Descriptors 1-3 hold the
+    /// manufacturer, product and serial number, encoded as UTF-16LE.  The
+    /// serial number descriptor is `None` if the serial number is unknown.
+    ///
+    /// Returns [`Error::StringTooLong`] if the string doesn't fit in a
+    /// descriptor.  Strings from white label data always fit, but the fields
+    /// of a [`UsbIdentity`] can be set directly.
+    pub fn string_descriptor(&self, index: u8) -> Result<Option<Vec<u8>>, Error> {
+        let string = match index {
+            0 => {
+                let mut desc = Vec::from([4, DESCRIPTOR_TYPE_STRING]);
+                desc.extend_from_slice(&self.language_id.to_le_bytes());
+                return Ok(Some(desc));
+            }
+            STRING_INDEX_MANUFACTURER => &self.manufacturer,
+            STRING_INDEX_PRODUCT => &self.product,
+            STRING_INDEX_SERIAL_NUMBER => match &self.serial_number {
+                Some(serial_number) => serial_number,
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        let mut desc = Vec::from([0, DESCRIPTOR_TYPE_STRING]);
+        for unit in string.encode_utf16() {
+            desc.extend_from_slice(&unit.to_le_bytes());
+        }
+        desc[0] = u8::try_from(desc.len()).map_err(|_| Error::StringTooLong(index))?;
+        Ok(Some(desc))
+    }
+
+    /// Returns the 36 byte standard SCSI INQUIRY response, for a removable
+    /// direct access block device.
+    ///
+    /// The vendor, product and version are space padded to 8, 16 and 4
+    /// bytes respectively.
+    pub fn scsi_inquiry(&self) -> [u8; SCSI_INQUIRY_LEN] {
+        let mut buf = [0; SCSI_INQUIRY_LEN];
+        // Removable medium
+        buf[1] = 0x80;
+        // Response data format 2
+        buf[2] = SCSI_INQUIRY_VERSION;
+        buf[3] = 0x02;
+        buf[4] = (SCSI_INQUIRY_LEN - 5) as u8;
+        for (field, range) in [
+            (&self.scsi_vendor, 8..16),
+            (&self.scsi_product, 16..32),
+            (&self.scsi_version, 32..36),
+        ] {
+            let dest = &mut buf[range];
+            dest.fill(b' ');
+            for (byte, src) in dest.iter_mut().zip(field.bytes()) {
+                *byte = src;
+            }
+        }
+        buf
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+    use crate::ecc;
+    use crate::whitelabel::TOTAL_OTP_ROWS;
+
+    #[test]
+    fn test_resolve() {
+        let identity = UsbIdentity::default();
+        assert_eq!(identity.vendor_id, 0x2e8a);
+        assert_eq!(identity.product, "RP2350 Boot");
+        assert_eq!(identity.serial_number, None);
+        assert_eq!(identity.redirect_url, None);
+        assert_eq!(identity.index_htm(), None);
+        assert_eq!(identity.attributes, 0x80);
+        assert_eq!(identity.max_power, 0xfa);
+        assert_eq!(
+            identity.info_uf2_txt(),
+            "UF2 Bootloader v1.0\nModel: Raspberry Pi RP2350\nBoard-ID: RP2350\n"
+        );
+
+        let mut raw = [0u32; TOTAL_OTP_ROWS];
+        for (ii, row) in [0x5b6b, 0x2f65, 0x9c23, 0xde3f].iter().enumerate() {
+            raw[ii] = ecc::encode(*row);
+        }
+        let chip = ChipIdentity::from_raw_otp_data(&raw, true).unwrap();
+
+        let mut wls = WhiteLabelStruct::default();
+        wls.set_vid(0x1234);
+        wls.set_attr_power(0xc0, 0x32);
+        wls.set_product("Widget").unwrap();
+        wls.set_redirect_url("https://example.com").unwrap();
+        let identity = UsbIdentity::resolve(&wls, Some(&chip));
+        assert_eq!(identity.vendor_id, 0x1234);
+        assert_eq!(identity.product_id, DEFAULT_PRODUCT_ID);
+        assert_eq!(identity.manufacturer, "Raspberry Pi");
+        assert_eq!(identity.product, "Widget");
+        assert_eq!(identity.serial_number.as_deref(), Some("DE3F9C232F655B6B"));
+        assert_eq!(identity.attributes, 0xc0);
+        assert_eq!(identity.max_power, 0x32);
+        assert!(
+            identity
+                .index_htm()
+                .unwrap()
+                .contains("<a href='https://example.com'>raspberrypi.com</a>")
+        );
+
+        // A white labelled serial number takes precedence
+        wls.set_serial_number("1234").unwrap();
+        let identity = UsbIdentity::resolve(&wls, Some(&chip));
+        assert_eq!(identity.serial_number.as_deref(), Some("1234"));
+        let json = identity.to_json().unwrap();
+        assert_eq!(json["serial_number"], "1234");
+        assert_eq!(json["info_uf2_txt"], identity.info_uf2_txt());
+        assert_eq!(json["index_htm"], identity.index_htm().unwrap());
+    }
+
+    #[test]
+    fn test_descriptors() {
+        // Part of the example in docs/USB.md
+        let json = r#"{
+            "device": {
+                "vid": "0x2e8b",
+                "pid": "0x000e",
+                "bcd": 2.5,
+                "lang_id": "0x0c09",
+                "manufacturer": "zß水🍌 Test's Pis",
+                "max_power": "0x20",
+                "attributes": "0xe0"
+            },
+            "scsi": {
+                "vendor": "TestPi",
+                "version": "v897"
+            }
+        }"#;
+        let wls = WhiteLabelStruct::from_json(json).unwrap();
+        let identity = UsbIdentity::resolve(&wls, None);
+
+        // These are the bytes expected from the bootrom, rather than a
+        // capture from a real device
+        assert_eq!(
+            identity.device_descriptor(),
+            [
+                18, 1, 0x10, 0x02, 0, 0, 0, 64, 0x8b, 0x2e, 0x0e, 0x00, 0x50, 0x02, 1, 2, 3, 1
+            ]
+        );
+        assert_eq!(
+            identity.configuration_descriptor(),
+            [9, 2, 55, 0, 2, 1, 0, 0xe0, 0x20]
+        );
+
+        let string_descriptor = |index| identity.string_descriptor(index).unwrap();
+        assert_eq!(string_descriptor(0).unwrap(), [4, 3, 0x09, 0x0c]);
+        let manufacturer = string_descriptor(1).unwrap();
+        // The banana is a surrogate pair
+        assert_eq!(manufacturer.len(), 2 + 2 * 16);
+        assert_eq!(manufacturer[0], 34);
+        assert_eq!(&manufacturer[2..8], &[b'z', 0, 0xdf, 0, 0x34, 0x6c]);
+        assert_eq!(&manufacturer[8..12], &[0x3c, 0xd8, 0x4c, 0xdf]);
+        assert_eq!(
+            string_descriptor(2).unwrap(),
+            b"\x18\x03R\0P\x002\x003\x005\x000\0 \0B\0o\0o\0t\0"
+        );
+        assert_eq!(string_descriptor(3), None);
+        assert_eq!(string_descriptor(4), None);
+
+        let inquiry = identity.scsi_inquiry();
+        assert_eq!(&inquiry[..8], &[0, 0x80, 0x04, 0x02, 31, 0, 0, 0]);
+        assert_eq!(&inquiry[8..], b"TestPi  RP2350          v897");
+    }
+
+    #[test]
+    fn test_string_descriptor_too_long() {
+        let mut identity = UsbIdentity {
+            product: "a".repeat(126),
+            ..Default::default()
+        };
+        assert_eq!(identity.string_descriptor(2).unwrap().unwrap()[0], 254);
+
+        identity.product.push('a');
+        assert_eq!(identity.string_descriptor(2), Err(Error::StringTooLong(2)));
+        identity.serial_number = Some("🍌".repeat(64));
+        assert_eq!(identity.string_descriptor(3), Err(Error::StringTooLong(3)));
+        assert!(identity.string_descriptor(1).is_ok());
+    }
+}
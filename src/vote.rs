@@ -0,0 +1,266 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Majority voting of redundant raw OTP rows.
+//!
+//! The RP2350 stores flags such as USB_BOOT_FLAGS in three raw rows - the
+//! row itself, plus _R1 and _R2 copies.  The bootrom votes on each bit
+//! individually, using the value held by at least two of the three copies.
+//! As these rows are raw, and often written incrementally, copies which only
+//! partially agree are to be expected.
+//!
+//! The most critical flags, CRIT0 and CRIT1, are instead stored in eight
+//! copies, with a bit treated as set if it is set in at least three of them.
+//! See [`vote_3_of_8`].
+
+use alloc::vec::Vec;
+
+use crate::ecc::RAW_ROW_MASK;
+
+/// A bit which does not hold the same value in all three copies of a voted
+/// row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitDisagreement {
+    bit: u8,
+    value: bool,
+    dissenting_copy: usize,
+}
+
+impl BitDisagreement {
+    /// Returns the bit number.
+    pub fn bit(&self) -> u8 {
+        self.bit
+    }
+
+    /// Returns the effective (voted) value of the bit.
+    pub fn value(&self) -> bool {
+        self.value
+    }
+
+    /// Returns the copy which disagrees with the other two - 0 for the row
+    /// itself, 1 for _R1 and 2 for _R2.
+    pub fn dissenting_copy(&self) -> usize {
+        self.dissenting_copy
+    }
+}
+
+impl core::fmt::Display for BitDisagreement {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "bit {} is {} in copy {}, but {} in the others",
+            self.bit,
+            u8::from(!self.value),
+            self.dissenting_copy,
+            u8::from(self.value),
+        )
+    }
+}
+
+/// The result of majority voting three copies of a raw OTP row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoteResult {
+    value: u32,
+    disagreements: Vec<BitDisagreement>,
+}
+
+impl VoteResult {
+    /// Returns the effective value, as used by the bootrom.
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// Returns every bit which is not the same in all three copies, in bit
+    /// order.
+    pub fn disagreements(&self) -> &[BitDisagreement] {
+        &self.disagreements
+    }
+
+    /// Returns true if all three copies are identical.
+    pub fn is_unanimous(&self) -> bool {
+        self.disagreements.is_empty()
+    }
+}
+
+/// Votes on each bit of three copies of a raw 24-bit OTP row, returning the
+/// value held by at least two copies, along with the bits which differ.
+///
+/// Bits above bit 23 are ignored.
+pub fn vote_2_of_3(copies: [u32; 3]) -> VoteResult {
+    let [a, b, c] = copies.map(|copy| copy & RAW_ROW_MASK);
+    let value = (a & b) | (a & c) | (b & c);
+
+    let disagreements = (0..24)
+        .filter_map(|bit| {
+            let mask = 1 << bit;
+            copies
+                .iter()
+                .position(|copy| (copy & mask) != (value & mask))
+                .map(|dissenting_copy| BitDisagreement {
+                    bit,
+                    value: value & mask != 0,
+                    dissenting_copy,
+                })
+        })
+        .collect();
+
+    VoteResult {
+        value,
+        disagreements,
+    }
+}
+
+/// A bit which does not hold the same value in all eight copies of a
+/// critical flags row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CritBitDisagreement {
+    bit: u8,
+    value: bool,
+    copies_set: u8,
+}
+
+impl CritBitDisagreement {
+    /// Returns the bit number.
+    pub fn bit(&self) -> u8 {
+        self.bit
+    }
+
+    /// Returns the effective (voted) value of the bit.
+    pub fn value(&self) -> bool {
+        self.value
+    }
+
+    /// Returns a bitmask of the copies the bit is set in - bit 0 for the row
+    /// itself, bit 1 for _R1, and so on.
+    pub fn copies_set(&self) -> u8 {
+        self.copies_set
+    }
+}
+
+impl core::fmt::Display for CritBitDisagreement {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "bit {} is set in {} of 8 copies ({:#010b}), so is {}",
+            self.bit,
+            self.copies_set.count_ones(),
+            self.copies_set,
+            u8::from(self.value),
+        )
+    }
+}
+
+/// The result of voting eight copies of a critical flags row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CritVoteResult {
+    value: u32,
+    disagreements: Vec<CritBitDisagreement>,
+}
+
+impl CritVoteResult {
+    /// Returns the effective value, as used by the hardware.
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// Returns every bit which is not the same in all eight copies, in bit
+    /// order.
+    pub fn disagreements(&self) -> &[CritBitDisagreement] {
+        &self.disagreements
+    }
+
+    /// Returns true if all eight copies are identical.
+    pub fn is_unanimous(&self) -> bool {
+        self.disagreements.is_empty()
+    }
+}
+
+/// Votes on each bit of eight copies of a raw 24-bit critical flags row, as
+/// the hardware does - a bit is set if it is set in at least three copies.
+///
+/// Bits above bit 23 are ignored.
+pub fn vote_3_of_8(copies: [u32; 8]) -> CritVoteResult {
+    let mut value = 0;
+    let mut disagreements = Vec::new();
+    for bit in 0..24 {
+        let copies_set = copies
+            .iter()
+            .enumerate()
+            .filter(|(_, copy)| *copy & (1 << bit) != 0)
+            .fold(0u8, |mask, (ii, _)| mask | (1 << ii));
+        let set = copies_set.count_ones() >= 3;
+        if set {
+            value |= 1 << bit;
+        }
+        if copies_set != 0 && copies_set != 0xff {
+            disagreements.push(CritBitDisagreement {
+                bit,
+                value: set,
+                copies_set,
+            });
+        }
+    }
+
+    CritVoteResult {
+        value,
+        disagreements,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_vote_2_of_3() {
+        let result = vote_2_of_3([0x0040_ff77; 3]);
+        assert_eq!(result.value(), 0x0040_ff77);
+        assert!(result.is_unanimous());
+
+        // Partially programmed copies - bit 22 only set in the first, bit 0
+        // missing from the last.
+        let result = vote_2_of_3([0x0040_0003, 0x0000_0003, 0x0000_0002]);
+        assert_eq!(result.value(), 0x0000_0003);
+        assert_eq!(
+            result.disagreements(),
+            [
+                BitDisagreement {
+                    bit: 0,
+                    value: true,
+                    dissenting_copy: 2,
+                },
+                BitDisagreement {
+                    bit: 22,
+                    value: false,
+                    dissenting_copy: 0,
+                },
+            ]
+        );
+        assert_eq!(
+            result.disagreements()[1].to_string(),
+            "bit 22 is 1 in copy 0, but 0 in the others"
+        );
+
+        // No two copies match as whole words, but every bit has a majority
+        let result = vote_2_of_3([0b011, 0b101, 0b110]);
+        assert_eq!(result.value(), 0b111);
+        assert_eq!(result.disagreements().len(), 3);
+    }
+
+    #[test]
+    fn test_vote_3_of_8() {
+        assert!(vote_3_of_8([0x05; 8]).is_unanimous());
+
+        // Bit 0 in 3 copies is set, bit 1 in 2 copies is not
+        let result = vote_3_of_8([0b11, 0b11, 0b01, 0, 0, 0, 0, 0]);
+        assert_eq!(result.value(), 0b01);
+        assert_eq!(result.disagreements().len(), 2);
+        assert_eq!(result.disagreements()[0].copies_set(), 0b0000_0111);
+        assert_eq!(
+            result.disagreements()[1].to_string(),
+            "bit 1 is set in 2 of 8 copies (0b00000011), so is 0"
+        );
+    }
+}
@@ -26,6 +26,8 @@
 // mod tests;
 // ```
 
+#![allow(clippy::derivable_impls)]
+
 extern crate alloc;
 use alloc::string::ToString;
 #[cfg(test)]
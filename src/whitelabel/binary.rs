@@ -8,8 +8,16 @@ use alloc::string::ToString;
 use alloc::vec::Vec;
 
 use crate::WhiteLabelStruct;
+use crate::ecc::{self, EccWarning};
+use crate::errata;
+use crate::image::RowMode;
+use crate::lock::PageLocks;
+use crate::plan::OtpWrite;
+use crate::vote::{self, BitDisagreement};
 use crate::whitelabel::Error;
-use crate::whitelabel::top::{TOTAL_OTP_ROWS, WHITE_LABEL_ADDR_VALID_BIT_NUM};
+use crate::whitelabel::top::{
+    NUM_INDEX_ROWS, TOTAL_OTP_ROWS, WHITE_LABEL_ADDR_VALID_BIT_NUM, referenced_row_count,
+};
 use crate::whitelabel::{
     OTP_ROW_UNRESERVED_END, OTP_ROW_UNRESERVED_START, OTP_ROW_USB_BOOT_FLAGS,
     OTP_ROW_USB_BOOT_FLAGS_R1, OTP_ROW_USB_BOOT_FLAGS_R2, OTP_ROW_USB_WHITE_LABEL_DATA,
@@ -35,7 +43,7 @@ const MIN_REQD_OTP_WHITE_LABEL_ROWS: usize = 256;
 const MAX_OTP_WHITE_LABEL_ROWS: usize = 319;
 
 // Maximum valid WHITE_LABEL_ADDR value (row index) for white label data
-const MAX_WHITELABEL_ADDR: u16 =
+pub(crate) const MAX_WHITELABEL_ADDR: u16 =
     (OTP_ROW_UNRESERVED_END as usize - MIN_REQD_OTP_WHITE_LABEL_ROWS) as u16;
 
 /// Used to hold the OTP data for an RP2350's USB white label configuration.
@@ -55,6 +63,14 @@ pub struct OtpData {
 
     // Whether strict checking was enabled when parsing the OTP data.
     strict: bool,
+
+    // Any ECC corrections or failures found in the rows used, when parsed
+    // from a full OTP dump.
+    ecc_warnings: Vec<EccWarning>,
+
+    // Any bits which differ between the three copies of the USB boot flags,
+    // when parsed from a full OTP dump.
+    usb_boot_flags_disagreements: Vec<BitDisagreement>,
 }
 
 /// Converts a WhiteLabelStruct into OtpData.  Uses strict checking - will
@@ -81,7 +97,7 @@ impl OtpData {
     /// Creates a new OtpData object.
     ///
     /// This is provided for convenience, but you may prefer to use one of
-    /// [`from_json`](`Self::from_json`),
+    /// `from_json` (with the `serde_json` feature),
     /// [`from_full_otp_data`](`Self::from_full_otp_data`) or
     /// [`TryFrom<WhiteLabelStruct>`](`Self::try_from`) instead.
     ///
@@ -97,24 +113,30 @@ impl OtpData {
             usb_boot_flags,
             rows,
             strict,
+            ecc_warnings: Vec::new(),
+            usb_boot_flags_disagreements: Vec::new(),
         }
     }
 
+    #[cfg(feature = "serde_json")]
     /// Creates white label OTP data directly from a JSON string.
     ///
     /// Can be used to skip the creation of the WhiteLabelStruct where that
     /// isn't required.
     pub fn from_json(json: &str) -> Result<Self, Error> {
-        let wls = WhiteLabelStruct::from_json(json)?;
+        WhiteLabelStruct::from_json(json)?.to_otp_data_strict()
+    }
 
-        match wls.to_otp_data_strict() {
-            Ok(otp_data) => Ok(otp_data),
-            Err(e) => {
-                panic!("Internal inconsistency generating OTP data: {e}");
-            }
-        }
+    #[cfg(feature = "serde_json")]
+    /// Creates white label OTP data from a JSON string, following
+    /// `picotool`'s encoding rules, as understood by `pico-otp`.  See
+    /// [`WhiteLabelStruct::from_json_picotool`], including why the rows
+    /// may not yet match `picotool`'s.
+    pub fn from_json_picotool(json: &str) -> Result<Self, Error> {
+        WhiteLabelStruct::from_json_picotool(json)?.to_otp_data_strict()
     }
 
+    #[cfg(feature = "serde_json")]
     /// Returns a JSON representation of this OTP data.
     pub fn to_json(&self) -> Result<serde_json::Value, Error> {
         WhiteLabelStruct::try_from(self)?.to_json()
@@ -133,10 +155,30 @@ impl OtpData {
     ///   return an error if any inconsistencies are found.  If false,
     ///   attempts to recover from inconsistencies.
     ///
+    /// The USB boot flags are determined by voting on each bit of the three
+    /// copies, as the bootrom does - see [`vote`](crate::vote).
+    ///
     /// The types of consistencies enforced when `strict` is true are:
     /// - All three copies of the USB boot flags must match.
     /// - The WHITE_LABEL_ADDR must point to a non-reserved location in OTP
     ///   memory, with at least 256 rows available for the white label data.
+    /// - The USB_WHITE_LABEL_ADDR row, and every row used by the white label
+    ///   data, must ECC decode from `non_ecc_data` without an uncorrectable
+    ///   error, to the same value as provided in `ecc_data`.  Corrected
+    ///   single bit errors and polarity reversed rows are accepted, as the
+    ///   RP2350 accepts them too.
+    /// - The white label data must start on an even row, and must not share
+    ///   an even/odd row pair with any other data, due to errata RP2350-E17.
+    ///   See [`errata`](crate::errata).
+    ///
+    /// Whether `strict` or not, the WHITE_LABEL_ADDR must leave room for the
+    /// 16 row white label struct before the end of OTP.
+    ///
+    /// Whether `strict` or not, any ECC corrections, polarity reversals or
+    /// uncorrectable errors found in these rows are available afterwards
+    /// from [`ecc_warnings`](`Self::ecc_warnings`), and any bits which differ
+    /// between the copies of the USB boot flags from
+    /// [`usb_boot_flags_disagreements`](`Self::usb_boot_flags_disagreements`).
     ///
     /// Returns:
     /// - `Ok(OtpData)`: The extracted OTP data.
@@ -147,51 +189,114 @@ impl OtpData {
         strict: bool,
     ) -> Result<Self, Error> {
         // Extract the 3 copies of the USB boot flags from the non-ECC OTP
-        // data and verify they match.
-        let usb_boot_flags = non_ecc_data[OTP_ROW_USB_BOOT_FLAGS as usize];
-        let usb_boot_flags_r1 = non_ecc_data[OTP_ROW_USB_BOOT_FLAGS_R1 as usize];
-        let usb_boot_flags_r2 = non_ecc_data[OTP_ROW_USB_BOOT_FLAGS_R2 as usize];
-        let master_usb_boot_flags = if strict {
+        // data and vote on each bit, as the bootrom does.
+        let vote = vote::vote_2_of_3([
+            non_ecc_data[OTP_ROW_USB_BOOT_FLAGS as usize],
+            non_ecc_data[OTP_ROW_USB_BOOT_FLAGS_R1 as usize],
+            non_ecc_data[OTP_ROW_USB_BOOT_FLAGS_R2 as usize],
+        ]);
+        if strict && !vote.is_unanimous() {
             // All three copies of the USB boot flags must match.
-            if usb_boot_flags != usb_boot_flags_r1 || usb_boot_flags != usb_boot_flags_r2 {
-                return Err(Error::NonMatchingUsbBootFlags);
-            }
-            usb_boot_flags
-        } else {
-            // Determine the master copy - at least two out of three must match.
-            if usb_boot_flags == usb_boot_flags_r1 || usb_boot_flags == usb_boot_flags_r2 {
-                usb_boot_flags
-            } else if usb_boot_flags_r1 == usb_boot_flags_r2 {
-                usb_boot_flags_r1
-            } else {
-                return Err(Error::NonMatchingUsbBootFlags);
-            }
-        };
+            return Err(Error::NonMatchingUsbBootFlags);
+        }
+        let master_usb_boot_flags = vote.value();
 
         // Extract the white label data from the ECC OTP data.
         let white_label_addr = ecc_data[OTP_ROW_USB_WHITE_LABEL_DATA as usize];
+
+        // Check the white label address row's ECC view matches its raw view.
+        let mut ecc_warnings = Vec::new();
+        check_ecc_rows(
+            non_ecc_data,
+            ecc_data,
+            OTP_ROW_USB_WHITE_LABEL_DATA as usize,
+            1,
+            strict,
+            &mut ecc_warnings,
+        )?;
+
         if strict {
             // Check the white label address is not in a reserved region.
-            if white_label_addr < OTP_ROW_UNRESERVED_START || white_label_addr > MAX_WHITELABEL_ADDR
-            {
-                return Err(Error::InvalidWhiteLabelAddressValue(
-                    white_label_addr as u16,
-                ));
+            if !(OTP_ROW_UNRESERVED_START..=MAX_WHITELABEL_ADDR).contains(&white_label_addr) {
+                return Err(Error::InvalidWhiteLabelAddressValue(white_label_addr));
             }
         }
 
+        // When not strict, the address may be anywhere, so there must at
+        // least be room for the struct itself.
+        let start = white_label_addr as usize;
+        if start + NUM_INDEX_ROWS > TOTAL_OTP_ROWS {
+            return Err(Error::InvalidWhiteLabelAddressValue(white_label_addr));
+        }
+
         // Store off the maximum required amount of white label data.  There
         // is an inconsistency here with the test above - we will copy the
         // theoretical maximum, not the minimum required amount.  This could
-        // result in copying some reserved data, but shouldn't go off the end
-        // of the provided OTP ECC data dump.
-        assert!((white_label_addr as usize) + MAX_OTP_WHITE_LABEL_ROWS <= TOTAL_OTP_ROWS);
-        let rows = Vec::from(
-            &ecc_data
-                [white_label_addr as usize..(white_label_addr as usize) + MAX_OTP_WHITE_LABEL_ROWS],
-        );
+        // result in copying some reserved data.  When not strict, fewer rows
+        // are copied if the maximum would go off the end of the dump.
+        let end = (start + MAX_OTP_WHITE_LABEL_ROWS).min(TOTAL_OTP_ROWS);
+        let rows = Vec::from(&ecc_data[start..end]);
+
+        // Only check the rows the white label data actually uses - the rest
+        // of the copied rows may legitimately hold raw data.
+        let used = referenced_row_count(master_usb_boot_flags, &rows).min(rows.len());
+        check_ecc_rows(
+            non_ecc_data,
+            ecc_data,
+            white_label_addr as usize,
+            used,
+            strict,
+            &mut ecc_warnings,
+        )?;
 
-        Self::from_white_label_data(master_usb_boot_flags, &rows, strict)
+        if strict {
+            // Check the white label data doesn't share an even/odd row pair
+            // with unrelated data, as this is affected by errata RP2350-E17.
+            let issues = errata::placement_issues(white_label_addr, used, false, |row| {
+                non_ecc_data
+                    .get(row as usize)
+                    .map_or(RowMode::Blank, |raw| RowMode::infer(*raw))
+            });
+            if let Some(issue) = issues.first() {
+                return Err(Error::ErrataE17(*issue));
+            }
+        }
+
+        let mut otp_data = Self::from_white_label_data(master_usb_boot_flags, &rows, strict)?;
+        otp_data.ecc_warnings = ecc_warnings;
+        otp_data.usb_boot_flags_disagreements = vote.disagreements().to_vec();
+        Ok(otp_data)
+    }
+
+    /// Creates an OtpData object from a complete raw (non-ECC) OTP dump only,
+    /// and extracts the USB white label specific OTP data, returning a
+    /// [`OtpData`].
+    ///
+    /// The ECC view of OTP is derived by ECC decoding each raw row, as the
+    /// RP2350 would when reading the row in ECC mode.
+    ///
+    /// Args:
+    /// - `raw_data`: A reference to an array of 4096 u32 words containing
+    ///   the raw OTP data dump, each holding a 24-bit row.
+    /// - `strict`: If true, performs strict checking on the data, and will
+    ///   return an error if any inconsistencies are found.  See
+    ///   [`from_full_otp_data`](`Self::from_full_otp_data`) for details.  If
+    ///   false, rows with uncorrectable ECC errors are used as read.
+    ///
+    /// Any ECC corrections, polarity reversals or (when not `strict`)
+    /// uncorrectable errors found in the rows used are available afterwards
+    /// from [`ecc_warnings`](`Self::ecc_warnings`).
+    ///
+    /// Returns:
+    /// - `Ok(OtpData)`: The extracted OTP data.
+    /// - `Err(Error)`: An error occurred while parsing the OTP data.
+    pub fn from_raw_otp_data(
+        raw_data: &[u32; TOTAL_OTP_ROWS],
+        strict: bool,
+    ) -> Result<Self, Error> {
+        let ecc_data: [u16; TOTAL_OTP_ROWS] =
+            core::array::from_fn(|row| ecc::decode(raw_data[row]).data());
+        Self::from_full_otp_data(raw_data, &ecc_data, strict)
     }
 
     /// Creates an OtpData object from a slice of OTP ECC row data only, plus
@@ -212,12 +317,10 @@ impl OtpData {
         ecc_rows: &[u16],
         strict: bool,
     ) -> Result<Self, Error> {
-        if strict {
-            if usb_boot_flags & (1 << WHITE_LABEL_ADDR_VALID_BIT_NUM) == 0 {
-                return Err(Error::OtpDataError(
-                    "WHITE_LABEL_ADDR_VALID bit not set in USB boot flags".to_string(),
-                ));
-            }
+        if strict && usb_boot_flags & (1 << WHITE_LABEL_ADDR_VALID_BIT_NUM) == 0 {
+            return Err(Error::OtpDataError(
+                "WHITE_LABEL_ADDR_VALID bit not set in USB boot flags".to_string(),
+            ));
         }
 
         Ok(OtpData::new(usb_boot_flags, Vec::from(ecc_rows), strict))
@@ -243,8 +346,431 @@ impl OtpData {
             .collect::<Vec<u8>>()
     }
 
+    /// Returns the OTP rows containing the white label data as raw 24-bit
+    /// values, including the ECC parity bits.  This is what would be read
+    /// back from the rows in raw mode after writing [`rows`](`Self::rows`)
+    /// in ECC mode, and can be used to program the rows in raw mode.
+    pub fn to_raw_rows(&self) -> Vec<u32> {
+        self.rows.iter().map(|row| ecc::encode(*row)).collect()
+    }
+
+    /// Returns a vector of bytes representing the raw OTP data, including the
+    /// ECC parity bits, in little-endian format - 4 bytes per row, with the
+    /// top byte zero.  This matches the layout of the RP2350's raw memory
+    /// mapped OTP region.
+    pub fn to_le_raw_bytes(&self) -> Vec<u8> {
+        self.to_raw_rows()
+            .iter()
+            .flat_map(|row| row.to_le_bytes())
+            .collect::<Vec<u8>>()
+    }
+
+    /// Returns the ordered list of OTP writes required to program this white
+    /// label configuration, with the white label structure at row
+    /// `white_label_addr`.
+    ///
+    /// The writes are in the order given in docs/USB.md:
+    /// 1. The white label address to USB_WHITE_LABEL_ADDR (0x05c), ECC.
+    /// 2. The white label structure and strings, from `white_label_addr`
+    ///    onwards, ECC.
+    /// 3. The USB boot flags, without WHITE_LABEL_ADDR_VALID, to
+    ///    USB_BOOT_FLAGS, USB_BOOT_FLAGS_R1 and USB_BOOT_FLAGS_R2 (0x059 -
+    ///    0x05b), raw.  Omitted if no other flags are set.
+    /// 4. The USB boot flags, with WHITE_LABEL_ADDR_VALID, to the same three
+    ///    rows, raw.
+    ///
+    /// Returns an error if the white label data would not fit entirely within
+    /// the unreserved OTP rows when stored at `white_label_addr`, if
+    /// `white_label_addr` is odd and `allow_odd_start` is false - see
+    /// [`errata`](crate::errata) - or if any of its rows are in a page
+    /// `locks` shows the bootrom or bootloader cannot read.  Pass the target
+    /// device's [`PageLocks`] if known, or None to skip this check.
+    ///
+    /// The plan assumes the target rows are blank, and the boot flags rows
+    /// have no bits set other than those in this configuration.  Use
+    /// [`OtpImage::apply_plan`](crate::OtpImage::apply_plan) to check the
+    /// plan against a dump of the target device's OTP, and
+    /// [`errata::check_white_label_placement`] to check the rows either side
+    /// of the white label data.
+    pub fn programming_plan(
+        &self,
+        white_label_addr: u16,
+        allow_odd_start: bool,
+        locks: Option<&PageLocks>,
+    ) -> Result<Vec<OtpWrite>, Error> {
+        let end = white_label_addr as usize + self.rows.len();
+        if white_label_addr < OTP_ROW_UNRESERVED_START || end > OTP_ROW_UNRESERVED_END as usize {
+            return Err(Error::InvalidWhiteLabelAddressValue(white_label_addr));
+        }
+        if let Some(issue) =
+            errata::placement_issues(white_label_addr, self.rows.len(), allow_odd_start, |_| {
+                RowMode::Blank
+            })
+            .first()
+        {
+            return Err(Error::ErrataE17(*issue));
+        }
+        if let Some(page) = locks
+            .map(|locks| locks.unreadable_white_label_pages(white_label_addr, self.rows.len()))
+            .and_then(|pages| pages.first().copied())
+        {
+            return Err(Error::UnreadablePage(page));
+        }
+
+        let boot_flag_rows = [
+            OTP_ROW_USB_BOOT_FLAGS,
+            OTP_ROW_USB_BOOT_FLAGS_R1,
+            OTP_ROW_USB_BOOT_FLAGS_R2,
+        ];
+        let valid_bit = 1 << WHITE_LABEL_ADDR_VALID_BIT_NUM;
+        let flags = self.usb_boot_flags | valid_bit;
+        let flags_without_valid = flags & !valid_bit;
+
+        let mut plan = Vec::with_capacity(self.rows.len() + 7);
+        plan.push(OtpWrite::ecc(
+            OTP_ROW_USB_WHITE_LABEL_DATA,
+            white_label_addr,
+        ));
+        plan.extend(
+            self.rows
+                .iter()
+                .zip(white_label_addr..)
+                .map(|(value, row)| OtpWrite::ecc(row, *value)),
+        );
+        if flags_without_valid != 0 {
+            plan.extend(
+                boot_flag_rows
+                    .iter()
+                    .map(|row| OtpWrite::raw(*row, flags_without_valid)),
+            );
+        }
+        plan.extend(boot_flag_rows.iter().map(|row| OtpWrite::raw(*row, flags)));
+
+        Ok(plan)
+    }
+
     /// Returns whether strict checking was enabled when parsing the OTP data.
     pub fn strict(&self) -> bool {
         self.strict
     }
+
+    /// Returns any ECC corrections, polarity reversals, uncorrectable errors
+    /// or mismatches between the raw and ECC views found in the rows used,
+    /// when this object was created from a full OTP dump.  Always empty
+    /// otherwise.
+    pub fn ecc_warnings(&self) -> &[EccWarning] {
+        &self.ecc_warnings
+    }
+
+    /// Returns any bits which differ between the three copies of the USB boot
+    /// flags, when this object was created from a full OTP dump without
+    /// strict checking.  Always empty otherwise.
+    pub fn usb_boot_flags_disagreements(&self) -> &[BitDisagreement] {
+        &self.usb_boot_flags_disagreements
+    }
+}
+
+// Why check_ecc_rows() failed, converted into each caller's own error type.
+pub(crate) enum EccCheckError {
+    // The raw value of the row holds an uncorrectable ECC error.
+    Uncorrectable(u16),
+
+    // The raw value of the row decodes to a different value to the ECC one.
+    Mismatch(u16),
+}
+
+impl From<EccCheckError> for Error {
+    fn from(err: EccCheckError) -> Self {
+        match err {
+            EccCheckError::Uncorrectable(row) => Error::UncorrectableEcc(row),
+            EccCheckError::Mismatch(row) => Error::EccMismatch(row),
+        }
+    }
+}
+
+// Checks that `count` rows starting at `start` ECC decode from the raw data to
+// the values in the ECC data, adding a warning for every row which isn't
+// clean, or doesn't match.  Uncorrectable and mismatching rows are only errors
+// if `strict`.
+pub(crate) fn check_ecc_rows(
+    non_ecc_data: &[u32; TOTAL_OTP_ROWS],
+    ecc_data: &[u16; TOTAL_OTP_ROWS],
+    start: usize,
+    count: usize,
+    strict: bool,
+    warnings: &mut Vec<EccWarning>,
+) -> Result<(), EccCheckError> {
+    for row in start..(start + count).min(TOTAL_OTP_ROWS) {
+        let decoded = ecc::decode(non_ecc_data[row]);
+        if strict {
+            if !decoded.is_ok() {
+                return Err(EccCheckError::Uncorrectable(row as u16));
+            }
+            if decoded.data() != ecc_data[row] {
+                return Err(EccCheckError::Mismatch(row as u16));
+            }
+        }
+        if decoded.is_ok() && decoded.data() != ecc_data[row] {
+            warnings.push(EccWarning::mismatch(row as u16, decoded, ecc_data[row]));
+        } else if !decoded.is_clean() {
+            warnings.push(EccWarning::new(row as u16, decoded));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+    use crate::OtpImage;
+    use crate::lock::{self, LockLevel, PageLock};
+    use alloc::vec;
+
+    // Builds raw and ECC views of a full OTP dump, with the white label data
+    // at 0x100.
+    fn full_dump(otp_data: &OtpData) -> (Vec<u32>, Vec<u16>) {
+        let mut raw = vec![0u32; TOTAL_OTP_ROWS];
+        let flags = otp_data.usb_boot_flags();
+        raw[OTP_ROW_USB_BOOT_FLAGS as usize] = flags;
+        raw[OTP_ROW_USB_BOOT_FLAGS_R1 as usize] = flags;
+        raw[OTP_ROW_USB_BOOT_FLAGS_R2 as usize] = flags;
+        raw[OTP_ROW_USB_WHITE_LABEL_DATA as usize] = ecc::encode(0x100);
+        for (ii, row) in otp_data.to_raw_rows().iter().enumerate() {
+            raw[0x100 + ii] = *row;
+        }
+        let ecc_rows = raw.iter().map(|r| ecc::decode(*r).data()).collect();
+        (raw, ecc_rows)
+    }
+
+    #[test]
+    fn test_to_raw_rows() {
+        let otp_data = OtpData::from_json(include_str!("../../json/sample-wl.json")).unwrap();
+        let raw = otp_data.to_raw_rows();
+        assert_eq!(raw.len(), otp_data.rows().len());
+        for (raw, row) in raw.iter().zip(otp_data.rows()) {
+            assert_eq!(ecc::decode(*raw).data(), *row);
+            assert!(ecc::decode(*raw).is_clean());
+        }
+        let bytes = otp_data.to_le_raw_bytes();
+        assert_eq!(bytes.len(), raw.len() * 4);
+        assert_eq!(
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            raw[0]
+        );
+    }
+
+    #[test]
+    fn test_from_full_otp_data_ecc_check() {
+        let otp_data = OtpData::from_json(include_str!("../../json/sample-wl.json")).unwrap();
+        let (raw, ecc_rows) = full_dump(&otp_data);
+        let raw: [u32; TOTAL_OTP_ROWS] = raw.try_into().unwrap();
+        let ecc_rows: [u16; TOTAL_OTP_ROWS] = ecc_rows.try_into().unwrap();
+
+        let parsed = OtpData::from_full_otp_data(&raw, &ecc_rows, true).unwrap();
+        assert_eq!(parsed.to_json().unwrap(), otp_data.to_json().unwrap());
+
+        // A single bit error is corrected, so is accepted
+        let mut bad_raw = raw;
+        bad_raw[0x101] ^= 1 << 3;
+        assert!(OtpData::from_full_otp_data(&bad_raw, &ecc_rows, true).is_ok());
+
+        // A double bit error is not
+        bad_raw[0x101] ^= 1 << 5;
+        assert!(matches!(
+            OtpData::from_full_otp_data(&bad_raw, &ecc_rows, true),
+            Err(Error::UncorrectableEcc(0x101))
+        ));
+        assert!(OtpData::from_full_otp_data(&bad_raw, &ecc_rows, false).is_ok());
+
+        // Nor is a mismatch between the two views
+        let mut bad_ecc = ecc_rows;
+        bad_ecc[0x102] ^= 1;
+        assert!(matches!(
+            OtpData::from_full_otp_data(&raw, &bad_ecc, true),
+            Err(Error::EccMismatch(0x102))
+        ));
+        let parsed = OtpData::from_full_otp_data(&raw, &bad_ecc, false).unwrap();
+        assert_eq!(parsed.ecc_warnings().len(), 1);
+        assert_eq!(parsed.ecc_warnings()[0].row(), 0x102);
+        assert_eq!(parsed.ecc_warnings()[0].data(), ecc_rows[0x102]);
+        assert_eq!(parsed.ecc_warnings()[0].ecc_data(), Some(bad_ecc[0x102]));
+
+        // Rows beyond the white label data are not checked
+        let num_rows = otp_data.rows().len();
+        assert_eq!(num_rows % 2, 1);
+        let mut extra_raw = raw;
+        extra_raw[0x100 + num_rows + 1] = 0x00ff_0003;
+        assert!(OtpData::from_full_otp_data(&extra_raw, &ecc_rows, true).is_ok());
+
+        // Unless they share an even/odd pair with the white label data
+        let neighbour = (0x100 + num_rows) as u16;
+        extra_raw[neighbour as usize] = 0x00ff_0003;
+        assert!(matches!(
+            OtpData::from_full_otp_data(&extra_raw, &ecc_rows, true),
+            Err(Error::ErrataE17(errata::E17Issue::MixedModes(row))) if row == neighbour - 1
+        ));
+        assert!(OtpData::from_full_otp_data(&extra_raw, &ecc_rows, false).is_ok());
+    }
+
+    #[test]
+    fn test_usb_boot_flags_voting() {
+        let otp_data = OtpData::from_json(include_str!("../../json/sample-wl.json")).unwrap();
+        let (raw, ecc_rows) = full_dump(&otp_data);
+        let mut raw: [u32; TOTAL_OTP_ROWS] = raw.try_into().unwrap();
+        let ecc_rows: [u16; TOTAL_OTP_ROWS] = ecc_rows.try_into().unwrap();
+        let flags = otp_data.usb_boot_flags();
+
+        // No two copies match, but each bit has a majority
+        raw[OTP_ROW_USB_BOOT_FLAGS as usize] = flags & !1;
+        raw[OTP_ROW_USB_BOOT_FLAGS_R1 as usize] = flags & !2;
+        assert!(matches!(
+            OtpData::from_full_otp_data(&raw, &ecc_rows, true),
+            Err(Error::NonMatchingUsbBootFlags)
+        ));
+        let parsed = OtpData::from_full_otp_data(&raw, &ecc_rows, false).unwrap();
+        assert_eq!(parsed.usb_boot_flags(), flags);
+        let disagreements = parsed.usb_boot_flags_disagreements();
+        assert_eq!(disagreements.len(), 2);
+        assert_eq!(disagreements[0].bit(), 0);
+        assert_eq!(disagreements[0].dissenting_copy(), 0);
+        assert_eq!(disagreements[1].bit(), 1);
+        assert_eq!(disagreements[1].dissenting_copy(), 1);
+    }
+
+    #[test]
+    fn test_from_raw_otp_data() {
+        let otp_data = OtpData::from_json(include_str!("../../json/test/complete.json")).unwrap();
+        let (raw, _) = full_dump(&otp_data);
+        let mut raw: [u32; TOTAL_OTP_ROWS] = raw.try_into().unwrap();
+
+        let parsed = OtpData::from_raw_otp_data(&raw, true).unwrap();
+        assert!(parsed.ecc_warnings().is_empty());
+        assert_eq!(parsed.rows()[..otp_data.rows().len()], otp_data.rows()[..]);
+        assert_eq!(parsed.to_json().unwrap(), otp_data.to_json().unwrap());
+
+        // Corrected and polarity reversed rows are reported, but accepted
+        raw[0x104] ^= 1 << 18;
+        raw[0x110] = (raw[0x110] ^ 0x003f_ffff) | ecc::ECC_BRP_MASK;
+        let parsed = OtpData::from_raw_otp_data(&raw, true).unwrap();
+        assert_eq!(parsed.to_json().unwrap(), otp_data.to_json().unwrap());
+        let warnings = parsed.ecc_warnings();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].row(), 0x104);
+        assert_eq!(
+            warnings[0].status(),
+            ecc::EccStatus::Corrected {
+                bit: 18,
+                polarity_reversed: false
+            }
+        );
+        assert_eq!(warnings[1].row(), 0x110);
+        assert_eq!(warnings[1].status(), ecc::EccStatus::PolarityReversed);
+
+        // Uncorrectable rows fail strict parsing, but are reported otherwise
+        raw[0x05c] ^= 0b11;
+        assert!(matches!(
+            OtpData::from_raw_otp_data(&raw, true),
+            Err(Error::UncorrectableEcc(0x05c))
+        ));
+        raw[0x05c] ^= 0b11;
+        raw[0x120] ^= 0b11 << 8;
+        let parsed = OtpData::from_raw_otp_data(&raw, false).unwrap();
+        assert_eq!(parsed.ecc_warnings().len(), 3);
+        assert_eq!(parsed.ecc_warnings()[2].row(), 0x120);
+        assert_eq!(
+            parsed.ecc_warnings()[2].status(),
+            ecc::EccStatus::Uncorrectable
+        );
+    }
+
+    #[test]
+    fn test_programming_plan() {
+        let otp_data = OtpData::from_json(include_str!("../../json/sample-wl.json")).unwrap();
+        let flags = otp_data.usb_boot_flags();
+        let valid_bit = 1 << WHITE_LABEL_ADDR_VALID_BIT_NUM;
+        let num_rows = otp_data.rows().len();
+        let plan = otp_data.programming_plan(0x100, false, None).unwrap();
+        assert_eq!(plan.len(), 1 + num_rows + 6);
+
+        assert_eq!(plan[0], OtpWrite::ecc(OTP_ROW_USB_WHITE_LABEL_DATA, 0x100));
+        for (ii, row) in otp_data.rows().iter().enumerate() {
+            assert_eq!(plan[1 + ii], OtpWrite::ecc(0x100 + ii as u16, *row));
+        }
+        let boot_flags = &plan[1 + num_rows..];
+        let rows = [
+            OTP_ROW_USB_BOOT_FLAGS,
+            OTP_ROW_USB_BOOT_FLAGS_R1,
+            OTP_ROW_USB_BOOT_FLAGS_R2,
+        ];
+        for (ii, row) in rows.iter().enumerate() {
+            assert_eq!(boot_flags[ii], OtpWrite::raw(*row, flags & !valid_bit));
+            assert_eq!(boot_flags[3 + ii], OtpWrite::raw(*row, flags | valid_bit));
+        }
+
+        // The data must fit within the unreserved rows
+        assert!(
+            otp_data
+                .programming_plan(OTP_ROW_UNRESERVED_START, false, None)
+                .is_ok()
+        );
+        assert!(matches!(
+            otp_data.programming_plan(OTP_ROW_UNRESERVED_START - 1, true, None),
+            Err(Error::InvalidWhiteLabelAddressValue(_))
+        ));
+        let last = OTP_ROW_UNRESERVED_END - num_rows as u16;
+        assert!(otp_data.programming_plan(last, true, None).is_ok());
+        assert!(matches!(
+            otp_data.programming_plan(last + 1, true, None),
+            Err(Error::InvalidWhiteLabelAddressValue(_))
+        ));
+
+        // Odd addresses are refused unless explicitly allowed
+        assert!(matches!(
+            otp_data.programming_plan(0x101, false, None),
+            Err(Error::ErrataE17(errata::E17Issue::OddStartAddress(0x101)))
+        ));
+        assert!(otp_data.programming_plan(0x101, true, None).is_ok());
+
+        // As are pages the bootloader can't read
+        let mut image = OtpImage::new();
+        let lock = PageLock {
+            bootloader: LockLevel::Inaccessible,
+            ..Default::default()
+        };
+        image
+            .apply_plan(&lock::plan_page_lock(&image, 4, &lock).unwrap())
+            .unwrap();
+        let locks = PageLocks::from_image(&image, true).unwrap();
+        assert!(matches!(
+            otp_data.programming_plan(0x100, false, Some(&locks)),
+            Err(Error::UnreadablePage(4))
+        ));
+        assert!(
+            otp_data
+                .programming_plan(0x140, false, Some(&locks))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_from_full_otp_data_address_near_end() {
+        let otp_data = OtpData::from_json(include_str!("../../json/sample-wl.json")).unwrap();
+        let (mut raw, _) = full_dump(&otp_data);
+
+        // Only room for the struct - parses, without the strings
+        raw[OTP_ROW_USB_WHITE_LABEL_DATA as usize] = ecc::encode(0xff0);
+        let raw: &mut [u32; TOTAL_OTP_ROWS] = raw.as_mut_slice().try_into().unwrap();
+        let parsed = OtpData::from_raw_otp_data(raw, false).unwrap();
+        assert_eq!(parsed.rows().len(), 16);
+        assert!(parsed.to_json().is_ok());
+
+        // No room for the struct, or beyond the end of OTP
+        for addr in [0xff1, 0xfff, 0xffff] {
+            raw[OTP_ROW_USB_WHITE_LABEL_DATA as usize] = ecc::encode(addr);
+            assert!(matches!(
+                OtpData::from_raw_otp_data(raw, false),
+                Err(Error::InvalidWhiteLabelAddressValue(a)) if a == addr
+            ));
+        }
+    }
 }
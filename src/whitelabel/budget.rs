@@ -0,0 +1,336 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Reports how much of the white label data's row budget its strings use,
+//! and how long each string field could be.
+//!
+//! Each STRDEF holds an 8-bit offset, so every string must start within 255
+//! rows of the start of the white label struct, although the last may extend
+//! beyond it.  ASCII strings use one row per two characters, and UTF-16
+//! strings one row per code unit.  The strings are placed according to the
+//! struct's [`LayoutPolicy`](crate::whitelabel::LayoutPolicy).
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::Serialize;
+
+use crate::whitelabel::strdef::{self, MAX_STRING_OFFSET};
+use crate::whitelabel::{FIELDS, Field, FieldValue, OtpString, WhiteLabelStruct};
+
+/// Row usage of a [`WhiteLabelStruct`], returned by
+/// [`WhiteLabelStruct::row_budget`].
+///
+/// ```rust
+/// use pico_otp::WhiteLabelStruct;
+/// use pico_otp::whitelabel::fields::FIELD_USB_PRODUCT;
+///
+/// # fn main() -> Result<(), pico_otp::WhiteLabelError> {
+/// let mut wls = WhiteLabelStruct::default();
+/// wls.set_manufacturer("My Company")?;
+///
+/// let budget = wls.row_budget();
+/// assert_eq!(budget.rows_used(), 16 + 5);
+/// assert_eq!(budget.rows_remaining(), 256 - 16 - 5);
+/// assert_eq!(budget.field(&FIELD_USB_PRODUCT).unwrap().max_ascii_chars(), 30);
+/// #   Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RowBudget {
+    rows_used: usize,
+    fits: bool,
+    fields: Vec<FieldBudget>,
+}
+
+impl RowBudget {
+    pub(crate) fn new(wls: &WhiteLabelStruct) -> Self {
+        let layout = wls.layout();
+        let fields = FIELDS
+            .iter()
+            .filter(|field| field.is_string())
+            .map(|field| {
+                let mut budget = FieldBudget::new(wls, field);
+                budget.shared = layout.rows_used(field) == Some(0) && budget.rows > 0;
+                budget.max_ascii_chars = max_chars(wls, field, false);
+                budget.max_utf16_chars = if field.supports_utf16() {
+                    max_chars(wls, field, true)
+                } else {
+                    0
+                };
+                budget
+            })
+            .collect();
+
+        Self {
+            rows_used: layout.row_count(),
+            fits: layout.fits(),
+            fields,
+        }
+    }
+
+    /// Returns the number of rows used by the white label struct and its
+    /// strings.
+    pub fn rows_used(&self) -> usize {
+        self.rows_used
+    }
+
+    /// Returns the number of unused rows at offsets up to 255, where strings
+    /// can start.  A string may be longer than this if it can be placed last,
+    /// as it may extend beyond offset 255.
+    pub fn rows_remaining(&self) -> usize {
+        (MAX_STRING_OFFSET + 1).saturating_sub(self.rows_used)
+    }
+
+    /// Returns true if every string starts within the rows available.  This
+    /// may be false for white label data parsed from OTP, or with long
+    /// strings set.
+    pub fn fits(&self) -> bool {
+        self.fits
+    }
+
+    /// Returns the budget for each string field, in field index order.
+    pub fn fields(&self) -> &[FieldBudget] {
+        &self.fields
+    }
+
+    /// Returns the budget for the given field, or None if it isn't a string
+    /// field.
+    pub fn field(&self, field: &Field) -> Option<&FieldBudget> {
+        self.fields.iter().find(|f| f.field_index == field.index())
+    }
+}
+
+/// Row usage of, and maximum length available to, a single string field.
+/// Part of a [`RowBudget`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldBudget {
+    field_index: usize,
+    field_name: &'static str,
+    is_set: bool,
+    chars: usize,
+    rows: usize,
+    is_utf16: bool,
+    shared: bool,
+    max_ascii_chars: usize,
+    max_utf16_chars: usize,
+}
+
+impl FieldBudget {
+    fn new(wls: &WhiteLabelStruct, field: &Field) -> Self {
+        let value = wls.get(field);
+        let (chars, rows, is_utf16) = match &value {
+            Some(FieldValue::String(value)) => strdef::string_layout(value),
+            _ => (0, 0, false),
+        };
+        Self {
+            field_index: field.index(),
+            field_name: field.name(),
+            is_set: value.is_some(),
+            chars,
+            rows,
+            is_utf16,
+            shared: false,
+            max_ascii_chars: 0,
+            max_utf16_chars: 0,
+        }
+    }
+
+    /// Returns the field.
+    pub fn field(&self) -> &'static Field {
+        &FIELDS[self.field_index]
+    }
+
+    /// Returns true if the field is set.
+    pub fn is_set(&self) -> bool {
+        self.is_set
+    }
+
+    /// Returns the number of characters in the field's value, counting each
+    /// UTF-16 code unit as a character, as the STRDEF does.  0 if unset.
+    pub fn chars(&self) -> usize {
+        self.chars
+    }
+
+    /// Returns the number of rows the field's value needs, in its encoding.
+    /// 0 if unset.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns true if the field's value shares the rows of another field's,
+    /// so adds no rows.  Only happens with
+    /// [`LayoutPolicy::Compact`](crate::whitelabel::LayoutPolicy::Compact).
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Returns true if the field's value is stored as UTF-16, because it
+    /// contains non-ASCII characters.
+    pub fn is_utf16(&self) -> bool {
+        self.is_utf16
+    }
+
+    /// Returns the maximum number of ASCII characters this field could be set
+    /// to, given the rows used by the other fields.  Assumes the value
+    /// wouldn't share rows with another field.
+    pub fn max_ascii_chars(&self) -> usize {
+        self.max_ascii_chars
+    }
+
+    /// Returns the maximum number of UTF-16 code units this field could be
+    /// set to, given the rows used by the other fields.  Characters outside
+    /// the Basic Multilingual Plane, such as emoji, use two.  0 if the field
+    /// only supports ASCII.
+    pub fn max_utf16_chars(&self) -> usize {
+        self.max_utf16_chars
+    }
+}
+
+// Returns the maximum number of characters `field` could be set to, in the
+// given encoding, with the other fields unchanged.
+fn max_chars(wls: &WhiteLabelStruct, field: &'static Field, utf16: bool) -> usize {
+    let max_length = field.max_length().unwrap();
+    let max_rows = if utf16 {
+        max_length
+    } else {
+        max_length.div_ceil(2)
+    };
+    let chars = |rows: usize| if utf16 { rows } else { rows * 2 }.min(max_length);
+
+    // Lays out a value which uses the given number of rows, and is unlikely
+    // to share rows with another field.
+    let fits = |rows: usize| {
+        let value = (rows > 0).then(|| {
+            let c = if utf16 { '\u{fffd}' } else { '\x7f' };
+            OtpString::new(core::iter::repeat_n(c, chars(rows)).collect::<String>())
+        });
+        wls.layout_with(|f| {
+            if f.index() == field.index() {
+                value.as_ref()
+            } else {
+                wls.string_field(f)
+            }
+        })
+        .fits()
+    };
+
+    // More rows never makes the layout fit, so binary search for the most
+    // rows which fit
+    let (mut low, mut high) = (0, max_rows);
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if fits(mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    chars(low)
+}
+
+impl WhiteLabelStruct {
+    /// Returns how many rows this white label struct's strings use, how many
+    /// remain, and the maximum length each string field could be set to.
+    pub fn row_budget(&self) -> RowBudget {
+        RowBudget::new(self)
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+    use crate::whitelabel::LayoutPolicy;
+    use crate::whitelabel::fields::{
+        FIELD_SCSI_VENDOR, FIELD_UF2_BOARD_ID, FIELD_USB_MANUFACTURER, FIELD_USB_PRODUCT,
+        FIELD_USB_VENDOR_ID, FIELD_VOLUME_LABEL,
+    };
+
+    #[test]
+    fn test_row_budget() {
+        let mut wls = WhiteLabelStruct::default();
+        let budget = wls.row_budget();
+        assert_eq!(budget.rows_used(), 16);
+        assert_eq!(budget.rows_remaining(), 240);
+        assert_eq!(budget.fields().len(), 11);
+        assert!(budget.field(&FIELD_USB_VENDOR_ID).is_none());
+        let manufacturer = budget.field(&FIELD_USB_MANUFACTURER).unwrap();
+        assert!(!manufacturer.is_set());
+        assert_eq!(manufacturer.max_ascii_chars(), 30);
+        assert_eq!(manufacturer.max_utf16_chars(), 30);
+        let scsi_vendor = budget.field(&FIELD_SCSI_VENDOR).unwrap();
+        assert_eq!(scsi_vendor.max_ascii_chars(), 8);
+        assert_eq!(scsi_vendor.max_utf16_chars(), 0);
+
+        // 3 UTF-16 code units, and 3 ASCII characters
+        wls.set_manufacturer("a😀").unwrap();
+        wls.set_product("abc").unwrap();
+        let budget = wls.row_budget();
+        let manufacturer = budget.field(&FIELD_USB_MANUFACTURER).unwrap();
+        assert!(manufacturer.is_utf16());
+        assert_eq!(manufacturer.chars(), 3);
+        assert_eq!(manufacturer.rows(), 3);
+        let product = budget.field(&FIELD_USB_PRODUCT).unwrap();
+        assert!(!product.is_utf16());
+        assert_eq!(product.chars(), 3);
+        assert_eq!(product.rows(), 2);
+        assert_eq!(budget.rows_used(), 21);
+        assert_eq!(budget.rows_remaining(), 235);
+
+        // Sharing rows
+        wls.set_product("😀").unwrap();
+        wls.set_layout_policy(LayoutPolicy::Compact);
+        let budget = wls.row_budget();
+        assert!(budget.field(&FIELD_USB_PRODUCT).unwrap().is_shared());
+        assert_eq!(budget.rows_used(), 19);
+    }
+
+    #[test]
+    fn test_row_budget_limited() {
+        // 90 rows of UTF-16 and 192 rows of ASCII strings.  The last string
+        // starts at row 234, as the longest is placed last.
+        let mut wls = WhiteLabelStruct::default();
+        wls.set_manufacturer("号".repeat(30)).unwrap();
+        wls.set_product("号".repeat(30)).unwrap();
+        wls.set_serial_number("号".repeat(30)).unwrap();
+        wls.set_redirect_url("a".repeat(127)).unwrap();
+        wls.set_redirect_name("a".repeat(127)).unwrap();
+        wls.set_uf2_model("a".repeat(127)).unwrap();
+        let budget = wls.row_budget();
+        assert_eq!(budget.rows_used(), 16 + 90 + 192);
+        assert_eq!(budget.rows_remaining(), 0);
+        assert!(budget.fits());
+
+        // Unset fields are limited to the 21 rows before the last string
+        // would start beyond row 255
+        let board_id = budget.field(&FIELD_UF2_BOARD_ID).unwrap();
+        assert_eq!(board_id.max_ascii_chars(), 42);
+        let volume_label = budget.field(&FIELD_VOLUME_LABEL).unwrap();
+        assert_eq!(volume_label.max_ascii_chars(), 11);
+
+        // A set field may also reuse its own rows
+        let manufacturer = budget.field(&FIELD_USB_MANUFACTURER).unwrap();
+        assert_eq!(manufacturer.max_utf16_chars(), 30);
+
+        // The maximum is accepted, but one more isn't
+        wls.set_uf2_board_id("a".repeat(42)).unwrap();
+        assert!(wls.is_clean());
+        wls.set_uf2_board_id("a".repeat(43)).unwrap();
+        assert!(!wls.is_clean());
+        assert!(!wls.row_budget().fits());
+
+        // In field order, the board ID can't be placed at all
+        wls.clear(&FIELD_UF2_BOARD_ID);
+        wls.set_layout_policy(LayoutPolicy::FieldOrder);
+        let budget = wls.row_budget();
+        assert_eq!(
+            budget.field(&FIELD_UF2_BOARD_ID).unwrap().max_ascii_chars(),
+            0
+        );
+        assert_eq!(
+            budget.field(&FIELD_VOLUME_LABEL).unwrap().max_ascii_chars(),
+            11
+        );
+    }
+}
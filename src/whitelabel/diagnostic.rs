@@ -0,0 +1,264 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Structured warnings produced when validating white label data, or parsing
+//! it from OTP.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use serde::Serialize;
+
+use crate::whitelabel::fields::Field;
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Notable, but not a problem.
+    Info,
+
+    /// Suspicious, but the data was still used.
+    Warning,
+
+    /// The data is invalid, and the affected field was not used, or cannot
+    /// be stored in OTP.
+    Error,
+}
+
+/// What a [`Diagnostic`] is about.  Each code always has the same
+/// [`Severity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticCode {
+    /// The WHITE_LABEL_ADDR_VALID bit in USB_BOOT_FLAGS is clear.
+    AddrValidClear,
+
+    /// The DPDM_SWAP bit in USB_BOOT_FLAGS is set.
+    DpDmSwap,
+
+    /// Reserved bits in USB_BOOT_FLAGS are set.
+    ReservedBootFlags,
+
+    /// A field's boot flag bit is clear, but its struct row isn't zero.
+    FieldNotEnabled,
+
+    /// A string field's boot flag bit is set, but its STRDEF is zero.
+    EmptyStrdef,
+
+    /// A STRDEF's offset points into the struct rows.
+    StringOffsetInStruct,
+
+    /// A STRDEF is UTF-16, but the field only supports ASCII.
+    Utf16NotAllowed,
+
+    /// A string extends beyond the rows provided.
+    StringOutOfBounds,
+
+    /// A UTF-16 string isn't valid UTF-16.
+    InvalidUtf16,
+
+    /// An ASCII string has non-zero padding after its last character.
+    NonZeroPadding,
+
+    /// A string which must be ASCII contains non-ASCII characters.
+    NonAsciiString,
+
+    /// A UTF-16 string contains only ASCII characters, so would be stored as
+    /// ASCII if written back.
+    Utf16AsciiOnly,
+
+    /// A string is longer than the field's maximum length.
+    StringTooLong,
+
+    /// A string is empty.
+    EmptyString,
+
+    /// The USB attributes or max power value is invalid.
+    InvalidAttrPower,
+
+    /// The USB BCD device value is invalid.
+    InvalidBcdDevice,
+
+    /// There are too few rows for the strings indicated by the boot flags, so
+    /// no strings were parsed.
+    TooFewRows,
+
+    /// There are fewer rows than the parsed strings need.
+    RowCountMismatch,
+
+    /// The white label data needs more rows than can be generated.
+    TooManyRows,
+}
+
+impl DiagnosticCode {
+    /// Returns the severity of diagnostics with this code.
+    pub fn severity(&self) -> Severity {
+        match self {
+            DiagnosticCode::DpDmSwap => Severity::Info,
+            DiagnosticCode::AddrValidClear
+            | DiagnosticCode::ReservedBootFlags
+            | DiagnosticCode::FieldNotEnabled
+            | DiagnosticCode::Utf16NotAllowed
+            | DiagnosticCode::NonZeroPadding
+            | DiagnosticCode::Utf16AsciiOnly
+            | DiagnosticCode::RowCountMismatch => Severity::Warning,
+            DiagnosticCode::EmptyStrdef
+            | DiagnosticCode::StringOffsetInStruct
+            | DiagnosticCode::StringOutOfBounds
+            | DiagnosticCode::InvalidUtf16
+            | DiagnosticCode::NonAsciiString
+            | DiagnosticCode::StringTooLong
+            | DiagnosticCode::EmptyString
+            | DiagnosticCode::InvalidAttrPower
+            | DiagnosticCode::InvalidBcdDevice
+            | DiagnosticCode::TooFewRows
+            | DiagnosticCode::TooManyRows => Severity::Error,
+        }
+    }
+}
+
+/// A problem, or point of interest, found when validating white label data or
+/// parsing it from OTP.
+///
+/// `Display` gives the human readable message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    code: DiagnosticCode,
+    severity: Severity,
+    field_index: Option<usize>,
+    field_name: Option<&'static str>,
+    rows: Vec<usize>,
+    message: String,
+}
+
+impl Diagnostic {
+    // Creates a diagnostic which doesn't relate to a single field.
+    pub(crate) fn new(code: DiagnosticCode, message: String) -> Self {
+        Self {
+            code,
+            severity: code.severity(),
+            field_index: None,
+            field_name: None,
+            rows: Vec::new(),
+            message,
+        }
+    }
+
+    // Creates a diagnostic about `field`, involving its struct row.
+    pub(crate) fn for_field(code: DiagnosticCode, field: &Field, message: String) -> Self {
+        Self {
+            field_index: Some(field.index()),
+            field_name: Some(field.name()),
+            rows: Vec::from([field.index()]),
+            ..Self::new(code, message)
+        }
+    }
+
+    // Adds the given rows to those involved.
+    pub(crate) fn with_rows(mut self, rows: Range<usize>) -> Self {
+        self.rows.extend(rows);
+        self
+    }
+
+    /// Returns what the diagnostic is about.
+    pub fn code(&self) -> DiagnosticCode {
+        self.code
+    }
+
+    /// Returns how serious the diagnostic is.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Returns the index of the affected field within the white label struct,
+    /// if the diagnostic relates to a single field.
+    pub fn field_index(&self) -> Option<usize> {
+        self.field_index
+    }
+
+    /// Returns the name of the affected field, such as `usb_manufacturer`, if
+    /// the diagnostic relates to a single field.
+    pub fn field_name(&self) -> Option<&'static str> {
+        self.field_name
+    }
+
+    /// Returns the affected field, if the diagnostic relates to a single
+    /// field.
+    pub fn field(&self) -> Option<&'static Field> {
+        self.field_index.and_then(Field::from_index)
+    }
+
+    /// Returns the offsets of the OTP rows involved, from the start of the
+    /// white label struct.  Empty if the diagnostic doesn't relate to
+    /// specific rows.
+    pub fn rows(&self) -> &[usize] {
+        &self.rows
+    }
+
+    /// Returns the human readable message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl core::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+// Joins the diagnostics' messages, one per line.
+pub(crate) fn join(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+    use crate::WhiteLabelStruct;
+    use crate::whitelabel::fields::FIELD_USB_MANUFACTURER;
+
+    #[test]
+    fn test_diagnostics() {
+        // From validation
+        let mut wls = WhiteLabelStruct::default();
+        wls.set_manufacturer("").unwrap();
+        let diagnostic = &wls.warnings()[0];
+        assert_eq!(diagnostic.code(), DiagnosticCode::EmptyString);
+        assert_eq!(diagnostic.severity(), Severity::Error);
+        assert_eq!(diagnostic.field_index(), Some(4));
+        assert_eq!(diagnostic.field_name(), Some("usb_manufacturer"));
+        assert_eq!(diagnostic.field(), Some(&FIELD_USB_MANUFACTURER));
+        assert_eq!(diagnostic.rows(), &[4]);
+        assert_eq!(
+            diagnostic.to_string(),
+            "Field 'usb_manufacturer' is an empty string"
+        );
+
+        // From parsing OTP - a 3 character ASCII product at offset 16, with
+        // non-zero padding
+        let mut rows = [0u16; 18];
+        rows[5] = 0x1003;
+        rows[16] = 0x6261;
+        rows[17] = 0xff63;
+        let flags = 0x0040_0020;
+        let result = WhiteLabelStruct::parse_otp(flags, &rows).unwrap();
+        let diagnostic = &result.warnings()[0];
+        assert_eq!(diagnostic.code(), DiagnosticCode::NonZeroPadding);
+        assert_eq!(diagnostic.severity(), Severity::Warning);
+        assert_eq!(diagnostic.field_name(), Some("usb_product"));
+        assert_eq!(diagnostic.rows(), &[5, 16, 17]);
+        assert_eq!(
+            diagnostic.to_string(),
+            "usb_product: non-zero padding bytes found after declared length 3"
+        );
+        assert_eq!(result.white_label().product().unwrap(), "abc");
+    }
+}
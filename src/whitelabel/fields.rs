@@ -3,18 +3,34 @@
 // MIT License
 
 //! Definitions of fields in the white label struct.
+//!
+//! Each of the 16 fields is described by a [`Field`], which gives its index,
+//! name, [`FieldKind`], default value and the JSON path(s) used for it in the
+//! `picotool` white label schema.  [`FIELDS`] lists them all, in index
+//! order.  With the `alloc` feature, use `WhiteLabelStruct::get` and
+//! `WhiteLabelStruct::set` to access a field's value in a white label struct.
+//!
+//! The table itself doesn't require `alloc`, so is also used by
+//! [`heapless`](crate::heapless).
 
+#[cfg(feature = "alloc")]
 use alloc::format;
-use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::string::{String, ToString};
 
+#[cfg(feature = "alloc")]
 use crate::WhiteLabelStruct;
+#[cfg(feature = "alloc")]
+use crate::usb;
+#[cfg(feature = "alloc")]
+use crate::whitelabel::{Diagnostic, DiagnosticCode};
 
-// No OTP string field can be longer than this.
+/// No OTP string field can be longer than this.
 pub const MAX_STRING_LENGTH: usize = 127;
 
 /// Kind of field in the white label struct.
-#[derive(Debug, Clone, PartialEq)]
-pub(crate) enum FieldKind {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
     /// String supporting both ASCII and UTF-16, with the given maximum
     /// number of characters.
     StringUtf16AndAscii(usize),
@@ -25,14 +41,64 @@ pub(crate) enum FieldKind {
 
     /// u16 field.
     U16,
+
+    /// u16 field holding a BCD value, such as 0x0234 for version 2.34.
+    Bcd16,
+
+    /// u16 field holding the USB attributes in the low byte, and max power
+    /// in the high byte.
+    AttrPower,
+}
+
+/// Value of a field in the white label struct.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldValue {
+    /// Value of a u16 field.
+    U16(u16),
+
+    /// Value of a string field.
+    String(String),
+}
+
+#[cfg(feature = "alloc")]
+impl From<u16> for FieldValue {
+    fn from(value: u16) -> Self {
+        FieldValue::U16(value)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        FieldValue::String(value.to_string())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        FieldValue::String(value)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl core::fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FieldValue::U16(value) => write!(f, "{value:#06x}"),
+            FieldValue::String(value) => f.write_str(value),
+        }
+    }
 }
 
 /// Definition of a field in the white label struct.
-#[derive(Debug, Clone, PartialEq)]
-pub(crate) struct Field {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
     index: usize,
     name: &'static str,
     kind: FieldKind,
+    json_paths: &'static [&'static str],
 }
 
 impl Field {
@@ -46,9 +112,69 @@ impl Field {
         self.index
     }
 
+    /// Returns the field with the given index, if there is one.
+    pub fn from_index(index: usize) -> Option<&'static Field> {
+        FIELDS.get(index)
+    }
+
+    /// Returns the field with the given name, such as `usb_manufacturer`, if
+    /// there is one.
+    pub fn from_name(name: &str) -> Option<&'static Field> {
+        FIELDS.iter().find(|field| field.name == name)
+    }
+
+    /// Returns the field stored from the given JSON path in the `picotool`
+    /// white label schema, such as `device.manufacturer`, if there is one.
+    pub fn from_json_path(path: &str) -> Option<&'static Field> {
+        FIELDS.iter().find(|field| field.json_paths.contains(&path))
+    }
+
+    /// Returns the kind of the field.
+    pub fn kind(&self) -> FieldKind {
+        self.kind
+    }
+
+    /// Returns the JSON path(s) of the field in the `picotool` white label
+    /// schema.  `usb_attr_power` is the only field with two -
+    /// `device.attributes` and `device.max_power`.
+    pub fn json_paths(&self) -> &'static [&'static str] {
+        self.json_paths
+    }
+
+    /// Returns the value the bootrom uses if the field isn't set, or None for
+    /// `usb_serial_number`, which defaults to the device's chip ID, and
+    /// `redirect_url`, whose default is not known.  See
+    /// [`crate::usb::UsbIdentity`].
+    #[cfg(feature = "alloc")]
+    pub fn default_value(&self) -> Option<FieldValue> {
+        let value = match self.index {
+            0 => usb::DEFAULT_VENDOR_ID.into(),
+            1 => usb::DEFAULT_PRODUCT_ID.into(),
+            2 => usb::DEFAULT_BCD_DEVICE.into(),
+            3 => usb::DEFAULT_LANGUAGE_ID.into(),
+            4 => usb::DEFAULT_MANUFACTURER.into(),
+            5 => usb::DEFAULT_PRODUCT.into(),
+            6 => return None,
+            7 => usb::DEFAULT_ATTR_POWER.into(),
+            8 => usb::DEFAULT_VOLUME_LABEL.into(),
+            9 => usb::DEFAULT_SCSI_VENDOR.into(),
+            10 => usb::DEFAULT_SCSI_PRODUCT.into(),
+            11 => usb::DEFAULT_SCSI_VERSION.into(),
+            12 => return None,
+            13 => usb::DEFAULT_REDIRECT_NAME.into(),
+            14 => usb::DEFAULT_UF2_MODEL.into(),
+            15 => usb::DEFAULT_UF2_BOARD_ID.into(),
+            _ => unreachable!("Unknown field index"),
+        };
+        Some(value)
+    }
+
     /// Returns true if the field is a u16 field.
     pub fn is_u16(&self) -> bool {
-        matches!(self.kind, FieldKind::U16)
+        matches!(
+            self.kind,
+            FieldKind::U16 | FieldKind::Bcd16 | FieldKind::AttrPower
+        )
     }
 
     /// Returns true if the field is a string field.
@@ -57,7 +183,7 @@ impl Field {
     }
 
     /// Returns true if the field supports UTF-16 encoding (and is a string
-    /// field).
+    /// field).  All string fields support ASCII.
     pub fn supports_utf16(&self) -> bool {
         matches!(self.kind, FieldKind::StringUtf16AndAscii(_))
     }
@@ -67,12 +193,13 @@ impl Field {
         match &self.kind {
             FieldKind::StringUtf16AndAscii(len) => Some(*len),
             FieldKind::StringAsciiOnly(len) => Some(*len),
-            FieldKind::U16 => None,
+            FieldKind::U16 | FieldKind::Bcd16 | FieldKind::AttrPower => None,
         }
     }
 
     /// Validates the field value in the given white label struct.
-    pub fn validate(&self, wls: &WhiteLabelStruct) -> Result<(), String> {
+    #[cfg(feature = "alloc")]
+    pub fn validate(&self, wls: &WhiteLabelStruct) -> Result<(), Diagnostic> {
         if self.is_string() {
             self.validate_string_field(wls)
         } else {
@@ -80,7 +207,8 @@ impl Field {
         }
     }
 
-    fn validate_u16_field(&self, wls: &WhiteLabelStruct) -> Result<(), String> {
+    #[cfg(feature = "alloc")]
+    fn validate_u16_field(&self, wls: &WhiteLabelStruct) -> Result<(), Diagnostic> {
         assert!(self.is_u16());
         match self.name() {
             "usb_vendor_id" => Ok(()),
@@ -90,14 +218,20 @@ impl Field {
                 if let Some(attr_power) = wls.attr_power() {
                     let attr = attr_power & 0xFF;
                     if (attr & 0x80) == 0 || (attr & 0x1F) != 0 {
-                        return Err(format!("Invalid usb_attr_power {:#04X}", attr));
+                        return Err(self.diagnostic(
+                            DiagnosticCode::InvalidAttrPower,
+                            format!("Invalid usb_attr_power {:#04X}", attr),
+                        ));
                     }
 
                     let power = (attr_power >> 8) & 0xFF;
                     let max_power = if (attr & 0x40) != 0 { 510 } else { 500 };
                     if power == 0 || power > max_power {
-                        return Err(format!(
-                            "Invalid usb_attr_power: power must be between 1 and {max_power} mA, got {power} mA",
+                        return Err(self.diagnostic(
+                            DiagnosticCode::InvalidAttrPower,
+                            format!(
+                                "Invalid usb_attr_power: power must be between 1 and {max_power} mA, got {power} mA",
+                            ),
                         ));
                     }
                 }
@@ -109,7 +243,10 @@ impl Field {
                     let minor = (bcd >> 4) & 0x0F;
                     let patch = bcd & 0x0F;
                     if major > 99 || minor > 9 || patch > 9 {
-                        return Err(format!("Invalid usb_bcd_device: {:04X} (max 99.9.9)", bcd));
+                        return Err(self.diagnostic(
+                            DiagnosticCode::InvalidBcdDevice,
+                            format!("Invalid usb_bcd_device: {:04X} (max 99.9.9)", bcd),
+                        ));
                     }
                 }
                 Ok(())
@@ -118,137 +255,171 @@ impl Field {
         }
     }
 
-    fn validate_string_field(&self, wls: &WhiteLabelStruct) -> Result<(), String> {
+    #[cfg(feature = "alloc")]
+    fn validate_string_field(&self, wls: &WhiteLabelStruct) -> Result<(), Diagnostic> {
         assert!(self.is_string());
 
         let max_len = self.max_length().unwrap();
         let field_name = self.name();
 
-        let field_value = match field_name {
-            "usb_manufacturer" => wls.manufacturer(),
-            "usb_product" => wls.product(),
-            "usb_serial_number" => wls.serial_number(),
-            "volume_label" => wls.volume_label(),
-            "scsi_vendor" => wls.scsi_vendor(),
-            "scsi_product" => wls.scsi_product(),
-            "scsi_version" => wls.scsi_version(),
-            "uf2_model" => wls.uf2_model(),
-            "uf2_board_id" => wls.uf2_board_id(),
-            "redirect_url" => wls.redirect_url(),
-            "redirect_name" => wls.redirect_name(),
-            _ => panic!("Unknown field"),
+        let field_value = match wls.get(self) {
+            Some(FieldValue::String(value)) => Some(value),
+            _ => None,
         };
 
         if let Some(value) = field_value {
             let value_len = value.chars().count();
             if value_len == 0 {
-                return Err(format!("Field '{field_name}' is an empty string",));
+                return Err(self.diagnostic(
+                    DiagnosticCode::EmptyString,
+                    format!("Field '{field_name}' is an empty string",),
+                ));
             }
             if value_len > max_len {
-                return Err(format!(
-                    "Field '{field_name}' is too long: max length is {max_len}, got {value_len}",
+                return Err(self.diagnostic(
+                    DiagnosticCode::StringTooLong,
+                    format!(
+                        "Field '{field_name}' is too long: max length is {max_len}, got {value_len}",
+                    ),
                 ));
             }
 
-            if !self.supports_utf16() {
-                if !value.is_ascii() {
-                    return Err(format!(
-                        "Field '{field_name}' contains non-ASCII characters",
-                    ));
-                }
+            if !self.supports_utf16() && !value.is_ascii() {
+                return Err(self.diagnostic(
+                    DiagnosticCode::NonAsciiString,
+                    format!("Field '{field_name}' contains non-ASCII characters",),
+                ));
             }
         }
 
         Ok(())
     }
+
+    // Returns a diagnostic about this field.
+    #[cfg(feature = "alloc")]
+    fn diagnostic(&self, code: DiagnosticCode, message: String) -> Diagnostic {
+        Diagnostic::for_field(code, self, message)
+    }
 }
 
 /// Number of fields in the white label struct.
 pub const NUM_FIELDS: usize = 16;
 
+/// USB device vendor ID (VID) field.
 pub const FIELD_USB_VENDOR_ID: Field = Field {
     index: 0,
     name: "usb_vendor_id",
     kind: FieldKind::U16,
+    json_paths: &["device.vid"],
 };
+/// USB device product ID (PID) field.
 pub const FIELD_USB_PRODUCT_ID: Field = Field {
     index: 1,
     name: "usb_product_id",
     kind: FieldKind::U16,
+    json_paths: &["device.pid"],
 };
+/// USB device version field.
 pub const FIELD_USB_BCD_DEVICE: Field = Field {
     index: 2,
     name: "usb_bcd_device",
-    kind: FieldKind::U16,
+    kind: FieldKind::Bcd16,
+    json_paths: &["device.bcd"],
 };
+/// USB device language ID field.
 pub const FIELD_USB_LANGUAGE_ID: Field = Field {
     index: 3,
     name: "usb_language_id",
     kind: FieldKind::U16,
+    json_paths: &["device.lang_id"],
 };
+/// USB device manufacturer field.
 pub const FIELD_USB_MANUFACTURER: Field = Field {
     index: 4,
     name: "usb_manufacturer",
     kind: FieldKind::StringUtf16AndAscii(30),
+    json_paths: &["device.manufacturer"],
 };
+/// USB device product field.
 pub const FIELD_USB_PRODUCT: Field = Field {
     index: 5,
     name: "usb_product",
     kind: FieldKind::StringUtf16AndAscii(30),
+    json_paths: &["device.product"],
 };
+/// USB device serial number field.
 pub const FIELD_USB_SERIAL_NUMBER: Field = Field {
     index: 6,
     name: "usb_serial_number",
     kind: FieldKind::StringUtf16AndAscii(30),
+    json_paths: &["device.serial_number"],
 };
+/// USB device attributes and max power field.
 pub const FIELD_USB_ATTR_POWER: Field = Field {
     index: 7,
     name: "usb_attr_power",
-    kind: FieldKind::U16,
+    kind: FieldKind::AttrPower,
+    json_paths: &["device.attributes", "device.max_power"],
 };
+/// Mass storage volume label field.
 pub const FIELD_VOLUME_LABEL: Field = Field {
     index: 8,
     name: "volume_label",
     kind: FieldKind::StringAsciiOnly(11),
+    json_paths: &["volume.label"],
 };
+/// SCSI inquiry vendor field.
 pub const FIELD_SCSI_VENDOR: Field = Field {
     index: 9,
     name: "scsi_vendor",
     kind: FieldKind::StringAsciiOnly(8),
+    json_paths: &["scsi.vendor"],
 };
+/// SCSI inquiry product field.
 pub const FIELD_SCSI_PRODUCT: Field = Field {
     index: 10,
     name: "scsi_product",
     kind: FieldKind::StringAsciiOnly(16),
+    json_paths: &["scsi.product"],
 };
+/// SCSI inquiry version field.
 pub const FIELD_SCSI_VERSION: Field = Field {
     index: 11,
     name: "scsi_version",
     kind: FieldKind::StringAsciiOnly(4),
+    json_paths: &["scsi.version"],
 };
+/// INDEX.HTM redirect URL field.
 pub const FIELD_REDIRECT_URL: Field = Field {
     index: 12,
     name: "redirect_url",
     kind: FieldKind::StringAsciiOnly(127),
+    json_paths: &["volume.redirect_url"],
 };
+/// INDEX.HTM redirect name field.
 pub const FIELD_REDIRECT_NAME: Field = Field {
     index: 13,
     name: "redirect_name",
     kind: FieldKind::StringAsciiOnly(127),
+    json_paths: &["volume.redirect_name"],
 };
+/// INFO_UF2.TXT model field.
 pub const FIELD_UF2_MODEL: Field = Field {
     index: 14,
     name: "uf2_model",
     kind: FieldKind::StringAsciiOnly(127),
+    json_paths: &["volume.model"],
 };
+/// INFO_UF2.TXT board ID field.
 pub const FIELD_UF2_BOARD_ID: Field = Field {
     index: 15,
     name: "uf2_board_id",
     kind: FieldKind::StringAsciiOnly(127),
+    json_paths: &["volume.board_id"],
 };
 
-/// White label struct fields.
-pub const FIELDS: [Field; NUM_FIELDS] = [
+/// White label struct fields, in index order.
+pub static FIELDS: [Field; NUM_FIELDS] = [
     FIELD_USB_VENDOR_ID,
     FIELD_USB_PRODUCT_ID,
     FIELD_USB_BCD_DEVICE,
@@ -266,3 +437,73 @@ pub const FIELDS: [Field; NUM_FIELDS] = [
     FIELD_UF2_MODEL,
     FIELD_UF2_BOARD_ID,
 ];
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+    use crate::whitelabel::Error;
+
+    #[test]
+    fn test_field_lookup() {
+        for (index, field) in FIELDS.iter().enumerate() {
+            assert_eq!(field.index(), index);
+            assert_eq!(Field::from_index(index), Some(field));
+            assert_eq!(Field::from_name(field.name()), Some(field));
+            for path in field.json_paths() {
+                assert_eq!(Field::from_json_path(path), Some(field));
+            }
+            match field.default_value() {
+                Some(FieldValue::U16(_)) => assert!(field.is_u16()),
+                Some(FieldValue::String(value)) => {
+                    assert!(value.chars().count() <= field.max_length().unwrap());
+                }
+                None => assert!(field == &FIELD_USB_SERIAL_NUMBER || field == &FIELD_REDIRECT_URL),
+            }
+        }
+        assert_eq!(Field::from_index(NUM_FIELDS), None);
+        assert_eq!(Field::from_name("manufacturer"), None);
+        assert_eq!(
+            Field::from_json_path("device.max_power"),
+            Some(&FIELD_USB_ATTR_POWER)
+        );
+        assert_eq!(FIELD_USB_BCD_DEVICE.kind(), FieldKind::Bcd16);
+        assert_eq!(
+            FIELD_USB_MANUFACTURER.kind(),
+            FieldKind::StringUtf16AndAscii(30)
+        );
+    }
+
+    #[test]
+    fn test_get_set() {
+        let mut wls = WhiteLabelStruct::default();
+        for field in FIELDS.iter() {
+            let value = field
+                .default_value()
+                .unwrap_or_else(|| "E0C9125B0D9B".into());
+            assert_eq!(wls.get(field), None);
+            wls.set(field, value.clone()).unwrap();
+            assert_eq!(wls.get(field), Some(value));
+        }
+        assert!(wls.is_clean());
+        assert_eq!(wls.attr_power(), Some(0xfa80));
+        assert_eq!(wls.scsi_vendor().unwrap(), "RPI");
+
+        // Lengths are checked as for the specific setters
+        wls.set(&FIELD_SCSI_VENDOR, "123456789".into()).unwrap();
+        assert_eq!(wls.warnings().len(), 1);
+        assert_eq!(wls.warnings()[0].code(), DiagnosticCode::StringTooLong);
+        wls.clear(&FIELD_SCSI_VENDOR);
+        assert_eq!(wls.get(&FIELD_SCSI_VENDOR), None);
+        assert!(wls.is_clean());
+
+        // Wrong value types are rejected
+        assert!(matches!(
+            wls.set(&FIELD_USB_PRODUCT, 0x1234.into()),
+            Err(Error::FieldTypeMismatch("usb_product"))
+        ));
+        assert!(matches!(
+            wls.set(&FIELD_USB_VENDOR_ID, "0x1234".into()),
+            Err(Error::FieldTypeMismatch("usb_vendor_id"))
+        ));
+    }
+}
@@ -0,0 +1,304 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Chooses where each string is placed after the white label struct.
+//!
+//! Each STRDEF holds an 8-bit offset, so every string must start within 255
+//! rows of the start of the white label struct, although the last may extend
+//! beyond it.  Placing strings in field order, as `picotool` does, can leave
+//! the last string starting beyond this, when a different order would fit.
+//! Strings which are identical to, or a suffix of, another string with the
+//! same encoding can also share its rows.
+
+use alloc::vec::Vec;
+
+use serde::Serialize;
+
+use crate::whitelabel::OtpString;
+use crate::whitelabel::fields::{Field, NUM_FIELDS};
+use crate::whitelabel::strdef::{MAX_STRING_OFFSET, auto_last, best_last};
+use crate::whitelabel::top::NUM_INDEX_ROWS;
+
+/// How a [`WhiteLabelStruct`](crate::WhiteLabelStruct) places its strings in
+/// OTP, after the struct rows.  Set with
+/// [`WhiteLabelStruct::set_layout_policy`](crate::WhiteLabelStruct::set_layout_policy).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutPolicy {
+    /// Strings are placed in field index order, as `picotool` does.  Fails if
+    /// that leaves a string starting beyond offset 255.
+    FieldOrder,
+
+    /// Strings are placed in field index order if that fits, so the rows are
+    /// the same as with [`LayoutPolicy::FieldOrder`].  Otherwise, the longest
+    /// string is moved last.
+    #[default]
+    Auto,
+
+    /// The fewest rows are used.  Strings identical to, or a suffix of,
+    /// another string with the same encoding share its rows, and the longest
+    /// string is placed last.  ASCII strings can only share from a row
+    /// boundary, so the difference in lengths must be even.
+    Compact,
+}
+
+/// Where a single string is placed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PlacedString {
+    field: &'static Field,
+    offset: usize,
+    rows: usize,
+    shared: bool,
+}
+
+impl PlacedString {
+    /// Returns the string's field.
+    pub(crate) fn field(&self) -> &'static Field {
+        self.field
+    }
+
+    /// Returns the string's offset from the start of the white label struct.
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns true if the string uses rows written for another string.
+    pub(crate) fn is_shared(&self) -> bool {
+        self.shared
+    }
+}
+
+/// The placement of every string present.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Layout {
+    // Strings with their own rows, in row order, followed by shared strings.
+    strings: Vec<PlacedString>,
+    row_count: usize,
+}
+
+impl Layout {
+    /// Lays out `strings`, given in field index order, according to `policy`.
+    pub(crate) fn new(strings: &[(&'static Field, &OtpString)], policy: LayoutPolicy) -> Self {
+        let unshared = alloc::vec![None; strings.len()];
+        match policy {
+            LayoutPolicy::FieldOrder => Self::place(strings, &unshared, None),
+            LayoutPolicy::Auto => {
+                // The same choice as heapless encoding makes
+                let mut rows = [None; NUM_FIELDS];
+                for (field, string) in strings {
+                    rows[field.index()] = Some(string.otp_row_count() as usize);
+                }
+                Self::place(strings, &unshared, auto_last(&rows))
+            }
+            LayoutPolicy::Compact => {
+                // The tail of each string with its own rows is those after
+                // the last offset which must be within the limit - its own,
+                // or that of a string it hosts.
+                let hosts = find_hosts(strings);
+                let mut tails = [None; NUM_FIELDS];
+                for (ii, (field, string)) in strings.iter().enumerate() {
+                    if hosts[ii].is_none() {
+                        let hosted = hosts
+                            .iter()
+                            .filter_map(|host| host.filter(|(host, _)| *host == ii))
+                            .map(|(_, offset)| offset)
+                            .max()
+                            .unwrap_or(0);
+                        tails[field.index()] = Some(string.otp_row_count() as usize - hosted);
+                    }
+                }
+                Self::place(strings, &hosts, best_last(&tails))
+            }
+        }
+    }
+
+    // Places the strings, sharing rows with the `hosts` found by
+    // `find_hosts`.  Those with their own rows are placed in field order,
+    // apart from the one with field index `last`, which goes last.
+    fn place(
+        strings: &[(&'static Field, &OtpString)],
+        hosts: &[Option<(usize, usize)>],
+        last: Option<usize>,
+    ) -> Self {
+        let mut owners: Vec<usize> = (0..strings.len())
+            .filter(|&ii| hosts[ii].is_none())
+            .collect();
+        if let Some(pos) = owners
+            .iter()
+            .position(|&ii| Some(strings[ii].0.index()) == last)
+        {
+            let last = owners.remove(pos);
+            owners.push(last);
+        }
+
+        // Place them
+        let mut offsets = alloc::vec![0; strings.len()];
+        let mut layout = Self {
+            strings: Vec::with_capacity(strings.len()),
+            row_count: NUM_INDEX_ROWS,
+        };
+        for &ii in owners.iter() {
+            offsets[ii] = layout.row_count;
+            layout.push(strings[ii], offsets[ii], false);
+            layout.row_count += strings[ii].1.otp_row_count() as usize;
+        }
+        for (ii, host) in hosts.iter().enumerate() {
+            if let Some((host, offset)) = host {
+                layout.push(strings[ii], offsets[*host] + offset, true);
+            }
+        }
+
+        layout
+    }
+
+    fn push(&mut self, string: (&'static Field, &OtpString), offset: usize, shared: bool) {
+        self.strings.push(PlacedString {
+            field: string.0,
+            offset,
+            rows: string.1.otp_row_count() as usize,
+            shared,
+        });
+    }
+
+    /// Returns the placed strings - those with their own rows in row order,
+    /// followed by those sharing rows.
+    pub(crate) fn strings(&self) -> &[PlacedString] {
+        &self.strings
+    }
+
+    /// Returns the total number of rows, including the struct.
+    pub(crate) fn row_count(&self) -> usize {
+        self.row_count
+    }
+
+    /// Returns the string starting furthest from the start of the struct, if
+    /// any.
+    pub(crate) fn last_string(&self) -> Option<&PlacedString> {
+        self.strings.iter().max_by_key(|s| s.offset)
+    }
+
+    /// Returns true if every string starts within [`MAX_STRING_OFFSET`] rows.
+    pub(crate) fn fits(&self) -> bool {
+        self.last_string()
+            .is_none_or(|s| s.offset <= MAX_STRING_OFFSET)
+    }
+
+    /// Returns the number of rows used by the given field's string, which is
+    /// 0 if it shares another string's rows, or None if it isn't present.
+    pub(crate) fn rows_used(&self, field: &Field) -> Option<usize> {
+        self.strings
+            .iter()
+            .find(|s| s.field.index() == field.index())
+            .map(|s| if s.shared { 0 } else { s.rows })
+    }
+}
+
+// Finds each string's host - the string it shares rows with - and the
+// offset within the host's rows.  Longer strings are considered first, so
+// every host is found before the strings it hosts.
+fn find_hosts(strings: &[(&'static Field, &OtpString)]) -> Vec<Option<(usize, usize)>> {
+    let mut hosts: Vec<Option<(usize, usize)>> = alloc::vec![None; strings.len()];
+    let mut by_length: Vec<usize> = (0..strings.len()).collect();
+    by_length.sort_by_key(|&ii| core::cmp::Reverse(strings[ii].1.otp_row_count()));
+    for (pos, &ii) in by_length.iter().enumerate() {
+        hosts[ii] = by_length[..pos]
+            .iter()
+            .filter(|&&host| hosts[host].is_none())
+            .find_map(|&host| {
+                suffix_offset(strings[host].1, strings[ii].1).map(|offset| (host, offset))
+            });
+    }
+    hosts
+}
+
+// Returns the row offset within `host` at which `string` can share its rows,
+// if `string` is a non-empty suffix of `host` with the same encoding, and
+// starts on a row boundary.
+fn suffix_offset(host: &OtpString, string: &OtpString) -> Option<usize> {
+    let (host, string_value) = (host.string(), string.string());
+    if string_value.is_empty()
+        || host.is_ascii() != string_value.is_ascii()
+        || !host.ends_with(string_value.as_str())
+    {
+        return None;
+    }
+    if host.is_ascii() {
+        let diff = host.len() - string_value.len();
+        diff.is_multiple_of(2).then_some(diff / 2)
+    } else {
+        Some(host.encode_utf16().count() - string_value.encode_utf16().count())
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+    use crate::WhiteLabelStruct;
+    use crate::whitelabel::fields::{FIELD_UF2_MODEL, FIELD_USB_PRODUCT};
+
+    #[test]
+    fn test_longest_last() {
+        // In field order, the board ID would start at row 260
+        let mut wls = WhiteLabelStruct::default();
+        wls.set_manufacturer("号".repeat(30)).unwrap();
+        wls.set_product("号".repeat(30)).unwrap();
+        wls.set_serial_number("号".repeat(20)).unwrap();
+        wls.set_redirect_url("a".repeat(100)).unwrap();
+        wls.set_redirect_name("a".repeat(100)).unwrap();
+        wls.set_uf2_model("a".repeat(127)).unwrap();
+        wls.set_uf2_board_id("a".repeat(100)).unwrap();
+
+        wls.set_layout_policy(LayoutPolicy::FieldOrder);
+        assert!(!wls.is_clean());
+        assert!(!wls.layout().fits());
+
+        wls.set_layout_policy(LayoutPolicy::Auto);
+        assert!(wls.is_clean());
+        let layout = wls.layout();
+        let last = layout.last_string().unwrap();
+        assert_eq!(last.field(), &FIELD_UF2_MODEL);
+        assert_eq!(last.offset(), 246);
+        assert_eq!(layout.row_count(), 246 + 64);
+
+        // Round trips
+        let otp = wls.to_otp_data_strict().unwrap();
+        let parsed = WhiteLabelStruct::parse_otp(otp.usb_boot_flags(), otp.rows()).unwrap();
+        assert!(parsed.warnings().is_empty());
+        assert_eq!(parsed.white_label().uf2_model(), wls.uf2_model());
+        assert_eq!(parsed.white_label().product(), wls.product());
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut wls = WhiteLabelStruct::default();
+        wls.set_manufacturer("Raspberry Pi").unwrap();
+        wls.set_product("Pi").unwrap();
+        wls.set_serial_number("号码").unwrap();
+        wls.set_volume_label("RP2350").unwrap();
+        wls.set_scsi_vendor("Pi").unwrap();
+        wls.set_scsi_product("RP2350").unwrap();
+        wls.set_uf2_board_id("RP2350").unwrap();
+        // Odd difference in length, so can't share
+        wls.set_uf2_model("i").unwrap();
+        let rows = wls.otp_row_count();
+        assert_eq!(rows, 16 + 6 + 1 + 2 + 3 + 1 + 3 + 3 + 1);
+
+        wls.set_layout_policy(LayoutPolicy::Compact);
+        let layout = wls.layout();
+        assert_eq!(layout.rows_used(&FIELD_USB_PRODUCT), Some(0));
+        assert_eq!(layout.row_count(), 16 + 6 + 2 + 3 + 1);
+        assert_eq!(layout.strings().iter().filter(|s| s.is_shared()).count(), 4);
+
+        // Round trips
+        let otp = wls.to_otp_data_strict().unwrap();
+        assert_eq!(otp.rows().len(), layout.row_count());
+        let parsed = WhiteLabelStruct::parse_otp(otp.usb_boot_flags(), otp.rows()).unwrap();
+        assert!(parsed.warnings().is_empty());
+        let parsed = parsed.white_label();
+        assert_eq!(parsed.product().unwrap(), "Pi");
+        assert_eq!(parsed.scsi_vendor().unwrap(), "Pi");
+        assert_eq!(parsed.uf2_board_id().unwrap(), "RP2350");
+        assert_eq!(parsed.serial_number().unwrap(), "号码");
+    }
+}
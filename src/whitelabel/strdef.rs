@@ -0,0 +1,108 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Encodes strings, and their STRDEFs, as stored in OTP after the white label
+//! struct.
+//!
+//! Doesn't require `alloc`, so is shared by
+//! [`WhiteLabelStruct`](crate::WhiteLabelStruct) and
+//! [`heapless`](crate::heapless).
+
+use crate::whitelabel::fields::NUM_FIELDS;
+
+/// The maximum offset a string can start at, from the start of the white
+/// label struct.
+pub(crate) const MAX_STRING_OFFSET: usize = 255;
+
+/// Returns the field index of the string to place last, given, for each
+/// field with rows of its own, the number of rows after the last offset
+/// which must be within [`MAX_STRING_OFFSET`].  That is the string with the
+/// most such rows - on a tie, the one with the highest field index.
+pub(crate) fn best_last(tails: &[Option<usize>; NUM_FIELDS]) -> Option<usize> {
+    (0..NUM_FIELDS)
+        .filter_map(|index| tails[index].map(|tail| (index, tail)))
+        .max_by_key(|&(_, tail)| tail)
+        .map(|(index, _)| index)
+}
+
+/// Returns the field index of the string the `Auto` layout policy places
+/// last, given the number of rows each field's string uses.  That is the
+/// last string in field order, if it starts within [`MAX_STRING_OFFSET`]
+/// rows, and otherwise the one chosen by [`best_last`].  The string returned
+/// may still start beyond the limit.
+pub(crate) fn auto_last(rows: &[Option<usize>; NUM_FIELDS]) -> Option<usize> {
+    let last = (0..NUM_FIELDS).rfind(|&index| rows[index].is_some())?;
+    if last_offset(rows, last) <= MAX_STRING_OFFSET {
+        Some(last)
+    } else {
+        best_last(rows)
+    }
+}
+
+/// Returns the offset of field `last`'s string, if it is placed after every
+/// other string in `rows`, none of which share rows.
+pub(crate) fn last_offset(rows: &[Option<usize>; NUM_FIELDS], last: usize) -> usize {
+    // The struct has a row for each field
+    let total: usize = rows.iter().flatten().sum();
+    NUM_FIELDS + total - rows[last].unwrap_or(0)
+}
+
+/// Returns the STRDEF character count, the number of rows and whether the
+/// string is stored as UTF-16, for `string`.  Non-ASCII strings are stored
+/// as UTF-16, and each UTF-16 code unit counts as a character.
+pub(crate) fn string_layout(string: &str) -> (usize, usize, bool) {
+    if string.is_ascii() {
+        (string.len(), string.len().div_ceil(2), false)
+    } else {
+        let units = string.encode_utf16().count();
+        (units, units, true)
+    }
+}
+
+/// Encodes `string` into `rows`, which must be exactly the number of rows
+/// returned by [`string_layout`].  ASCII strings are packed two characters
+/// per row, with the first character in the low byte, and any unused byte
+/// zero.
+pub(crate) fn encode_string(string: &str, rows: &mut [u16]) {
+    if string.is_ascii() {
+        for (row, pair) in rows.iter_mut().zip(string.as_bytes().chunks(2)) {
+            *row = pair[0] as u16 | (pair.get(1).copied().unwrap_or(0) as u16) << 8;
+        }
+    } else {
+        for (row, unit) in rows.iter_mut().zip(string.encode_utf16()) {
+            *row = unit;
+        }
+    }
+}
+
+/// Returns a STRDEF - the character count in bits 6:0, bit 7 set for UTF-16
+/// and the offset of the string in the high byte.
+pub(crate) fn strdef(char_count: u8, is_utf16: bool, offset: u8) -> u16 {
+    (char_count as u16 & 0x7f) | if is_utf16 { 0x80 } else { 0 } | (offset as u16) << 8
+}
+
+/// Returns the offset encoded within a STRDEF.
+pub(crate) fn strdef_offset(strdef: u16) -> u8 {
+    (strdef >> 8) as u8
+}
+
+/// Returns the character count encoded within a STRDEF.
+pub(crate) fn strdef_char_count(strdef: u16) -> u8 {
+    (strdef & 0x7f) as u8
+}
+
+/// Returns true if the STRDEF indicates a UTF-16 string.
+pub(crate) fn strdef_is_utf16(strdef: u16) -> bool {
+    strdef & 0x80 != 0
+}
+
+/// Returns the number of rows occupied by the string the STRDEF defines.
+pub(crate) fn strdef_row_count(strdef: u16) -> u8 {
+    let char_count = strdef_char_count(strdef);
+    if strdef_is_utf16(strdef) {
+        char_count
+    } else {
+        char_count.div_ceil(2)
+    }
+}
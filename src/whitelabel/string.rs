@@ -6,10 +6,12 @@
 
 use alloc::format;
 use alloc::string::{String, ToString};
+use alloc::vec;
 use alloc::vec::Vec;
 
-use crate::whitelabel::Error;
 use crate::whitelabel::fields::{Field, MAX_STRING_LENGTH, NUM_FIELDS};
+use crate::whitelabel::strdef;
+use crate::whitelabel::{Diagnostic, DiagnosticCode, Error};
 
 /// Represents a string to be stored in OTP, using the STRDEF encoding defined
 /// in the RP2350 datasheet at OTP_DATA:USB_WHITE_LABEL_ADDR Register, section
@@ -25,9 +27,9 @@ pub(crate) struct OtpString {
     string: String,
 }
 
-impl ToString for OtpString {
-    fn to_string(&self) -> String {
-        self.string.clone()
+impl core::fmt::Display for OtpString {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.string)
     }
 }
 
@@ -54,11 +56,14 @@ impl TryFrom<String> for OtpString {
 impl OtpString {
     // STRDEF uses 7 bits to represent length
     const MAX_LEN: u8 = MAX_STRING_LENGTH as u8;
-    const MAX_ROWS: u8 = 64;
 
-    /// Creates a new OtpString from the given string.
+    /// Creates a new OtpString from the given string, which must fit in a
+    /// STRDEF - at most 127 ASCII characters or UTF-16 code units.
     pub fn new(string: String) -> Self {
-        assert!(string.len() <= Self::MAX_LEN as usize, "String is too long");
+        assert!(
+            strdef::string_layout(&string).0 <= Self::MAX_LEN as usize,
+            "String is too long"
+        );
         Self { string }
     }
 
@@ -77,65 +82,30 @@ impl OtpString {
         &self.string
     }
 
-    pub(crate) fn from_pre_validated_string(string: &String) -> Self {
-        Self::try_from(string.as_str()).expect("internal error - string validation failed")
+    #[cfg(feature = "serde_json")]
+    pub(crate) fn from_pre_validated_string(string: &str) -> Self {
+        Self::try_from(string).expect("internal error - string validation failed")
     }
 
     /// Returns the string encoded as a `Vec<u16>` suitable for writing to OTP.
     /// ASCII strings are packed two characters per u16, with the first
     /// character in the low byte.  Non-ASCII strings are encoded as UTF-16.
     pub fn to_otp_rows(&self) -> Vec<u16> {
-        let otp_row_count = self.otp_row_count();
-
-        if self.string.is_ascii() {
-            // Return pairs of bytes as u16, with the first character in the
-            // low byte.  This ends up first "on the wire" and first in OTP
-            // memory, which is what the bootrom does:
-            // dest[i * 2] = (uint8_t)tmp;
-            // dest[i * 2 + 1] = (uint8_t)(tmp >> 8);
-            let char_count = self.string.len();
-            let ascii_vec_len = otp_row_count;
-            let mut ascii_vec = Vec::with_capacity(ascii_vec_len as usize);
-            let bytes = self.string.as_bytes();
-            let mut i = 0;
-            while i < char_count {
-                let low = bytes[i] as u16;
-                let high = if i + 1 < char_count {
-                    bytes[i + 1] as u16
-                } else {
-                    0
-                };
-                ascii_vec.push(low | (high << 8));
-                i += 2;
-            }
-            ascii_vec
-        } else {
-            // UTF-16 encoding for non-ascii strings
-            self.string.encode_utf16().collect()
-        }
+        // The first character goes in the low byte.  This ends up first "on
+        // the wire" and first in OTP memory, which is what the bootrom does:
+        // dest[i * 2] = (uint8_t)tmp;
+        // dest[i * 2 + 1] = (uint8_t)(tmp >> 8);
+        let mut rows = vec![0; self.otp_row_count() as usize];
+        strdef::encode_string(&self.string, &mut rows);
+        rows
     }
 
     /// Returns the 16-bit STRDEF representation of this string.
     ///
     /// `offset` is the offset from the location of the white label data
-    /// structure to the first free row, which is where this string will
-    /// be stored.  This is modified to point to the next free row after this
-    /// string.
-    pub fn to_strdef(&self, offset: &mut u8) -> u16 {
-        let is_utf16 = !self.is_ascii();
-
-        let len = self.char_count() as u16;
-        let low_byte = (len & 0x7F) | if is_utf16 { 0x80 } else { 0x00 };
-
-        let old_offset = *offset;
-        let high_byte = (old_offset as u16) << 8;
-
-        // Update data_offset to point to the next free row after this string
-        *offset = old_offset
-            .checked_add(self.otp_row_count() as u8)
-            .expect("internal error - offset overflow");
-
-        low_byte | high_byte
+    /// structure to the row where this string is stored.
+    pub fn to_strdef(&self, offset: u8) -> u16 {
+        strdef::strdef(self.char_count(), self.is_utf16(), offset)
     }
 
     /// Returns the number of characters in this string, as defined by STRDEF.
@@ -156,7 +126,7 @@ impl OtpString {
 
     /// Returns the number of rows this string will occupy in OTP
     pub fn otp_row_count(&self) -> u8 {
-        let row_count = if self.is_ascii() {
+        if self.is_ascii() {
             // ASCII strings are packed 2 characters per row
             self.char_count().div_ceil(2)
         } else {
@@ -167,47 +137,28 @@ impl OtpString {
                 "UTF-16 string length exceeds maximum of 127 characters"
             );
             utf_count as u8
-        };
-
-        assert!(
-            row_count <= Self::MAX_ROWS,
-            "Row count exceeds maximum of 64"
-        );
-        row_count
+        }
     }
 
     /// Returns the offset encoded within a STRDEF row.
     pub(crate) fn offset_from_row(row: u16) -> u8 {
-        // MSB encodes the offset
-        ((row & 0xFF00) >> 8) as u8
+        strdef::strdef_offset(row)
     }
 
     /// Returns the character count encoded within a STRDEF row.
     pub(crate) fn char_count_from_row(row: u16) -> u8 {
-        // LSB bits 0-6 encode the character count
-        (row & 0x007F) as u8
-    }
-
-    /// Returns true if the STRDEF row indicates a UTF-16 string.
-    pub(crate) fn is_ascii_from_row(row: u16) -> bool {
-        // LSB bit 7 indicates UTF-16 if set
-        (row & 0x0080) == 0
+        strdef::strdef_char_count(row)
     }
 
     /// Returns true if the STRDEF row indicates a UTF-16 string.
     pub(crate) fn is_utf16_from_row(row: u16) -> bool {
-        !Self::is_ascii_from_row(row)
+        strdef::strdef_is_utf16(row)
     }
 
     /// Returns the total amount of OTP rows occupied by the string defined
     /// in the STRDEF row.
     pub(crate) fn row_count_from_strdef(row: u16) -> u8 {
-        let char_count = Self::char_count_from_row(row);
-        if Self::is_utf16_from_row(row) {
-            char_count
-        } else {
-            char_count.div_ceil(2)
-        }
+        strdef::strdef_row_count(row)
     }
 
     /// Extracts an OtpString field from the provided OTP rows at the specified
@@ -216,8 +167,8 @@ impl OtpString {
     /// Returns `Ok(None)` if the boot flag bit is clear (indicating no string
     /// is present).
     ///
-    /// Returns `Err` for hard errors that prevent parsing - primarily if the
-    /// field name is invalid, or if the field does not support strings.
+    /// Returns `Err` for hard errors that prevent parsing - if the field does
+    /// not support strings.  No OTP data causes an `Err`.
     ///
     /// Adds warnings for unusual and suspicious but at least partially
     /// parseable conditions.
@@ -226,9 +177,11 @@ impl OtpString {
         usb_boot_flags: u16,
         field: &Field,
         utf16_allowed: bool,
-        warnings: &mut Vec<String>,
+        warnings: &mut Vec<Diagnostic>,
     ) -> Result<Option<Self>, Error> {
-        let max_string_len = field.max_length().expect("called with non-string field");
+        let max_string_len = field.max_length().ok_or_else(|| {
+            Error::InternalInconsistency(format!("{} is not a string field", field.name()))
+        })?;
         let field_name = field.name();
         let field_index = field.index();
 
@@ -240,9 +193,13 @@ impl OtpString {
             // valid - if the user has written the OTP data for this row, but
             // not yet set the boot flag bit, so the bootloader doesn't use it.
             if strdef != 0 {
-                warnings.push(format!(
-                    "{}: boot flag bit {} clear but row contains non-zero STRDEF 0x{:04X}",
-                    field_name, field_index, strdef
+                warnings.push(Diagnostic::for_field(
+                    DiagnosticCode::FieldNotEnabled,
+                    field,
+                    format!(
+                        "{}: boot flag bit {} clear but row contains non-zero STRDEF 0x{:04X}",
+                        field_name, field_index, strdef
+                    ),
                 ));
             }
             return Ok(None);
@@ -250,9 +207,13 @@ impl OtpString {
 
         // Boot flag bit is set - we should have a valid string
         if strdef == 0 {
-            warnings.push(format!(
-                "{}: boot flag bit {} set but STRDEF is zero",
-                field_name, field_index
+            warnings.push(Diagnostic::for_field(
+                DiagnosticCode::EmptyStrdef,
+                field,
+                format!(
+                    "{}: boot flag bit {} set but STRDEF is zero",
+                    field_name, field_index
+                ),
             ));
             return Ok(None);
         }
@@ -264,22 +225,17 @@ impl OtpString {
 
         // Validate offset is within bounds and after struct fields
         if offset < NUM_FIELDS {
-            warnings.push(format!(
-                "{}: string offset {} is less than {} (must be after struct fields)",
-                field_name, offset, NUM_FIELDS
+            warnings.push(Diagnostic::for_field(
+                DiagnosticCode::StringOffsetInStruct,
+                field,
+                format!(
+                    "{}: string offset {} is less than {} (must be after struct fields)",
+                    field_name, offset, NUM_FIELDS
+                ),
             ));
             return Ok(None);
         }
 
-        // Validate UTF-16 usage if not allowed
-        if is_utf16 && !utf16_allowed {
-            warnings.push(format!(
-                "{}: UTF-16 string not allowed but STRDEF indicates UTF-16 encoding",
-                field_name
-            ));
-            // We will continue to parse in this instance.
-        }
-
         // Calculate how many rows the string occupies
         let row_count = if is_utf16 {
             // UTF-16 strings use 1 row per character
@@ -288,16 +244,37 @@ impl OtpString {
             // ASCII strings are packed 2 characters per row
             char_count.div_ceil(2)
         };
+        let end_offset = offset + row_count;
+
+        // Returns a diagnostic involving the STRDEF and the string's rows.
+        let diagnostic = |code, message| {
+            Diagnostic::for_field(code, field, message)
+                .with_rows(offset..end_offset.min(rows.len()))
+        };
+
+        // Validate UTF-16 usage if not allowed
+        if is_utf16 && !utf16_allowed {
+            warnings.push(diagnostic(
+                DiagnosticCode::Utf16NotAllowed,
+                format!(
+                    "{}: UTF-16 string not allowed but STRDEF indicates UTF-16 encoding",
+                    field_name
+                ),
+            ));
+            // We will continue to parse in this instance.
+        }
 
         // Validate we have enough rows
-        let end_offset = offset + row_count;
         if end_offset > rows.len() {
-            warnings.push(format!(
-                "{}: string at offset {} with {} rows exceeds available {} rows",
-                field_name,
-                offset,
-                row_count,
-                rows.len()
+            warnings.push(diagnostic(
+                DiagnosticCode::StringOutOfBounds,
+                format!(
+                    "{}: string at offset {} with {} rows exceeds available {} rows",
+                    field_name,
+                    offset,
+                    row_count,
+                    rows.len()
+                ),
             ));
             return Ok(None);
         }
@@ -311,9 +288,12 @@ impl OtpString {
             match String::from_utf16(string_rows) {
                 Ok(s) => s,
                 Err(e) => {
-                    warnings.push(format!(
-                        "{}: invalid UTF-16 data at offset {}: {}",
-                        field_name, offset, e
+                    warnings.push(diagnostic(
+                        DiagnosticCode::InvalidUtf16,
+                        format!(
+                            "{}: invalid UTF-16 data at offset {}: {}",
+                            field_name, offset, e
+                        ),
                     ));
                     return Ok(None);
                 }
@@ -332,9 +312,12 @@ impl OtpString {
             if bytes.len() > char_count {
                 let padding = &bytes[char_count..];
                 if padding.iter().any(|&b| b != 0) {
-                    warnings.push(format!(
-                        "{}: non-zero padding bytes found after declared length {}",
-                        field_name, char_count
+                    warnings.push(diagnostic(
+                        DiagnosticCode::NonZeroPadding,
+                        format!(
+                            "{}: non-zero padding bytes found after declared length {}",
+                            field_name, char_count
+                        ),
                     ));
                 }
             }
@@ -342,12 +325,28 @@ impl OtpString {
             // Trim to exact character count (removes padding zeros)
             bytes.truncate(char_count);
 
+            // Valid UTF-8 isn't enough - non-ASCII strings are stored as
+            // UTF-16, so couldn't be represented.
+            if !bytes.is_ascii() {
+                warnings.push(diagnostic(
+                    DiagnosticCode::NonAsciiString,
+                    format!(
+                        "{}: non-ASCII data in ASCII string at offset {}",
+                        field_name, offset
+                    ),
+                ));
+                return Ok(None);
+            }
+
             match String::from_utf8(bytes) {
                 Ok(s) => s,
                 Err(e) => {
-                    warnings.push(format!(
-                        "{}: invalid ASCII/UTF-8 data at offset {}: {}",
-                        field_name, offset, e
+                    warnings.push(diagnostic(
+                        DiagnosticCode::NonAsciiString,
+                        format!(
+                            "{}: invalid ASCII/UTF-8 data at offset {}: {}",
+                            field_name, offset, e
+                        ),
                     ));
                     return Ok(None);
                 }
@@ -356,28 +355,38 @@ impl OtpString {
 
         // Check length
         if string.chars().count() > max_string_len {
-            warnings.push(format!(
-                "{}: extracted string length {} exceeds maximum of {}",
-                field_name,
-                string.chars().count(),
-                max_string_len
+            warnings.push(diagnostic(
+                DiagnosticCode::StringTooLong,
+                format!(
+                    "{}: extracted string length {} exceeds maximum of {}",
+                    field_name,
+                    string.chars().count(),
+                    max_string_len
+                ),
             ));
         }
 
         let otp_string = OtpString::new(string);
 
-        // Sanity check: validate the extracted string matches the STRDEF
-        // These are asserts because if they fail, our parsing logic is wrong.
+        // A UTF-16 string containing only ASCII characters is valid, but
+        // would be stored as ASCII if written back.
+        if otp_string.is_utf16() != is_utf16 {
+            warnings.push(diagnostic(
+                DiagnosticCode::Utf16AsciiOnly,
+                format!(
+                    "{}: ASCII only string stored as UTF-16 at offset {}",
+                    field_name, offset
+                ),
+            ));
+        }
+
+        // Sanity check: validate the extracted string matches the STRDEF.
+        // This is an assert because if it fails, our parsing logic is wrong.
         assert_eq!(
             otp_string.char_count() as usize,
             char_count,
             "Extracted string character count does not match STRDEF"
         );
-        assert_eq!(
-            otp_string.is_utf16(),
-            is_utf16,
-            "Extracted string encoding does not match STRDEF"
-        );
 
         Ok(Some(otp_string))
     }
@@ -416,7 +425,7 @@ mod tests {
         let otp_rows = s.to_otp_rows();
         assert_eq!(otp_rows, vec![0x0068, 0x00e9, 0x006c, 0x006c, 0x006f]);
 
-        let strdef = s.to_strdef(&mut 10);
+        let strdef = s.to_strdef(10);
         assert_eq!(strdef, 0x0A85); // offset 10, length 5, UTF-16 flag set
     }
 
@@ -425,7 +434,7 @@ mod tests {
         let s = OtpString::try_from("ABCD").unwrap();
         let rows = s.to_otp_rows();
         assert_eq!(rows, vec![0x4241, 0x4443]); // 'A' 'B', 'C' 'D'
-        let strdef = s.to_strdef(&mut 5);
+        let strdef = s.to_strdef(5);
         assert_eq!(strdef, 0x0504); // offset 5, length
         let otp_row_count = s.otp_row_count();
         assert_eq!(otp_row_count, 2);
@@ -433,7 +442,7 @@ mod tests {
         let s = OtpString::try_from("abcde").unwrap();
         let rows = s.to_otp_rows();
         assert_eq!(rows, vec![0x6261, 0x6463, 0x0065]); // 'a' 'b', 'c' 'd', 'e' 0
-        let strdef = s.to_strdef(&mut 3);
+        let strdef = s.to_strdef(3);
         assert_eq!(strdef, 0x0305); // offset 3, length
         let otp_row_count = s.otp_row_count();
         assert_eq!(otp_row_count, 3);
@@ -444,7 +453,7 @@ mod tests {
         let s = OtpString::try_from("héllo").unwrap();
         let rows = s.to_otp_rows();
         assert_eq!(rows, vec![0x0068, 0x00e9, 0x006c, 0x006c, 0x006f]);
-        let strdef = s.to_strdef(&mut 8);
+        let strdef = s.to_strdef(8);
         assert_eq!(strdef, 0x0805 | 0x0080);
         let char_count = s.char_count();
         assert_eq!(char_count, 5);
@@ -458,7 +467,7 @@ mod tests {
             rows,
             vec![0x0068, 0x0065, 0x006c, 0x006c, 0x006f, 0xd83d, 0xde00]
         );
-        let strdef = s.to_strdef(&mut 12);
+        let strdef = s.to_strdef(12);
         assert_eq!(strdef, 0x0C07 | 0x0080); // 7 rows (including surrogate pair)
         let char_count = s.char_count();
         assert_eq!(char_count, 7); // 7 characters as surrogate pair counts double
@@ -9,47 +9,45 @@ use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec;
 use alloc::vec::Vec;
+#[cfg(feature = "serde_json")]
 use core::str::FromStr;
 
+use crate::flags::UsbBootFlags;
+#[cfg(feature = "serde_json")]
+use crate::whitelabel::auto::error::ConversionError;
+#[cfg(feature = "serde_json")]
 use crate::whitelabel::auto::{
-    WhiteLabellingDeviceAttributes, WhiteLabellingDeviceManufacturer, WhiteLabellingDeviceMaxPower,
-    WhiteLabellingDeviceProduct, WhiteLabellingDeviceSerialNumber,
-};
-use crate::whitelabel::auto::{
-    WhiteLabellingScsiProduct, WhiteLabellingScsiVendor, WhiteLabellingScsiVersion,
-};
-use crate::whitelabel::auto::{
-    WhiteLabellingVolumeBoardId, WhiteLabellingVolumeLabel, WhiteLabellingVolumeModel,
-    WhiteLabellingVolumeRedirectName, WhiteLabellingVolumeRedirectUrl,
+    WhiteLabelling, WhiteLabellingDevice, WhiteLabellingDeviceAttributes,
+    WhiteLabellingDeviceMaxPower, WhiteLabellingScsi, WhiteLabellingVolume,
 };
+use crate::whitelabel::diagnostic;
 use crate::whitelabel::fields::{
     FIELD_REDIRECT_NAME, FIELD_REDIRECT_URL, FIELD_SCSI_PRODUCT, FIELD_SCSI_VENDOR,
     FIELD_SCSI_VERSION, FIELD_UF2_BOARD_ID, FIELD_UF2_MODEL, FIELD_USB_ATTR_POWER,
     FIELD_USB_BCD_DEVICE, FIELD_USB_LANGUAGE_ID, FIELD_USB_MANUFACTURER, FIELD_USB_PRODUCT,
     FIELD_USB_PRODUCT_ID, FIELD_USB_SERIAL_NUMBER, FIELD_USB_VENDOR_ID, FIELD_VOLUME_LABEL, FIELDS,
-    Field,
-};
-use crate::whitelabel::{
-    Error, OtpData, OtpString, WhiteLabelling, WhiteLabellingDevice, WhiteLabellingScsi,
-    WhiteLabellingVolume,
+    Field, FieldValue,
 };
+use crate::whitelabel::layout::Layout;
+use crate::whitelabel::strdef::MAX_STRING_OFFSET;
+use crate::whitelabel::{Diagnostic, DiagnosticCode, Error, LayoutPolicy, OtpData, OtpString};
 
 // Number of rows in the white label struct that are u16 fields.
 const NUM_U16_ROWS: usize = 5;
 // Indices of the u16 rows in the white label struct.
 const U16_ROWS: [usize; NUM_U16_ROWS] = [0, 1, 2, 3, 7];
 // Number of rows in the white label struct that are STRDEF pointers.
-const NUM_STRDEF_ROWS: usize = 11;
+pub(crate) const NUM_STRDEF_ROWS: usize = 11;
 // Indices of the STRDEF rows in the white label struct.
-const STRDEF_ROWS: [usize; NUM_STRDEF_ROWS] = [4, 5, 6, 8, 9, 10, 11, 12, 13, 14, 15];
+pub(crate) const STRDEF_ROWS: [usize; NUM_STRDEF_ROWS] = [4, 5, 6, 8, 9, 10, 11, 12, 13, 14, 15];
 // Total number of rows in the white label struct.
-const NUM_INDEX_ROWS: usize = NUM_U16_ROWS + NUM_STRDEF_ROWS;
+pub(crate) const NUM_INDEX_ROWS: usize = NUM_U16_ROWS + NUM_STRDEF_ROWS;
 // White label address value valid bit index within the USB_BOOT_FLAGS
 pub(crate) const WHITE_LABEL_ADDR_VALID_BIT_NUM: usize = 22;
 // DP/DM Swap bit index within the USB_BOOT_FLAGS
 const DP_DM_SWAP_BIT_NUM: usize = 23;
-// Total number of rows the RP2350's OTP memory
-pub(crate) const TOTAL_OTP_ROWS: usize = 4096;
+/// Total number of rows in the RP2350's OTP memory
+pub const TOTAL_OTP_ROWS: usize = 4096;
 
 /// OTP row index for USB_BOOT_FLAGS
 pub const OTP_ROW_USB_BOOT_FLAGS: u16 = 0x059;
@@ -77,8 +75,9 @@ pub const WHITE_LABEL_SCHEMA_URL: &str =
 
 /// Represents the USB white label structure stored in OTP.
 ///
-/// Use [`WhiteLabelStruct::from_json`] to create an instance from a JSON
-/// whitelabel representation, or use [`From<OtpData>::from`].
+/// Use `WhiteLabelStruct::from_json`, with the `serde_json` feature, to create
+/// an instance from a JSON whitelabel representation, or use
+/// [`From<OtpData>::from`].
 ///
 /// To create an empty instance, use [`WhiteLabelStruct::default`] and set
 /// fields as required.
@@ -103,11 +102,35 @@ pub struct WhiteLabelStruct {
     redirect_name: Option<OtpString>,
     uf2_model: Option<OtpString>,
     uf2_board_id: Option<OtpString>,
-    warnings: Vec<String>,
+    layout_policy: LayoutPolicy,
+    warnings: Vec<Diagnostic>,
 }
 
-impl From<WhiteLabelStruct> for WhiteLabellingDevice {
-    fn from(wls: WhiteLabelStruct) -> Self {
+// Converts an optional OtpString to the schema type for the field, which
+// checks its length.  Strings parsed from OTP may be longer than the schema
+// allows, so this can fail.
+#[cfg(feature = "serde_json")]
+fn schema_string<T>(field: &Field, string: Option<&OtpString>) -> Result<Option<T>, Error>
+where
+    T: FromStr<Err = ConversionError>,
+{
+    string
+        .map(|s| {
+            T::from_str(s.string()).map_err(|e| {
+                Error::InvalidWhiteLabelData(format!(
+                    "Field '{}' cannot be represented in JSON: {e}",
+                    field.name()
+                ))
+            })
+        })
+        .transpose()
+}
+
+#[cfg(feature = "serde_json")]
+impl TryFrom<&WhiteLabelStruct> for WhiteLabellingDevice {
+    type Error = Error;
+
+    fn try_from(wls: &WhiteLabelStruct) -> Result<Self, Error> {
         let mut device = WhiteLabellingDevice::default();
 
         let vid = wls.vendor_id.as_ref().map(|v| format!("{:#06x}", v));
@@ -120,17 +143,9 @@ impl From<WhiteLabelStruct> for WhiteLabellingDevice {
             integer as f64 + (tenths as f64 / 10.0) + (hundredths as f64 / 100.0)
         });
         let lang_id = wls.language_id.as_ref().map(|v| format!("{:#06x}", v));
-        let manufacturer = wls.manufacturer.as_ref().map(|s| {
-            WhiteLabellingDeviceManufacturer::from_str(&s.to_string())
-                .expect("Invalid manufacturer string")
-        });
-        let product = wls.product.as_ref().map(|s| {
-            WhiteLabellingDeviceProduct::from_str(&s.to_string()).expect("Invalid product string")
-        });
-        let serial_number = wls.serial_number.as_ref().map(|s| {
-            WhiteLabellingDeviceSerialNumber::from_str(&s.to_string())
-                .expect("Invalid serial number string")
-        });
+        let manufacturer = schema_string(&FIELD_USB_MANUFACTURER, wls.manufacturer.as_ref())?;
+        let product = schema_string(&FIELD_USB_PRODUCT, wls.product.as_ref())?;
+        let serial_number = schema_string(&FIELD_USB_SERIAL_NUMBER, wls.serial_number.as_ref())?;
         let attributes = wls.attr_power.as_ref().map(|v| {
             WhiteLabellingDeviceAttributes::String(format!("{:#04x}", ((*v & 0xFF) as u8)))
         });
@@ -148,75 +163,49 @@ impl From<WhiteLabelStruct> for WhiteLabellingDevice {
         device.attributes = attributes;
         device.max_power = max_power;
 
-        device
+        Ok(device)
     }
 }
 
-impl From<WhiteLabelStruct> for WhiteLabellingScsi {
-    fn from(wls: WhiteLabelStruct) -> Self {
-        let mut scsi = WhiteLabellingScsi::default();
-
-        let vendor = wls.scsi_vendor.as_ref().map(|s| {
-            WhiteLabellingScsiVendor::from_str(&s.to_string()).expect("Invalid SCSI vendor string")
-        });
-        let product = wls.scsi_product.as_ref().map(|s| {
-            WhiteLabellingScsiProduct::from_str(&s.to_string())
-                .expect("Invalid SCSI product string")
-        });
-        let version = wls.scsi_version.as_ref().map(|s| {
-            WhiteLabellingScsiVersion::from_str(&s.to_string())
-                .expect("Invalid SCSI version string")
-        });
-
-        scsi.vendor = vendor;
-        scsi.product = product;
-        scsi.version = version;
+#[cfg(feature = "serde_json")]
+impl TryFrom<&WhiteLabelStruct> for WhiteLabellingScsi {
+    type Error = Error;
 
-        scsi
+    fn try_from(wls: &WhiteLabelStruct) -> Result<Self, Error> {
+        Ok(WhiteLabellingScsi {
+            vendor: schema_string(&FIELD_SCSI_VENDOR, wls.scsi_vendor.as_ref())?,
+            product: schema_string(&FIELD_SCSI_PRODUCT, wls.scsi_product.as_ref())?,
+            version: schema_string(&FIELD_SCSI_VERSION, wls.scsi_version.as_ref())?,
+        })
     }
 }
 
-impl From<WhiteLabelStruct> for WhiteLabellingVolume {
-    fn from(wls: WhiteLabelStruct) -> Self {
-        let mut volume = WhiteLabellingVolume::default();
-
-        let label = wls.volume_label.as_ref().map(|s| {
-            WhiteLabellingVolumeLabel::from_str(&s.to_string())
-                .expect("Invalid volume label string")
-        });
-        let model = wls.uf2_model.as_ref().map(|s| {
-            WhiteLabellingVolumeModel::from_str(&s.to_string()).expect("Invalid UF2 model string")
-        });
-        let board_id = wls.uf2_board_id.as_ref().map(|s| {
-            WhiteLabellingVolumeBoardId::from_str(&s.to_string())
-                .expect("Invalid UF2 board ID string")
-        });
-        let redirect_name = wls.redirect_name.as_ref().map(|s| {
-            WhiteLabellingVolumeRedirectName::from_str(&s.to_string())
-                .expect("Invalid redirect name string")
-        });
-        let redirect_url = wls.redirect_url.as_ref().map(|s| {
-            WhiteLabellingVolumeRedirectUrl::from_str(&s.to_string())
-                .expect("Invalid redirect URL string")
-        });
-
-        volume.label = label;
-        volume.model = model;
-        volume.board_id = board_id;
-        volume.redirect_name = redirect_name;
-        volume.redirect_url = redirect_url;
+#[cfg(feature = "serde_json")]
+impl TryFrom<&WhiteLabelStruct> for WhiteLabellingVolume {
+    type Error = Error;
 
-        volume
+    fn try_from(wls: &WhiteLabelStruct) -> Result<Self, Error> {
+        Ok(WhiteLabellingVolume {
+            label: schema_string(&FIELD_VOLUME_LABEL, wls.volume_label.as_ref())?,
+            model: schema_string(&FIELD_UF2_MODEL, wls.uf2_model.as_ref())?,
+            board_id: schema_string(&FIELD_UF2_BOARD_ID, wls.uf2_board_id.as_ref())?,
+            redirect_name: schema_string(&FIELD_REDIRECT_NAME, wls.redirect_name.as_ref())?,
+            redirect_url: schema_string(&FIELD_REDIRECT_URL, wls.redirect_url.as_ref())?,
+        })
     }
 }
 
-impl From<WhiteLabelStruct> for WhiteLabelling {
-    fn from(wls: WhiteLabelStruct) -> Self {
-        let mut wl = WhiteLabelling::default();
+#[cfg(feature = "serde_json")]
+impl TryFrom<&WhiteLabelStruct> for WhiteLabelling {
+    type Error = Error;
 
-        wl.schema = Some(serde_json::Value::String(
-            WHITE_LABEL_SCHEMA_URL.to_string(),
-        ));
+    fn try_from(wls: &WhiteLabelStruct) -> Result<Self, Error> {
+        let mut wl = WhiteLabelling {
+            schema: Some(serde_json::Value::String(
+                WHITE_LABEL_SCHEMA_URL.to_string(),
+            )),
+            ..Default::default()
+        };
 
         // Only create device if any device fields are present
         if wls.vendor_id.is_some()
@@ -228,12 +217,12 @@ impl From<WhiteLabelStruct> for WhiteLabelling {
             || wls.serial_number.is_some()
             || wls.attr_power.is_some()
         {
-            wl.device = Some(WhiteLabellingDevice::from(wls.clone()));
+            wl.device = Some(WhiteLabellingDevice::try_from(wls)?);
         }
 
         // Only create scsi if any scsi fields are present
         if wls.scsi_vendor.is_some() || wls.scsi_product.is_some() || wls.scsi_version.is_some() {
-            wl.scsi = Some(WhiteLabellingScsi::from(wls.clone()));
+            wl.scsi = Some(WhiteLabellingScsi::try_from(wls)?);
         }
 
         // Only create volume if any volume fields are present
@@ -243,12 +232,12 @@ impl From<WhiteLabelStruct> for WhiteLabelling {
             || wls.redirect_name.is_some()
             || wls.redirect_url.is_some()
         {
-            wl.volume = Some(WhiteLabellingVolume::from(wls.clone()));
+            wl.volume = Some(WhiteLabellingVolume::try_from(wls)?);
         } else {
             wl.volume = None;
         }
 
-        wl
+        Ok(wl)
     }
 }
 
@@ -262,7 +251,7 @@ impl TryFrom<&OtpData> for WhiteLabelStruct {
             Ok(result.white_label().clone())
         } else {
             if otp_data.strict() {
-                Err(Error::OtpDataError(result.warnings().join("\n")))
+                Err(Error::OtpDataError(diagnostic::join(result.warnings())))
             } else {
                 let mut wl = result.white_label().clone();
                 wl.warnings = result.warnings().clone();
@@ -273,33 +262,58 @@ impl TryFrom<&OtpData> for WhiteLabelStruct {
 }
 
 impl WhiteLabelStruct {
+    #[cfg(feature = "serde_json")]
     /// Creates a WhiteLabelStruct from a JSON WhiteLabelling
     /// representation.
     ///
-    /// Returns `Self` or a `serde_json::Error` if parsing fails.
+    /// Returns `Self`, `Err(Error::Json)` if parsing fails, or
+    /// `Err(Error::InvalidWhiteLabelData)` if the data is valid JSON but
+    /// cannot be stored in OTP - for example if the strings need more than
+    /// 255 rows.
     ///
     /// To create a WhiteLabelStruct from the values directly, use
     /// [`Self::default`] to create an empty instnace, and set the fields as
     /// required.
     pub fn from_json(json: &str) -> Result<Self, Error> {
-        let wl = WhiteLabelling::from_json(json)?;
-
-        let wls = Self::from_white_labelling(wl);
-
-        // It should be impossible for strict OTP data generation to fail here,
-        // as `from_json` should return an Error if there are any issues.
-        // Therefore, we generate the OTP data in strict mode, and check that
-        // succeeds.
-        if let Err(e) = wls.to_otp_data_strict() {
-            panic!("Internal inconsistency generating OTP data: {e}");
-        }
+        Self::from_json_with_layout(json, LayoutPolicy::default())
+    }
 
+    #[cfg(feature = "serde_json")]
+    /// As [`Self::from_json`], placing strings according to `policy`, which
+    /// determines whether the strings fit in OTP.
+    pub fn from_json_with_layout(json: &str, policy: LayoutPolicy) -> Result<Self, Error> {
+        let wl = WhiteLabelling::from_json(json)?;
+        Self::from_white_labelling(wl, policy)
+    }
+
+    #[cfg(feature = "serde_json")]
+    /// As [`Self::from_json`], but following `picotool`'s encoding rules, as
+    /// understood by this crate:
+    /// - Strings are placed in field order - [`LayoutPolicy::FieldOrder`] -
+    ///   so the data is rejected if the last string would start beyond row
+    ///   255, even if another order would fit.
+    /// - The `bcd` device revision is converted using single precision
+    ///   floating point, which can give a different fractional part - 2.15
+    ///   is 0x0215, rather than 0x0214.
+    ///
+    /// Strings are stored as ASCII if they only contain ASCII characters,
+    /// and as UTF-16 otherwise, with any unused byte in the last ASCII row
+    /// zeroed, in all modes.
+    ///
+    /// The resulting rows have not yet been verified against rows written by
+    /// `picotool` itself - in particular, its `bcd` conversion is assumed.
+    pub fn from_json_picotool(json: &str) -> Result<Self, Error> {
+        let wl = WhiteLabelling::from_json(json)?;
+        let bcd_device = wl.usb_bcd_picotool();
+        let mut wls = Self::from_white_labelling(wl, LayoutPolicy::FieldOrder)?;
+        wls.bcd_device = bcd_device;
         Ok(wls)
     }
 
+    #[cfg(feature = "serde_json")]
     /// Creates a JSON representation of this WhiteLabelStruct.
     pub fn to_json(&self) -> Result<serde_json::Value, Error> {
-        let wl = WhiteLabelling::from(self.clone());
+        let wl = WhiteLabelling::try_from(self)?;
         let result = serde_json::to_value(&wl)?;
         Ok(result)
     }
@@ -312,22 +326,78 @@ impl WhiteLabelStruct {
         self.warnings.is_empty()
     }
 
-    /// Returns the warnings that were generated during creation.
-    pub fn warnings(&self) -> &[String] {
+    /// Returns the warnings that were generated during creation, or by
+    /// validation after any field was set.
+    pub fn warnings(&self) -> &[Diagnostic] {
         &self.warnings
     }
 
     // Validates all fields and updates warnings
     fn validate_fields(&mut self) {
         self.warnings = vec![];
-        for field in FIELDS {
-            match field.validate(&self) {
+        for field in FIELDS.iter() {
+            match field.validate(self) {
                 Ok(_) => {}
                 Err(e) => {
                     self.warnings.push(e);
                 }
             }
         }
+        if let Err(e) = self.check_layout() {
+            self.warnings.push(e);
+        }
+    }
+
+    // Checks every string starts within the offset a STRDEF can hold, when
+    // laid out according to the layout policy.
+    fn check_layout(&self) -> Result<(), Diagnostic> {
+        let layout = self.layout();
+        match layout.last_string() {
+            Some(last) if !layout.fits() => Err(Diagnostic::for_field(
+                DiagnosticCode::TooManyRows,
+                last.field(),
+                format!(
+                    "White label data requires {} rows, with '{}' starting at row {}, beyond the maximum of {MAX_STRING_OFFSET}",
+                    layout.row_count(),
+                    last.field().name(),
+                    last.offset(),
+                ),
+            )
+            .with_rows(last.offset()..last.offset() + 1)),
+            _ => Ok(()),
+        }
+    }
+
+    // Returns the placement of the strings present, according to the layout
+    // policy.
+    pub(crate) fn layout(&self) -> Layout {
+        self.layout_with(|field| self.string_field(field))
+    }
+
+    // Returns the placement of the strings present, according to the layout
+    // policy, with each string field's value given by `string`.
+    pub(crate) fn layout_with<'a>(
+        &self,
+        string: impl Fn(&'static Field) -> Option<&'a OtpString>,
+    ) -> Layout {
+        let strings: Vec<_> = FIELDS
+            .iter()
+            .filter(|field| field.is_string())
+            .filter_map(|field| string(field).map(|s| (field, s)))
+            .collect();
+        Layout::new(&strings, self.layout_policy)
+    }
+
+    /// Sets how strings are placed in OTP.  Defaults to
+    /// [`LayoutPolicy::Auto`].
+    pub fn set_layout_policy(&mut self, policy: LayoutPolicy) {
+        self.layout_policy = policy;
+        self.update_warnings();
+    }
+
+    /// Returns how strings are placed in OTP.
+    pub fn layout_policy(&self) -> LayoutPolicy {
+        self.layout_policy
     }
 
     // Update warnings after each set operation
@@ -532,8 +602,133 @@ impl WhiteLabelStruct {
         self.redirect_name.as_ref().map(|s| s.string())
     }
 
+    /// Returns the value of the given field, if set.
+    ///
+    /// ```rust
+    /// use pico_otp::WhiteLabelStruct;
+    /// use pico_otp::whitelabel::{Field, FieldValue};
+    ///
+    /// # fn main() -> Result<(), pico_otp::WhiteLabelError> {
+    /// let mut wls = WhiteLabelStruct::default();
+    /// let field = Field::from_json_path("device.manufacturer").unwrap();
+    /// wls.set(field, "My Company".into())?;
+    /// assert_eq!(wls.get(field), Some(FieldValue::from("My Company")));
+    /// #   Ok(())
+    /// # }
+    /// ```
+    pub fn get(&self, field: &Field) -> Option<FieldValue> {
+        if field.is_u16() {
+            self.u16_field(field).map(FieldValue::U16)
+        } else {
+            self.string_field(field)
+                .map(|s| FieldValue::String(s.string().clone()))
+        }
+    }
+
+    /// Sets the value of the given field.
+    ///
+    /// Returns `Err(Error::FieldTypeMismatch)` if the value is a string for a
+    /// u16 field, or vice versa, or `Err(Error::StringTooLong)` if the string
+    /// is longer than any OTP string can be.  Other problems, such as a
+    /// string longer than the field supports, are reported by
+    /// [`Self::warnings`], as with the field specific setters.
+    ///
+    /// For `usb_attr_power` the value holds the attributes in the low byte and
+    /// max power in the high byte, as returned by [`Self::attr_power`].
+    pub fn set(&mut self, field: &Field, value: FieldValue) -> Result<(), Error> {
+        match value {
+            FieldValue::U16(value) if field.is_u16() => {
+                *self.u16_field_mut(field) = Some(value);
+            }
+            FieldValue::String(value) if field.is_string() => {
+                *self.string_field_mut(field) = Some(OtpString::try_from(value.as_str())?);
+            }
+            _ => return Err(Error::FieldTypeMismatch(field.name())),
+        }
+        self.update_warnings();
+        Ok(())
+    }
+
+    /// Clears the given field, so the bootrom uses its default value.
+    pub fn clear(&mut self, field: &Field) {
+        if field.is_u16() {
+            *self.u16_field_mut(field) = None;
+        } else {
+            *self.string_field_mut(field) = None;
+        }
+        self.update_warnings();
+    }
+
+    // Returns the value of a u16 field.
+    fn u16_field(&self, field: &Field) -> Option<u16> {
+        match field.index() {
+            0 => self.vendor_id,
+            1 => self.product_id,
+            2 => self.bcd_device,
+            3 => self.language_id,
+            7 => self.attr_power,
+            _ => unreachable!("Not a u16 field"),
+        }
+    }
+
+    // Returns the storage for a u16 field.
+    fn u16_field_mut(&mut self, field: &Field) -> &mut Option<u16> {
+        match field.index() {
+            0 => &mut self.vendor_id,
+            1 => &mut self.product_id,
+            2 => &mut self.bcd_device,
+            3 => &mut self.language_id,
+            7 => &mut self.attr_power,
+            _ => unreachable!("Not a u16 field"),
+        }
+    }
+
+    // Returns the value of a string field.
+    pub(crate) fn string_field(&self, field: &Field) -> Option<&OtpString> {
+        match field.index() {
+            4 => self.manufacturer.as_ref(),
+            5 => self.product.as_ref(),
+            6 => self.serial_number.as_ref(),
+            8 => self.volume_label.as_ref(),
+            9 => self.scsi_vendor.as_ref(),
+            10 => self.scsi_product.as_ref(),
+            11 => self.scsi_version.as_ref(),
+            12 => self.redirect_url.as_ref(),
+            13 => self.redirect_name.as_ref(),
+            14 => self.uf2_model.as_ref(),
+            15 => self.uf2_board_id.as_ref(),
+            _ => unreachable!("Not a string field"),
+        }
+    }
+
+    // Returns the storage for a string field.
+    fn string_field_mut(&mut self, field: &Field) -> &mut Option<OtpString> {
+        match field.index() {
+            4 => &mut self.manufacturer,
+            5 => &mut self.product,
+            6 => &mut self.serial_number,
+            8 => &mut self.volume_label,
+            9 => &mut self.scsi_vendor,
+            10 => &mut self.scsi_product,
+            11 => &mut self.scsi_version,
+            12 => &mut self.redirect_url,
+            13 => &mut self.redirect_name,
+            14 => &mut self.uf2_model,
+            15 => &mut self.uf2_board_id,
+            _ => unreachable!("Not a string field"),
+        }
+    }
+
+    #[cfg(feature = "serde_json")]
     /// Creates a WhiteLabelStruct from a WhiteLabelling instance.
-    fn from_white_labelling(wl: WhiteLabelling) -> Self {
+    ///
+    /// The schema doesn't capture every constraint - such as the total
+    /// number of rows required - so returns
+    /// `Err(Error::InvalidWhiteLabelData)` if the result isn't clean.
+    fn from_white_labelling(
+        wl: WhiteLabelling,
+        layout_policy: LayoutPolicy,
+    ) -> Result<Self, Error> {
         let vendor_id = wl.usb_vid();
         let product_id = wl.usb_pid();
         let bcd_device = wl.usb_bcd();
@@ -568,6 +763,7 @@ impl WhiteLabelStruct {
             redirect_name,
             uf2_model,
             uf2_board_id,
+            layout_policy,
             warnings: vec![],
         };
 
@@ -575,12 +771,11 @@ impl WhiteLabelStruct {
         wls.validate_fields();
 
         if !wls.is_clean() {
-            panic!(
-                "Invalid WhiteLabelStruct created from WhiteLabelling: {:?}",
-                wls.warnings()
-            );
+            return Err(Error::InvalidWhiteLabelData(diagnostic::join(
+                wls.warnings(),
+            )));
         }
-        wls
+        Ok(wls)
     }
 
     fn validate(&self) {
@@ -734,23 +929,18 @@ impl WhiteLabelStruct {
     }
 
     /// Returns the number of OTP rows required to store this white label
-    /// structure and all associated string rows.
+    /// structure and all associated string rows, when laid out according to
+    /// the [`LayoutPolicy`].
+    ///
+    /// Every string must start within 255 rows of the start of the
+    /// structure, but the last may extend beyond it, so this may be up to 319.
+    /// It may be more for white label data parsed from OTP, or with long
+    /// strings set, which cannot then be stored.
     pub fn otp_row_count(&self) -> usize {
         // First (re-)validate
         self.validate();
 
-        let row_count: usize = 16 // struct fields
-            + self.manufacturer.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.product.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.serial_number.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.volume_label.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.scsi_vendor.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.scsi_product.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.scsi_version.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.redirect_url.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.redirect_name.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.uf2_model.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.uf2_board_id.as_ref().map_or(0, |s| s.otp_row_count()) as usize;
+        let row_count = self.layout().row_count();
 
         // Must be at least 16 rows.
         assert!(
@@ -758,35 +948,9 @@ impl WhiteLabelStruct {
             "White label structure must be at least {NUM_INDEX_ROWS} rows"
         );
 
-        // Strictly, we can support more than 255 rows, as the last string can
-        // start at or before row 255 and extend beyond it.  However, the code
-        // is simplified by limiting the total size to 255 rows.
-        assert!(
-            row_count <= 255,
-            "White label structure exceeds maximum size of 255 rows"
-        );
-
         row_count
     }
 
-    // Add up the number of rows all STRDEF entries will take.  This does not
-    // include the STRDEF "pointers" in the struct.
-    fn total_strdef_row_count(&self) -> usize {
-        let total: usize = self.manufacturer.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.product.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.serial_number.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.volume_label.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.scsi_vendor.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.scsi_product.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.scsi_version.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.redirect_url.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.redirect_name.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.uf2_model.as_ref().map_or(0, |s| s.otp_row_count()) as usize
-            + self.uf2_board_id.as_ref().map_or(0, |s| s.otp_row_count()) as usize;
-
-        total
-    }
-
     /// Creates the OTP data required to store this white label structure,
     /// returning an error if there were any issues found within the white
     /// label structure.
@@ -801,10 +965,10 @@ impl WhiteLabelStruct {
     ///   invalid or inconsistent.
     pub fn to_otp_data_strict(&self) -> Result<OtpData, Error> {
         if !self.is_clean() {
-            let warnings = self.warnings().join("\n");
+            let warnings = diagnostic::join(self.warnings());
             return Err(Error::InvalidWhiteLabelData(warnings));
         }
-        Ok(self.create_otp_data(true))
+        self.create_otp_data(true)
     }
 
     /// Creates the OTP data required to store this white label structure.
@@ -816,15 +980,17 @@ impl WhiteLabelStruct {
     /// data to be written to a real device.
     ///
     /// Returns:
-    /// - `OtpData` containing the OTP data rows.
-    pub fn to_otp_data_loose(&self) -> OtpData {
+    /// - `Ok(OtpData)` containing the OTP data rows.
+    /// - `Err(Error::InvalidWhiteLabelData)` if a string would start beyond
+    ///   row 255 - possible for white label data parsed from OTP.
+    pub fn to_otp_data_loose(&self) -> Result<OtpData, Error> {
         self.create_otp_data(false)
     }
 
-    fn create_otp_data(&self, strict: bool) -> OtpData {
-        let rows = self.to_otp_rows();
+    fn create_otp_data(&self, strict: bool) -> Result<OtpData, Error> {
+        let rows = self.to_otp_rows()?;
         let usb_boot_flags = self.usb_boot_flags();
-        OtpData::new(usb_boot_flags, rows, strict)
+        Ok(OtpData::new(usb_boot_flags, rows, strict))
     }
 
     /// Returns a `Vec<u16>` containing all of the rows required to store the
@@ -846,159 +1012,53 @@ impl WhiteLabelStruct {
     /// USB_WHITE_LABEL_ADDR (0x5c).  The offset chosen for the USB white
     /// label data is often 0x100, as it's in a normally clear, unreserved
     /// area.
-    pub(crate) fn to_otp_rows(&self) -> Vec<u16> {
+    ///
+    /// Strings are placed according to the [`LayoutPolicy`].
+    ///
+    /// Returns `Err(Error::InvalidWhiteLabelData)` if a string would start
+    /// beyond row 255.
+    pub(crate) fn to_otp_rows(&self) -> Result<Vec<u16>, Error> {
         // First (re-)validate
         self.validate();
+        self.check_layout()
+            .map_err(|e| Error::InvalidWhiteLabelData(e.to_string()))?;
 
         // Calculate how many rows will be required.  This is not just to make
         // the vec allocation more efficient, but also to help catch errors in
         // case we miscount.
-        let row_count = self.otp_row_count();
+        let layout = self.layout();
+        let row_count = layout.row_count();
 
         // Create a Vec and initialize it with zeros.
         let mut rows: Vec<u16> = vec![0u16; row_count];
-        let mut index = 0;
-        let mut data_offset = NUM_INDEX_ROWS as u8; // first free row after struct fields
-        assert!(row_count <= 255, "Row count exceeds maximum of 255");
-
-        // Next, add each field in order, if present.
-        rows[index] = self.vendor_id.unwrap_or(0);
-        index += 1;
-
-        rows[index] = self.product_id.unwrap_or(0);
-        index += 1;
-
-        rows[index] = self.bcd_device.unwrap_or(0);
-        index += 1;
-
-        rows[index] = self.language_id.unwrap_or(0);
-        index += 1;
-
-        rows[index] = self
-            .manufacturer
-            .as_ref()
-            .map_or(0, |s| s.to_strdef(&mut data_offset));
-        index += 1;
-
-        rows[index] = self
-            .product
-            .as_ref()
-            .map_or(0, |s| s.to_strdef(&mut data_offset));
-        index += 1;
-
-        rows[index] = self
-            .serial_number
-            .as_ref()
-            .map_or(0, |s| s.to_strdef(&mut data_offset));
-        index += 1;
-
-        rows[index] = self.attr_power.unwrap_or(0);
-        index += 1;
-
-        rows[index] = self
-            .volume_label
-            .as_ref()
-            .map_or(0, |s| s.to_strdef(&mut data_offset));
-        index += 1;
-
-        rows[index] = self
-            .scsi_vendor
-            .as_ref()
-            .map_or(0, |s| s.to_strdef(&mut data_offset));
-        index += 1;
-
-        rows[index] = self
-            .scsi_product
-            .as_ref()
-            .map_or(0, |s| s.to_strdef(&mut data_offset));
-        index += 1;
-
-        rows[index] = self
-            .scsi_version
-            .as_ref()
-            .map_or(0, |s| s.to_strdef(&mut data_offset));
-        index += 1;
-
-        rows[index] = self
-            .redirect_url
-            .as_ref()
-            .map_or(0, |s| s.to_strdef(&mut data_offset));
-        index += 1;
-
-        rows[index] = self
-            .redirect_name
-            .as_ref()
-            .map_or(0, |s| s.to_strdef(&mut data_offset));
-        index += 1;
-
-        rows[index] = self
-            .uf2_model
-            .as_ref()
-            .map_or(0, |s| s.to_strdef(&mut data_offset));
-        index += 1;
-
-        rows[index] = self
-            .uf2_board_id
-            .as_ref()
-            .map_or(0, |s| s.to_strdef(&mut data_offset));
-        index += 1;
-
-        // Check things are going smoothly.
-        assert_eq!(
-            index, NUM_INDEX_ROWS,
-            "Incorrect number of struct fields written"
-        );
 
-        // Now write each string in order, if present.
-        if let Some(s) = self.manufacturer.as_ref() {
-            assert!(STRDEF_ROWS.contains(&4));
-            write_otp_string_rows(s, &mut rows, &mut index, 4);
-        }
-        if let Some(s) = self.product.as_ref() {
-            assert!(STRDEF_ROWS.contains(&5));
-            write_otp_string_rows(s, &mut rows, &mut index, 5);
-        }
-        if let Some(s) = self.serial_number.as_ref() {
-            assert!(STRDEF_ROWS.contains(&6));
-            write_otp_string_rows(s, &mut rows, &mut index, 6);
-        }
-        if let Some(s) = self.volume_label.as_ref() {
-            assert!(STRDEF_ROWS.contains(&8));
-            write_otp_string_rows(s, &mut rows, &mut index, 8);
-        }
-        if let Some(s) = self.scsi_vendor.as_ref() {
-            assert!(STRDEF_ROWS.contains(&9));
-            write_otp_string_rows(s, &mut rows, &mut index, 9);
-        }
-        if let Some(s) = self.scsi_product.as_ref() {
-            assert!(STRDEF_ROWS.contains(&10));
-            write_otp_string_rows(s, &mut rows, &mut index, 10);
-        }
-        if let Some(s) = self.scsi_version.as_ref() {
-            assert!(STRDEF_ROWS.contains(&11));
-            write_otp_string_rows(s, &mut rows, &mut index, 11);
-        }
-        if let Some(s) = self.redirect_url.as_ref() {
-            assert!(STRDEF_ROWS.contains(&12));
-            write_otp_string_rows(s, &mut rows, &mut index, 12);
-        }
-        if let Some(s) = self.redirect_name.as_ref() {
-            assert!(STRDEF_ROWS.contains(&13));
-            write_otp_string_rows(s, &mut rows, &mut index, 13);
-        }
-        if let Some(s) = self.uf2_model.as_ref() {
-            assert!(STRDEF_ROWS.contains(&14));
-            write_otp_string_rows(s, &mut rows, &mut index, 14);
+        // Add each u16 field, if present.
+        for ii in U16_ROWS.iter() {
+            rows[*ii] = self.u16_field(&FIELDS[*ii]).unwrap_or(0);
         }
-        if let Some(s) = self.uf2_board_id.as_ref() {
-            assert!(STRDEF_ROWS.contains(&15));
-            write_otp_string_rows(s, &mut rows, &mut index, 15);
+
+        // Now add each string's STRDEF and rows, in the order the layout
+        // places them.  Strings sharing rows come last, so the rows they
+        // share have already been written.
+        let mut index = NUM_INDEX_ROWS;
+        for placed in layout.strings() {
+            let field = placed.field();
+            assert!(STRDEF_ROWS.contains(&field.index()));
+            let s = self.string_field(field).ok_or_else(|| {
+                Error::InternalInconsistency(format!("{} laid out but not set", field.name()))
+            })?;
+            rows[field.index()] = s.to_strdef(placed.offset() as u8);
+            if placed.is_shared() {
+                check_otp_string_rows(s, &rows, field.index());
+            } else {
+                write_otp_string_rows(s, &mut rows, &mut index, field.index());
+            }
         }
 
         // We're done.  Check it went as expected.
         assert_eq!(index, row_count, "Incorrect total number of rows written");
 
-        rows
+        Ok(rows)
     }
 
     /// Return a WhiteLabelStruct from the provided OTP rows.
@@ -1025,28 +1085,33 @@ impl WhiteLabelStruct {
     pub(crate) fn parse_otp(usb_boot_flags: u32, rows: &[u16]) -> Result<OtpParseResult, Error> {
         // Validate we have at least the struct fields
         if rows.len() < NUM_INDEX_ROWS {
-            return Err(Error::InternalInconsistency(format!(
-                "Too few rows provided"
-            )));
+            return Err(Error::InternalInconsistency(
+                "Too few rows provided".to_string(),
+            ));
         }
 
         let mut warnings = Vec::new();
 
         // Check the USB boot flags
         if (usb_boot_flags & (1 << WHITE_LABEL_ADDR_VALID_BIT_NUM)) == 0 {
-            warnings.push(format!(
-                "USB_BOOT_FLAGS bit {WHITE_LABEL_ADDR_VALID_BIT_NUM} (WHITE_LABEL_ADDR_VALID) is not set - white label data may be invalid",
+            warnings.push(Diagnostic::new(
+                DiagnosticCode::AddrValidClear,
+                format!(
+                    "USB_BOOT_FLAGS bit {WHITE_LABEL_ADDR_VALID_BIT_NUM} (WHITE_LABEL_ADDR_VALID) is not set - white label data may be invalid",
+                ),
             ));
         }
         if (usb_boot_flags & (1 << DP_DM_SWAP_BIT_NUM)) != 0 {
             // This is not an error, but interesting, so report it.
-            warnings.push(format!(
-                "USB_BOOT_FLAGS bit {DP_DM_SWAP_BIT_NUM} (DPDM_SWAP) is set",
+            warnings.push(Diagnostic::new(
+                DiagnosticCode::DpDmSwap,
+                format!("USB_BOOT_FLAGS bit {DP_DM_SWAP_BIT_NUM} (DPDM_SWAP) is set",),
             ));
         }
-        if (usb_boot_flags & 0xFF1F0000) != 0 {
-            warnings.push(format!(
-                "USB_BOOT_FLAGS has invalid bits set - ignoring these",
+        if UsbBootFlags::from_bits_retain(usb_boot_flags).reserved_bits() != 0 {
+            warnings.push(Diagnostic::new(
+                DiagnosticCode::ReservedBootFlags,
+                "USB_BOOT_FLAGS has invalid bits set - ignoring these".to_string(),
             ));
         }
         // Now take the bottom 16 bits only
@@ -1068,26 +1133,20 @@ impl WhiteLabelStruct {
         let attr_power =
             extract_u16_field(rows, usb_boot_flags, &FIELD_USB_ATTR_POWER, &mut warnings);
 
-        // Check we have enough rows for the strings indicated by the boot flags
-        let expected_total_row_count = NUM_INDEX_ROWS + {
-            let mut count = 0;
-            for ii in STRDEF_ROWS.iter() {
-                if (usb_boot_flags & (1 << ii)) != 0 {
-                    // String present - get STRDEF row
-                    let strdef = rows[*ii];
-                    let str_row_count = OtpString::row_count_from_strdef(strdef);
-                    count += str_row_count as usize;
-                }
-            }
-            count
-        };
+        // Check we have enough rows for the strings indicated by the boot
+        // flags.  Strings may be in any order, and may share rows, so this is
+        // the end of the furthest string.
+        let expected_total_row_count = referenced_row_count(usb_boot_flags as u32, rows);
         if rows.len() < expected_total_row_count {
             // Not enough rows to extract all strings indicated by boot flags
             // so return early with warnings and no strings.
-            warnings.push(format!(
-                "OTP rows length {} is less than expected {} based on USB_BOOT_FLAGS - will not extract any strings",
-                rows.len(),
-                expected_total_row_count
+            warnings.push(Diagnostic::new(
+                DiagnosticCode::TooFewRows,
+                format!(
+                    "OTP rows length {} is less than expected {} based on USB_BOOT_FLAGS - will not extract any strings",
+                    rows.len(),
+                    expected_total_row_count
+                ),
             ));
             let wl = Self {
                 vendor_id,
@@ -1106,6 +1165,7 @@ impl WhiteLabelStruct {
                 redirect_name: None,
                 uf2_model: None,
                 uf2_board_id: None,
+                layout_policy: LayoutPolicy::default(),
                 warnings: warnings.clone(),
             };
             wl.validate();
@@ -1117,93 +1177,84 @@ impl WhiteLabelStruct {
         }
 
         // Extract string fields
-        assert!(
-            NUM_STRDEF_ROWS == 11,
-            "Expected 11 STRDEF fields in white label struct"
-        );
+        const {
+            assert!(
+                NUM_STRDEF_ROWS == 11,
+                "Expected 11 STRDEF fields in white label struct"
+            )
+        };
         let manufacturer = OtpString::from_otp_data(
             rows,
             usb_boot_flags,
             &FIELD_USB_MANUFACTURER,
             true,
             &mut warnings,
-        )
-        .expect("Manufacturer string parsing failed");
+        )?;
         let product = OtpString::from_otp_data(
             rows,
             usb_boot_flags,
             &FIELD_USB_PRODUCT,
             true,
             &mut warnings,
-        )
-        .expect("Product string parsing failed");
+        )?;
         let serial_number = OtpString::from_otp_data(
             rows,
             usb_boot_flags,
             &FIELD_USB_SERIAL_NUMBER,
             true,
             &mut warnings,
-        )
-        .expect("Serial number string parsing failed");
+        )?;
         let volume_label = OtpString::from_otp_data(
             rows,
             usb_boot_flags,
             &FIELD_VOLUME_LABEL,
             false,
             &mut warnings,
-        )
-        .expect("Volume label string parsing failed");
+        )?;
         let scsi_vendor = OtpString::from_otp_data(
             rows,
             usb_boot_flags,
             &FIELD_SCSI_VENDOR,
             false,
             &mut warnings,
-        )
-        .expect("SCSI vendor string parsing failed");
+        )?;
         let scsi_product = OtpString::from_otp_data(
             rows,
             usb_boot_flags,
             &FIELD_SCSI_PRODUCT,
             false,
             &mut warnings,
-        )
-        .expect("SCSI product string parsing failed");
+        )?;
         let scsi_version = OtpString::from_otp_data(
             rows,
             usb_boot_flags,
             &FIELD_SCSI_VERSION,
             false,
             &mut warnings,
-        )
-        .expect("SCSI version string parsing failed");
+        )?;
         let redirect_url = OtpString::from_otp_data(
             rows,
             usb_boot_flags,
             &FIELD_REDIRECT_URL,
             false,
             &mut warnings,
-        )
-        .expect("Redirect URL string parsing failed");
+        )?;
         let redirect_name = OtpString::from_otp_data(
             rows,
             usb_boot_flags,
             &FIELD_REDIRECT_NAME,
             false,
             &mut warnings,
-        )
-        .expect("Redirect name string parsing failed");
+        )?;
         let uf2_model =
-            OtpString::from_otp_data(rows, usb_boot_flags, &FIELD_UF2_MODEL, false, &mut warnings)
-                .expect("UF2 model string parsing failed");
+            OtpString::from_otp_data(rows, usb_boot_flags, &FIELD_UF2_MODEL, false, &mut warnings)?;
         let uf2_board_id = OtpString::from_otp_data(
             rows,
             usb_boot_flags,
             &FIELD_UF2_BOARD_ID,
             false,
             &mut warnings,
-        )
-        .expect("UF2 board ID string parsing failed");
+        )?;
 
         let mut wl = Self {
             vendor_id,
@@ -1222,18 +1273,21 @@ impl WhiteLabelStruct {
             redirect_name,
             uf2_model,
             uf2_board_id,
+            layout_policy: LayoutPolicy::default(),
             warnings: vec![], // Set below
         };
 
-        let actual_strings_row_count = wl.total_strdef_row_count();
-        let expected_row_count = NUM_INDEX_ROWS + actual_strings_row_count;
+        let expected_row_count = referenced_row_count(wl.usb_boot_flags(), rows);
 
         // Allow too much data, but not too little
         if rows.len() < expected_row_count {
-            warnings.push(format!(
-                "OTP rows length {} does not match expected {} based on actual string data",
-                rows.len(),
-                expected_row_count
+            warnings.push(Diagnostic::new(
+                DiagnosticCode::RowCountMismatch,
+                format!(
+                    "OTP rows length {} does not match expected {} based on actual string data",
+                    rows.len(),
+                    expected_row_count
+                ),
             ));
         }
 
@@ -1259,7 +1313,7 @@ impl WhiteLabelStruct {
 // correct location as indicated by the strdef row at strdef_row_index.
 fn write_otp_string_rows(
     otp_string: &OtpString,
-    rows: &mut Vec<u16>,
+    rows: &mut [u16],
     start_index: &mut usize,
     strdef_row_index: usize,
 ) {
@@ -1287,11 +1341,23 @@ fn write_otp_string_rows(
     }
 }
 
+// Checks the rows pointed to by the STRDEF at strdef_row_index, which shares
+// rows already written for another string, hold the OtpString.
+fn check_otp_string_rows(otp_string: &OtpString, rows: &[u16], strdef_row_index: usize) {
+    let start_index = OtpString::offset_from_row(rows[strdef_row_index]) as usize;
+    let str_rows = otp_string.to_otp_rows();
+    assert_eq!(
+        &rows[start_index..start_index + str_rows.len()],
+        str_rows.as_slice(),
+        "Shared string rows do not match"
+    );
+}
+
 /// Result of parsing OTP white label data.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct OtpParseResult {
     white_label: WhiteLabelStruct,
-    warnings: Vec<String>,
+    warnings: Vec<Diagnostic>,
 }
 
 impl OtpParseResult {
@@ -1306,11 +1372,31 @@ impl OtpParseResult {
     }
 
     /// Returns any warnings generated during parsing.
-    pub(crate) fn warnings(&self) -> &Vec<String> {
+    pub(crate) fn warnings(&self) -> &Vec<Diagnostic> {
         &self.warnings
     }
 }
 
+/// Returns the number of rows, from the start of the white label struct, that
+/// are referenced by the struct - the struct itself plus every string whose
+/// boot flag bit is set.  Strings may be stored in any order, so this is
+/// the end of the furthest string, not the sum of the string lengths.
+///
+/// `rows` must be at least [`NUM_INDEX_ROWS`] long.  The value returned may
+/// exceed `rows.len()` if the data is corrupt.
+pub(crate) fn referenced_row_count(usb_boot_flags: u32, rows: &[u16]) -> usize {
+    let mut end = NUM_INDEX_ROWS;
+    for ii in STRDEF_ROWS.iter() {
+        if (usb_boot_flags & (1 << ii)) != 0 {
+            let strdef = rows[*ii];
+            let str_end = OtpString::offset_from_row(strdef) as usize
+                + OtpString::row_count_from_strdef(strdef) as usize;
+            end = end.max(str_end);
+        }
+    }
+    end
+}
+
 /// Extracts a u16 field from OTP rows, checking the boot flag bit.
 ///
 /// If the boot flag bit is clear but the row contains non-zero data,
@@ -1319,7 +1405,7 @@ fn extract_u16_field(
     rows: &[u16],
     usb_boot_flags: u16,
     field: &Field,
-    warnings: &mut Vec<String>,
+    warnings: &mut Vec<Diagnostic>,
 ) -> Option<u16> {
     let index = field.index();
 
@@ -1333,18 +1419,22 @@ fn extract_u16_field(
         // the user may have written USB white label information but not set it
         // to valid, so it is unused by the bootloader.
         if value != 0 {
-            warnings.push(format!(
-                "{}: boot flag bit {} clear but row contains non-zero value 0x{:04X}",
-                field.name(),
-                index,
-                value
+            warnings.push(Diagnostic::for_field(
+                DiagnosticCode::FieldNotEnabled,
+                field,
+                format!(
+                    "{}: boot flag bit {} clear but row contains non-zero value 0x{:04X}",
+                    field.name(),
+                    index,
+                    value
+                ),
             ));
         }
         None
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "serde_json"))]
 mod tests {
     extern crate std;
     use super::*;
@@ -1366,7 +1456,7 @@ mod tests {
         );
 
         // Get the row data
-        let otp_rows = wl.to_otp_rows();
+        let otp_rows = wl.to_otp_rows().unwrap();
         // 16 rows + string lengths
         assert_eq!(
             otp_rows.len(),
@@ -1411,7 +1501,7 @@ mod tests {
             let wl = wl.unwrap();
 
             let usb_boot_flags = wl.usb_boot_flags();
-            let otp_rows = wl.to_otp_rows();
+            let otp_rows = wl.to_otp_rows().unwrap();
             let new_wl = WhiteLabelStruct::parse_otp(usb_boot_flags, &otp_rows);
             assert!(
                 new_wl.is_ok(),
@@ -1463,7 +1553,7 @@ mod tests {
         );
 
         // Get the row data
-        let otp_rows = wl.to_otp_rows();
+        let otp_rows = wl.to_otp_rows().unwrap();
         // 16 rows + string lengths
         assert_eq!(
             otp_rows.len(),
@@ -1501,7 +1591,7 @@ mod tests {
             usb_boot_flags, 0x0040_FFFF,
             "USB boot flags do not match expected value"
         );
-        let otp_rows = wl.to_otp_rows();
+        let otp_rows = wl.to_otp_rows().unwrap();
         let new_wl = WhiteLabelStruct::parse_otp(usb_boot_flags, &otp_rows);
         assert!(new_wl.is_ok());
         let new_wl = new_wl.unwrap();
@@ -1527,7 +1617,7 @@ mod tests {
             usb_boot_flags, 0x0040_0000,
             "USB boot flags do not match expected value"
         );
-        let otp_rows = wl.to_otp_rows();
+        let otp_rows = wl.to_otp_rows().unwrap();
         assert_eq!(
             otp_rows.len(),
             16,
@@ -1588,7 +1678,7 @@ mod tests {
         );
 
         // And generate the binary data again
-        let regenerated_rows = wl.to_otp_rows();
+        let regenerated_rows = wl.to_otp_rows().unwrap();
         assert_eq!(
             row_data.to_vec(),
             regenerated_rows,
@@ -1596,7 +1686,7 @@ mod tests {
         );
 
         // Back to JSON schema
-        let regenerated_wling = WhiteLabelling::from(wl);
+        let regenerated_wling = WhiteLabelling::try_from(&wl).unwrap();
         let new_json = serde_json::to_value(regenerated_wling).unwrap();
         let orig_json = serde_json::from_str::<serde_json::Value>(json).unwrap();
         assert_eq!(
@@ -1617,8 +1707,75 @@ mod tests {
         let warnings = parse_result.warnings;
         assert_eq!(warnings.len(), 1, "Expected one warning during parsing");
         assert!(
-            warnings[0].contains("WHITE_LABEL_ADDR_VALID"),
+            warnings[0].to_string().contains("WHITE_LABEL_ADDR_VALID"),
             "Expected WHITE_LABEL_ADDR_VALID warning"
         );
+        assert_eq!(warnings[0].code(), DiagnosticCode::AddrValidClear);
+        assert_eq!(warnings[0].field_name(), None);
+    }
+
+    #[test]
+    fn test_from_otp_data_malformed() {
+        let flags = 0x0040_0000 | (1 << 4) | (1 << 5) | (1 << 6) | (1 << 12) | (1 << 13);
+        let mut rows = vec![0u16; 319];
+
+        // 127 character UTF-16 manufacturer, product and serial number, too
+        // long for the schema, and together needing more than 255 rows unless
+        // they share rows, as the manufacturer and serial number do
+        rows[4] = 0x10ff;
+        rows[5] = 0x8fff;
+        rows[6] = 0x10ff;
+        rows[16..16 + 254].fill(0x4e2d);
+        // UTF-16 redirect URL holding only ASCII
+        rows[12] = 0xff81;
+        rows[255] = 0x0041;
+        // ASCII redirect name holding UTF-8
+        rows[13] = 0xfe02;
+        rows[254] = 0xa9c3;
+
+        let mut wl = WhiteLabelStruct::parse_otp(flags, &rows)
+            .unwrap()
+            .white_label;
+        assert_eq!(wl.manufacturer().unwrap().chars().count(), 127);
+        assert_eq!(wl.serial_number(), wl.manufacturer());
+        assert_eq!(wl.redirect_url().unwrap(), "A");
+        assert!(wl.redirect_name().is_none());
+        let codes: Vec<_> = wl.warnings().iter().map(|w| w.code()).collect();
+        assert!(codes.contains(&DiagnosticCode::Utf16AsciiOnly));
+        assert!(codes.contains(&DiagnosticCode::NonAsciiString));
+
+        assert!(matches!(wl.to_json(), Err(Error::InvalidWhiteLabelData(_))));
+        assert!(matches!(
+            wl.to_otp_data_loose(),
+            Err(Error::InvalidWhiteLabelData(_))
+        ));
+        // The product differs, as it contains the redirect strings' rows
+        wl.set_layout_policy(LayoutPolicy::Compact);
+        assert_eq!(
+            wl.to_otp_data_loose().unwrap().rows().len(),
+            16 + 127 + 127 + 1
+        );
+    }
+
+    #[test]
+    fn test_from_json_too_many_rows() {
+        // Needs 272 rows, but the last string starts at row 208
+        let long = "x".repeat(127);
+        let json = format!(
+            r#"{{"volume": {{"redirect_url": "{long}", "redirect_name": "{long}", "model": "{long}", "board_id": "{long}"}}}}"#
+        );
+        let wls = WhiteLabelStruct::from_json(&json).unwrap();
+        assert_eq!(wls.to_otp_data_strict().unwrap().rows().len(), 272);
+
+        // Adding 90 rows of UTF-16 strings leaves the last starting at 298
+        let utf16 = "号".repeat(30);
+        let json = format!(
+            r#"{{"device": {{"manufacturer": "{utf16}", "product": "{utf16}", "serial_number": "{utf16}"}}, {}"#,
+            &json[1..]
+        );
+        let err = WhiteLabelStruct::from_json(&json).unwrap_err();
+        assert!(
+            matches!(err, Error::InvalidWhiteLabelData(ref s) if s.contains("'uf2_board_id' starting at row 298"))
+        );
     }
 }
@@ -0,0 +1,450 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Plans updates to white label data already stored in OTP.
+//!
+//! OTP bits can only be set, so once a device has been white labelled, fields
+//! can only be added if their boot flag bit is still clear, and the rows they
+//! need are still blank.  Changing a field which has already been written
+//! requires a new copy of the white label data, at a new address.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::ecc;
+use crate::errata::{self, E17Issue};
+use crate::image::{OtpImage, RowMode};
+use crate::lock::PageLocks;
+use crate::plan::OtpWrite;
+use crate::region::{self, RegionStrategy};
+use crate::whitelabel::fields::FIELDS;
+use crate::whitelabel::strdef::MAX_STRING_OFFSET;
+use crate::whitelabel::string::OtpString;
+use crate::whitelabel::top::{
+    NUM_INDEX_ROWS, TOTAL_OTP_ROWS, WHITE_LABEL_ADDR_VALID_BIT_NUM, referenced_row_count,
+};
+use crate::whitelabel::{
+    Error, OTP_ROW_USB_BOOT_FLAGS, OTP_ROW_USB_BOOT_FLAGS_R1, OTP_ROW_USB_BOOT_FLAGS_R2,
+    OTP_ROW_USB_WHITE_LABEL_DATA, OtpData, WhiteLabelStruct,
+};
+
+/// How an update to white label data is to be made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateKind {
+    /// The new fields are added to the existing white label data.
+    InPlace,
+
+    /// A complete new copy of the white label data is written at the given
+    /// row, and USB_WHITE_LABEL_ADDR updated to point to it.
+    NewCopy(u16),
+}
+
+/// A plan to update white label data already stored in OTP, from
+/// [`OtpData::plan_update`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdatePlan {
+    kind: UpdateKind,
+    writes: Vec<OtpWrite>,
+    reasons: Vec<String>,
+}
+
+impl UpdatePlan {
+    /// Returns how the update is to be made.
+    pub fn kind(&self) -> UpdateKind {
+        self.kind
+    }
+
+    /// Returns the OTP writes required, in order.  Empty if the desired white
+    /// label data is already stored.
+    pub fn writes(&self) -> &[OtpWrite] {
+        &self.writes
+    }
+
+    /// Returns why the update could not be made in place.  Empty for
+    /// [`UpdateKind::InPlace`].
+    pub fn reasons(&self) -> &[String] {
+        &self.reasons
+    }
+}
+
+impl OtpData {
+    /// Plans the OTP writes required to change the white label data stored on
+    /// a device from this OtpData, as parsed from the device, to `desired`.
+    ///
+    /// `image` must hold the device's OTP, as read with
+    /// [`OtpImage::from_raw_data`], and is used to find the existing white
+    /// label address, and which rows are still blank.  Use
+    /// [`OtpImage::white_label_data`] to get this OtpData from it.
+    ///
+    /// New fields are added in place where possible, with any new strings
+    /// stored in the blank rows following the existing strings.  Otherwise, a
+    /// new copy of the white label data is planned, in the lowest free region
+    /// of OTP whose row index can be written over the existing
+    /// USB_WHITE_LABEL_ADDR value - as an ECC row, this is only possible if
+    /// the new value's encoding has every bit of the existing one set.
+    ///
+    /// Returns an error if the update is not possible - for example if
+    /// `desired` omits a field which is already set, as its boot flag bit
+    /// cannot be cleared, or if the updated white label data would be in a
+    /// page `image`'s locks show the bootrom or bootloader cannot read.
+    pub fn plan_update(
+        &self,
+        image: &OtpImage,
+        desired: &WhiteLabelStruct,
+    ) -> Result<UpdatePlan, Error> {
+        let current_flags = self.usb_boot_flags();
+        if current_flags & (1 << WHITE_LABEL_ADDR_VALID_BIT_NUM) == 0 {
+            return Err(Error::InvalidWhiteLabelAddress);
+        }
+        let white_label_addr = image
+            .read_ecc(OTP_ROW_USB_WHITE_LABEL_DATA)
+            .map_err(|e| Error::OtpDataError(format!("{e}")))?;
+        if white_label_addr as usize + NUM_INDEX_ROWS > TOTAL_OTP_ROWS {
+            return Err(Error::InvalidWhiteLabelAddressValue(white_label_addr));
+        }
+        let desired = desired.to_otp_data_strict()?;
+
+        // Boot flag bits can't be cleared, so fields can't be removed.
+        let removed: Vec<&str> = FIELDS
+            .iter()
+            .filter(|field| {
+                let bit = 1 << field.index();
+                current_flags & bit != 0 && desired.usb_boot_flags() & bit == 0
+            })
+            .map(|field| field.name())
+            .collect();
+        if !removed.is_empty() {
+            return Err(Error::UpdateNotPossible(format!(
+                "set fields cannot be removed: {}",
+                removed.join(", ")
+            )));
+        }
+
+        let mut plan = match self.in_place_writes(image, white_label_addr, &desired) {
+            Ok(writes) => UpdatePlan {
+                kind: UpdateKind::InPlace,
+                writes,
+                reasons: Vec::new(),
+            },
+            Err(reasons) => new_copy(image, &desired, reasons)?,
+        };
+
+        // Check the bootrom can read the updated white label data - either
+        // the existing data, extended by any new rows, or the new copy.
+        let (addr, num_rows) = match plan.kind {
+            UpdateKind::InPlace => {
+                let end = plan
+                    .writes
+                    .iter()
+                    .map(|write| write.row() as usize + 1)
+                    .max()
+                    .unwrap_or(0);
+                let extended = end.saturating_sub(white_label_addr as usize);
+                (white_label_addr, self.rows().len().max(extended))
+            }
+            UpdateKind::NewCopy(addr) => (addr, desired.rows().len()),
+        };
+        let locks =
+            PageLocks::from_image(image, false).map_err(|e| Error::OtpDataError(format!("{e}")))?;
+        if let Some(page) = locks.unreadable_white_label_pages(addr, num_rows).first() {
+            return Err(Error::UnreadablePage(*page));
+        }
+
+        // Finally, set any new boot flag bits in each copy.
+        let flags = current_flags | desired.usb_boot_flags();
+        for row in [
+            OTP_ROW_USB_BOOT_FLAGS,
+            OTP_ROW_USB_BOOT_FLAGS_R1,
+            OTP_ROW_USB_BOOT_FLAGS_R2,
+        ] {
+            let current = image.read_raw(row).unwrap_or(0);
+            if current | flags != current {
+                plan.writes.push(OtpWrite::raw(row, current | flags));
+            }
+        }
+
+        Ok(plan)
+    }
+
+    // Returns the writes required to add the new fields in `desired` to the
+    // existing white label data at `white_label_addr`, or the reasons this
+    // isn't possible.
+    fn in_place_writes(
+        &self,
+        image: &OtpImage,
+        white_label_addr: u16,
+        desired: &OtpData,
+    ) -> Result<Vec<OtpWrite>, Vec<String>> {
+        let current_rows = self.rows();
+        if current_rows.len() < NUM_INDEX_ROWS {
+            return Err(Vec::from([String::from(
+                "existing white label data is incomplete",
+            )]));
+        }
+
+        let mut reasons = Vec::new();
+        let mut string_writes = Vec::new();
+        let mut index_writes = Vec::new();
+        let mut next_offset = referenced_row_count(self.usb_boot_flags(), current_rows);
+        let original_end = next_offset;
+
+        for field in FIELDS.iter() {
+            let index = field.index();
+            let bit = 1 << index;
+            if desired.usb_boot_flags() & bit == 0 {
+                continue;
+            }
+            let name = field.name();
+
+            if self.usb_boot_flags() & bit != 0 {
+                if field_rows(current_rows, index) != field_rows(desired.rows(), index) {
+                    reasons.push(format!("{name} is already set to a different value"));
+                }
+                continue;
+            }
+
+            let index_row = white_label_addr + index as u16;
+            if current_rows[index] != 0 || !is_blank(image, index_row) {
+                reasons.push(format!(
+                    "{name}'s struct row {index_row:#05x} has already been written"
+                ));
+                continue;
+            }
+
+            let mut value = desired.rows()[index];
+            if field.is_string() {
+                let str_rows = match field_rows(desired.rows(), index) {
+                    Some(rows) => rows,
+                    None => continue,
+                };
+                let str_rows = &str_rows[1..];
+                let start = white_label_addr as usize + next_offset;
+                if next_offset > MAX_STRING_OFFSET
+                    || !(start..start + str_rows.len()).all(|row| is_blank(image, row as u16))
+                {
+                    reasons.push(format!(
+                        "no blank rows for {name} after the existing strings"
+                    ));
+                    continue;
+                }
+                string_writes.extend(
+                    str_rows
+                        .iter()
+                        .zip(start as u16..)
+                        .filter(|(value, _)| **value != 0)
+                        .map(|(value, row)| OtpWrite::ecc(row, *value)),
+                );
+                value = (value & 0x00ff) | ((next_offset as u16) << 8);
+                next_offset += str_rows.len();
+            }
+            if value != 0 {
+                index_writes.push(OtpWrite::ecc(index_row, value));
+            }
+        }
+
+        // Check any new strings don't end up sharing an even/odd row pair
+        // with other data.
+        if next_offset > original_end {
+            let issues = errata::placement_issues(white_label_addr, next_offset, true, |row| {
+                image.row_mode(row).unwrap_or(RowMode::Raw)
+            });
+            for issue in issues {
+                let row = match issue {
+                    E17Issue::OddStartAddress(row)
+                    | E17Issue::MixedModes(row)
+                    | E17Issue::SharedPair(row) => row,
+                };
+                if row >= white_label_addr {
+                    reasons.push(format!("new strings would be affected by {issue}"));
+                }
+            }
+        }
+
+        if !reasons.is_empty() {
+            return Err(reasons);
+        }
+
+        // Write the strings before the struct rows which point to them.
+        string_writes.extend(index_writes);
+        Ok(string_writes)
+    }
+}
+
+// Plans writing a complete new copy of `desired`, and pointing
+// USB_WHITE_LABEL_ADDR at it.
+fn new_copy(
+    image: &OtpImage,
+    desired: &OtpData,
+    reasons: Vec<String>,
+) -> Result<UpdatePlan, Error> {
+    let current = image.read_raw(OTP_ROW_USB_WHITE_LABEL_DATA).unwrap_or(0);
+    let search = region::find_white_label_region_where(
+        image,
+        desired.rows().len(),
+        RegionStrategy::Lowest,
+        |addr| ecc::encode(addr) & current == current,
+    );
+    let white_label_addr = search.address().ok_or_else(|| {
+        Error::UpdateNotPossible(format!(
+            "{}, and no free OTP region found whose row index can be written to USB_WHITE_LABEL_ADDR",
+            reasons.join(", ")
+        ))
+    })?;
+
+    let mut writes: Vec<OtpWrite> = desired
+        .rows()
+        .iter()
+        .zip(white_label_addr..)
+        .filter(|(value, _)| **value != 0)
+        .map(|(value, row)| OtpWrite::ecc(row, *value))
+        .collect();
+    writes.push(OtpWrite::ecc_rewrite(
+        OTP_ROW_USB_WHITE_LABEL_DATA,
+        white_label_addr,
+    ));
+
+    Ok(UpdatePlan {
+        kind: UpdateKind::NewCopy(white_label_addr),
+        writes,
+        reasons,
+    })
+}
+
+// Returns the struct row for the field at `index`, followed by its string
+// rows for string fields, or None if the rows are truncated.  For strings,
+// only the length and encoding are kept from the STRDEF row, not the offset.
+fn field_rows(rows: &[u16], index: usize) -> Option<Vec<u16>> {
+    let value = rows[index];
+    if FIELDS[index].is_u16() {
+        return Some(Vec::from([value]));
+    }
+    let offset = OtpString::offset_from_row(value) as usize;
+    let count = OtpString::row_count_from_strdef(value) as usize;
+    let str_rows = rows.get(offset..offset + count)?;
+    let mut field = Vec::from([value & 0x00ff]);
+    field.extend_from_slice(str_rows);
+    Some(field)
+}
+
+// Returns whether the row is blank.  Rows beyond the end of OTP are not.
+fn is_blank(image: &OtpImage, row: u16) -> bool {
+    image.row_mode(row) == Ok(RowMode::Blank)
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::*;
+    use crate::lock::{self, LockLevel, PageLock};
+
+    // Programs `json` at 0x100 in a blank image, returning the image as read
+    // back from a device, and the OtpData parsed back from it.
+    fn programmed_image(json: &str) -> (OtpImage, OtpData) {
+        let otp_data = OtpData::from_json(json).unwrap();
+        let mut image = OtpImage::new();
+        image
+            .apply_plan(&otp_data.programming_plan(0x100, false, None).unwrap())
+            .unwrap();
+        let image = OtpImage::from_raw_data(image.raw_rows().try_into().unwrap());
+        let parsed = image.white_label_data(true).unwrap();
+        (image, parsed)
+    }
+
+    const CURRENT: &str = r#"{
+        "device": {
+            "vid": "0x1234",
+            "pid": "0x5678",
+            "manufacturer": "piers.rocks",
+            "product": "pico-otp"
+        }
+    }"#;
+
+    #[test]
+    fn test_in_place() {
+        let (mut image, current) = programmed_image(CURRENT);
+        let desired = WhiteLabelStruct::from_json(
+            r#"{
+            "device": {
+                "vid": "0x1234",
+                "pid": "0x5678",
+                "manufacturer": "piers.rocks",
+                "product": "pico-otp",
+                "serial_number": "ABC123",
+                "bcd": 1.23
+            }
+        }"#,
+        )
+        .unwrap();
+
+        let plan = current.plan_update(&image, &desired).unwrap();
+        assert!(plan.reasons().is_empty(), "{:?}", plan.reasons());
+        assert_eq!(plan.kind(), UpdateKind::InPlace);
+        image.apply_plan(plan.writes()).unwrap();
+        let updated = image.white_label_data(true).unwrap();
+        assert_eq!(updated.to_json().unwrap(), desired.to_json().unwrap());
+
+        // Nothing further to do
+        let plan = updated.plan_update(&image, &desired).unwrap();
+        assert_eq!(plan.kind(), UpdateKind::InPlace);
+        assert!(plan.writes().is_empty());
+
+        // Refused once the bootloader can't read the data's page
+        let lock = PageLock {
+            bootloader: LockLevel::Inaccessible,
+            ..Default::default()
+        };
+        image
+            .apply_plan(&lock::plan_page_lock(&image, 4, &lock).unwrap())
+            .unwrap();
+        assert!(matches!(
+            updated.plan_update(&image, &desired),
+            Err(Error::UnreadablePage(4))
+        ));
+    }
+
+    #[test]
+    fn test_new_copy() {
+        let (mut image, current) = programmed_image(CURRENT);
+        let desired = WhiteLabelStruct::from_json(
+            r#"{
+            "device": {
+                "vid": "0x1234",
+                "pid": "0x5678",
+                "manufacturer": "piers.rocks",
+                "product": "pico-otp 2"
+            }
+        }"#,
+        )
+        .unwrap();
+
+        let plan = current.plan_update(&image, &desired).unwrap();
+        let UpdateKind::NewCopy(addr) = plan.kind() else {
+            panic!("Expected a new copy");
+        };
+        assert_eq!(ecc::encode(addr) & ecc::encode(0x100), ecc::encode(0x100));
+        assert_eq!(plan.reasons().len(), 1);
+        image.apply_plan(plan.writes()).unwrap();
+        let updated = image.white_label_data(true).unwrap();
+        assert_eq!(updated.to_json().unwrap(), desired.to_json().unwrap());
+    }
+
+    #[test]
+    fn test_impossible() {
+        let (image, current) = programmed_image(CURRENT);
+        let desired = WhiteLabelStruct::from_json(
+            r#"{
+            "device": {
+                "vid": "0x1234",
+                "manufacturer": "piers.rocks",
+                "product": "pico-otp"
+            }
+        }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            current.plan_update(&image, &desired),
+            Err(Error::UpdateNotPossible(_))
+        ));
+    }
+}
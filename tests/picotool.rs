@@ -0,0 +1,112 @@
+// Copyright (C) 2025 Piers Finlayson <piers@piers.rocks>
+//
+// MIT License
+
+//! Checks the OTP rows generated in `picotool` compatibility mode against the
+//! corpus in `tests/picotool/`.  Each `<name>.json` white label file has a
+//! `<name>.rows.json` file holding the expected USB boot flags and rows,
+//! derived from `picotool`'s encoding rules.  See `tests/picotool/README.md`.
+
+#![cfg(feature = "serde_json")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use pico_otp::{OtpData, WhiteLabelStruct};
+
+const CORPUS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/picotool");
+
+// Returns the white label JSON files in the corpus, sorted by name.
+fn corpus() -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(CORPUS_DIR)
+        .expect("Failed to read corpus directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            name.ends_with(".json") && !name.ends_with(".rows.json")
+        })
+        .collect();
+    files.sort();
+    assert!(!files.is_empty(), "No corpus files found");
+    files
+}
+
+// Reads the expected USB boot flags and rows for the given white label file.
+fn expected(path: &Path) -> (u32, Vec<u16>) {
+    let path = path.with_extension("rows.json");
+    let json = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read {}: {e}", path.display()));
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let hex = |value: &serde_json::Value| {
+        let value = value.as_str().unwrap();
+        u32::from_str_radix(value.strip_prefix("0x").unwrap(), 16).unwrap()
+    };
+    let flags = hex(&value["usb_boot_flags"]);
+    let rows = value["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| u16::try_from(hex(row)).unwrap())
+        .collect();
+    (flags, rows)
+}
+
+#[test]
+fn test_picotool_corpus() {
+    for path in corpus() {
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let json = fs::read_to_string(&path).unwrap();
+        let (flags, rows) = expected(&path);
+
+        let otp_data = OtpData::from_json_picotool(&json)
+            .unwrap_or_else(|e| panic!("{name}: failed to generate OTP data: {e}"));
+        assert_eq!(
+            otp_data.usb_boot_flags(),
+            flags,
+            "{name}: USB boot flags differ"
+        );
+        assert_eq!(
+            otp_data.rows().len(),
+            rows.len(),
+            "{name}: row count differs"
+        );
+        for (ii, (actual, expected)) in otp_data.rows().iter().zip(rows.iter()).enumerate() {
+            assert_eq!(
+                actual, expected,
+                "{name}: row {ii} is {actual:#06x}, expected {expected:#06x}"
+            );
+        }
+
+        // The expected rows decode back to the same white label data, with
+        // strict checking
+        let wls = WhiteLabelStruct::try_from(&OtpData::new(flags, rows, true))
+            .unwrap_or_else(|e| panic!("{name}: failed to parse rows: {e}"));
+        assert_eq!(
+            wls.to_json().unwrap(),
+            WhiteLabelStruct::from_json_picotool(&json)
+                .unwrap()
+                .to_json()
+                .unwrap(),
+            "{name}: parsed rows differ"
+        );
+    }
+}
+
+#[test]
+fn test_picotool_differences() {
+    // pico-otp's default BCD conversion differs for some values
+    let json = fs::read_to_string(Path::new(CORPUS_DIR).join("complete.json")).unwrap();
+    let picotool = WhiteLabelStruct::from_json_picotool(&json).unwrap();
+    let default = WhiteLabelStruct::from_json(&json).unwrap();
+    assert_eq!(picotool.bcd_device(), Some(0x0215));
+    assert_eq!(default.bcd_device(), Some(0x0214));
+
+    // picotool rejects data which only fits with the longest string last
+    let long = "x".repeat(127);
+    let utf16 = "号".repeat(30);
+    let json = format!(
+        r#"{{"device": {{"manufacturer": "{utf16}", "product": "{utf16}"}}, "volume": {{"redirect_url": "{long}", "redirect_name": "{long}", "model": "{long}", "board_id": "abc"}}}}"#,
+    );
+    assert!(OtpData::from_json_picotool(&json).is_err());
+    assert!(OtpData::from_json(&json).is_ok());
+}